@@ -154,6 +154,9 @@ GRAFANA_PASSWORD=mealtracker_admin_$(date +%s)
 JWT_SECRET=$(openssl rand -hex 32)
 JWT_EXPIRATION_HOURS=24
 
+# Secret for email verification and password reset links
+ACTION_TOKEN_SECRET=$(openssl rand -hex 32)
+
 # External APIs (add your API keys here)
 NUTRITION_API_KEYS=
 
@@ -164,6 +167,14 @@ EOF
     else
         print_warning ".env file already exists, using existing configuration"
     fi
+
+    # The gateway refuses to start in production without a signing key
+    if ! ls keys/*.pem >/dev/null 2>&1; then
+        print_status "Generating JWT signing key..."
+        mkdir -p keys
+        openssl genpkey -algorithm ed25519 -out "keys/$(date +%Y%m%d).pem"
+        print_success "JWT signing key written to ./keys"
+    fi
 }
 
 # Function to stop existing services
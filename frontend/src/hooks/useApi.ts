@@ -1,5 +1,5 @@
 import { useState, useEffect, useCallback } from 'react';
-import axios, { AxiosError } from 'axios';
+import axios, { AxiosError, InternalAxiosRequestConfig } from 'axios';
 
 interface UseApiResult<T> {
   data: T | null;
@@ -34,15 +34,61 @@ apiClient.interceptors.request.use(
   (error) => Promise.reject(error)
 );
 
-// Response interceptor for error handling
+const clearSession = () => {
+  localStorage.removeItem('authToken');
+  localStorage.removeItem('refreshToken');
+  window.location.href = '/login';
+};
+
+// Access tokens are short-lived; concurrent 401s share a single refresh call
+// because each refresh token can only be redeemed once.
+let refreshInFlight: Promise<string> | null = null;
+
+// Credential endpoints answer 401 for bad input, not for an expired token
+const CREDENTIAL_ENDPOINTS = ['/auth/login', '/auth/register', '/auth/refresh', '/auth/2fa/verify'];
+
+const refreshAccessToken = (): Promise<string> => {
+  if (!refreshInFlight) {
+    const refreshToken = localStorage.getItem('refreshToken');
+    refreshInFlight = (refreshToken
+      ? axios.post(`${API_BASE_URL}/auth/refresh`, { refresh_token: refreshToken }).then((res) => {
+          localStorage.setItem('authToken', res.data.access_token);
+          localStorage.setItem('refreshToken', res.data.refresh_token);
+          return res.data.access_token as string;
+        })
+      : Promise.reject(new Error('No refresh token'))
+    ).finally(() => {
+      refreshInFlight = null;
+    });
+  }
+  return refreshInFlight;
+};
+
+// Response interceptor: refresh the access token once on 401, then retry
 apiClient.interceptors.response.use(
   (response) => response,
-  (error: AxiosError) => {
-    if (error.response?.status === 401) {
-      localStorage.removeItem('authToken');
-      window.location.href = '/login';
+  async (error: AxiosError) => {
+    const original = error.config as (InternalAxiosRequestConfig & { _retried?: boolean }) | undefined;
+    if (error.response?.status !== 401 || !original) {
+      return Promise.reject(error);
+    }
+    if (CREDENTIAL_ENDPOINTS.includes(original.url ?? '')) {
+      return Promise.reject(error);
+    }
+    if (original._retried) {
+      clearSession();
+      return Promise.reject(error);
+    }
+
+    original._retried = true;
+    try {
+      const token = await refreshAccessToken();
+      original.headers.Authorization = `Bearer ${token}`;
+      return apiClient.request(original);
+    } catch {
+      clearSession();
+      return Promise.reject(error);
     }
-    return Promise.reject(error);
   }
 );
 
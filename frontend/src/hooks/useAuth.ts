@@ -13,7 +13,7 @@ interface UseAuthReturn {
   token: RootState['auth']['token'];
   isAuthenticated: boolean;
   loading: boolean;
-  login: (token: string, user?: User) => void;
+  login: (token: string, user?: User, refreshToken?: string) => void;
   logout: () => void;
 }
 
@@ -25,14 +25,14 @@ export const useAuth = (): UseAuthReturn => {
     dispatch(logoutAction());
   };
   
-  const login = (token: string, user?: User) => {
+  const login = (token: string, user?: User, refreshToken?: string) => {
     dispatch(loginStart());
     const mockUser = user || {
       id: '1',
       username: 'user',
       email: 'user@example.com',
     };
-    dispatch(loginSuccess({ user: mockUser, token }));
+    dispatch(loginSuccess({ user: mockUser, token, refreshToken }));
   };
 
   return {
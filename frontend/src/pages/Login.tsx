@@ -11,6 +11,20 @@ const Login: React.FC = () => {
   const [password, setPassword] = useState('');
   const [error, setError] = useState<string | null>(null);
   const [loading, setLoading] = useState(false);
+  const [challengeToken, setChallengeToken] = useState<string | null>(null);
+  const [code, setCode] = useState('');
+  const [useRecoveryCode, setUseRecoveryCode] = useState(false);
+
+  // Accounts with 2FA get a challenge token instead of a session on the first step
+  const completeLogin = (data: any) => {
+    if (data.status === '2fa_required') {
+      setChallengeToken(data.challenge_token);
+      setCode('');
+      return;
+    }
+    login(data.access_token, data.user, data.refresh_token);
+    navigate('/dashboard');
+  };
 
   const handleLogin = async (e: React.FormEvent) => {
     e.preventDefault();
@@ -18,8 +32,7 @@ const Login: React.FC = () => {
     setLoading(true);
     try {
       const res = await apiClient.post('/auth/login', { email, password });
-      login(res.data.token, res.data.user);
-      navigate('/dashboard');
+      completeLogin(res.data);
     } catch (err: any) {
       setError(err?.response?.data?.message || 'Login failed');
     } finally {
@@ -27,6 +40,32 @@ const Login: React.FC = () => {
     }
   };
 
+  const handleVerifyCode = async (e: React.FormEvent) => {
+    e.preventDefault();
+    setError(null);
+    setLoading(true);
+    try {
+      const res = await apiClient.post('/auth/2fa/verify', {
+        challenge_token: challengeToken,
+        ...(useRecoveryCode ? { recovery_code: code.trim() } : { code: code.trim() }),
+      });
+      completeLogin(res.data);
+    } catch (err: any) {
+      setError(err?.response?.status === 429
+        ? 'Too many attempts, try again later'
+        : err?.response?.data?.message || 'Invalid or expired code');
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const cancelTwoFactor = () => {
+    setChallengeToken(null);
+    setCode('');
+    setUseRecoveryCode(false);
+    setError(null);
+  };
+
   const handleDemoLogin = async () => {
     setError(null);
     setLoading(true);
@@ -35,16 +74,9 @@ const Login: React.FC = () => {
         email: 'demo@mealprep.com',
         password: 'demopass'
       });
-      login(res.data.token, res.data.user);
-      navigate('/dashboard');
-    } catch {
-      // fallback: just set a demo token and user
-      login('demo-token', {
-        id: 'demo',
-        username: 'Demo User',
-        email: 'demo@mealprep.com'
-      });
-      navigate('/dashboard');
+      completeLogin(res.data);
+    } catch (err: any) {
+      setError(err?.response?.data?.message || 'Demo mode is not enabled on this server');
     } finally {
       setLoading(false);
     }
@@ -56,6 +88,47 @@ const Login: React.FC = () => {
         <Typography variant="h4" align="center" gutterBottom>
           Login
         </Typography>
+        {challengeToken ? (
+        <Box component="form" onSubmit={handleVerifyCode} mt={2}>
+          <Typography variant="body1" gutterBottom>
+            {useRecoveryCode
+              ? 'Enter one of your recovery codes.'
+              : 'Enter the 6-digit code from your authenticator app.'}
+          </Typography>
+          <TextField
+            label={useRecoveryCode ? 'Recovery code' : 'Authentication code'}
+            fullWidth
+            margin="normal"
+            required
+            autoFocus
+            value={code}
+            onChange={e => setCode(e.target.value)}
+            autoComplete="one-time-code"
+            inputProps={useRecoveryCode ? undefined : { inputMode: 'numeric', maxLength: 6 }}
+          />
+          {error && <Alert severity="error" sx={{ mt: 2 }}>{error}</Alert>}
+          <Button
+            type="submit"
+            variant="contained"
+            color="primary"
+            fullWidth
+            sx={{ mt: 2 }}
+            disabled={loading}
+          >
+            {loading ? 'Verifying...' : 'Verify'}
+          </Button>
+          <Button
+            fullWidth
+            sx={{ mt: 2 }}
+            onClick={() => { setUseRecoveryCode(!useRecoveryCode); setCode(''); }}
+          >
+            {useRecoveryCode ? 'Use authenticator app instead' : 'Use a recovery code instead'}
+          </Button>
+          <Button fullWidth sx={{ mt: 1 }} onClick={cancelTwoFactor}>
+            Back to login
+          </Button>
+        </Box>
+        ) : (
         <Box component="form" onSubmit={handleLogin} mt={2}>
           <TextField
             label="Email"
@@ -106,6 +179,7 @@ const Login: React.FC = () => {
             Don't have an account? Register
           </Button>
         </Box>
+        )}
       </Paper>
     </Container>
   );
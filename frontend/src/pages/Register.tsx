@@ -19,7 +19,7 @@ const Register: React.FC = () => {
     setLoading(true);
     try {
       const res = await apiClient.post('/auth/register', { username, email, password });
-      login(res.data.token, res.data.user);
+      login(res.data.access_token, res.data.user, res.data.refresh_token);
       navigate('/dashboard');
     } catch (err: any) {
       setError(err?.response?.data?.message || 'Registration failed');
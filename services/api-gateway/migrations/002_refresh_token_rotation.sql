@@ -0,0 +1,10 @@
+-- Refresh token rotation
+-- Every login starts a token family; each refresh replaces the presented token
+-- with a new one in the same family so reuse of a rotated token can be detected.
+
+ALTER TABLE refresh_tokens ADD COLUMN family_id UUID NOT NULL DEFAULT gen_random_uuid();
+ALTER TABLE refresh_tokens ADD COLUMN replaced_by UUID REFERENCES refresh_tokens(id) ON DELETE SET NULL;
+ALTER TABLE refresh_tokens ADD COLUMN revoked_at TIMESTAMPTZ;
+
+CREATE UNIQUE INDEX idx_refresh_tokens_token_hash ON refresh_tokens(token_hash);
+CREATE INDEX idx_refresh_tokens_family_id ON refresh_tokens(family_id);
@@ -0,0 +1,19 @@
+-- Roles and coach access
+-- users.role is one of user, coach (dietitian) or admin. Clients grant coaches
+-- read-only access to their data through coach_client_grants.
+
+UPDATE users SET role = 'user' WHERE role IS NULL OR role NOT IN ('user', 'coach', 'dietitian', 'admin');
+ALTER TABLE users ALTER COLUMN role SET NOT NULL;
+ALTER TABLE users ADD CONSTRAINT users_role_check CHECK (role IN ('user', 'coach', 'dietitian', 'admin'));
+
+CREATE TABLE coach_client_grants (
+    coach_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    client_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    granted_at TIMESTAMPTZ DEFAULT NOW(),
+    PRIMARY KEY (coach_id, client_id)
+);
+
+CREATE INDEX idx_coach_client_grants_client_id ON coach_client_grants(client_id);
+
+-- Catch-all partition so request logging keeps working past the yearly partitions
+CREATE TABLE api_requests_default PARTITION OF api_requests DEFAULT;
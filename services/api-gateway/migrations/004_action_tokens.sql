@@ -0,0 +1,14 @@
+-- One-time tokens for email verification and password reset links
+-- The token itself is HMAC-signed; this table only makes each token redeemable once.
+
+CREATE TABLE action_tokens (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    purpose VARCHAR(32) NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX idx_action_tokens_user_purpose ON action_tokens(user_id, purpose);
+CREATE INDEX idx_action_tokens_expires_at ON action_tokens(expires_at);
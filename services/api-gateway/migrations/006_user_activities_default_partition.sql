@@ -0,0 +1,2 @@
+-- Catch-all partition so activity logging (e.g. login lockouts) keeps working past the yearly partitions
+CREATE TABLE user_activities_default PARTITION OF user_activities DEFAULT;
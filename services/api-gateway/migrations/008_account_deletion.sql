@@ -0,0 +1,13 @@
+-- Account deletion with a grace period
+-- A deletion request only schedules the purge; the account can be restored until
+-- deletion_scheduled_for. Activity rows outlive the user in anonymised form, like api_requests.
+
+ALTER TABLE users ADD COLUMN deletion_requested_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN deletion_scheduled_for TIMESTAMPTZ;
+
+CREATE INDEX idx_users_deletion_scheduled_for ON users(deletion_scheduled_for) WHERE deletion_scheduled_for IS NOT NULL;
+
+ALTER TABLE user_activities ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE user_activities DROP CONSTRAINT IF EXISTS user_activities_user_id_fkey;
+ALTER TABLE user_activities ADD CONSTRAINT user_activities_user_id_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL;
@@ -0,0 +1,40 @@
+-- Ingredient nutrition database imported from USDA FoodData Central (SR Legacy and Foundation Foods)
+-- All amounts are per 100 g of edible portion. Loaded with `nutrition-service import-fdc <path>`.
+
+CREATE TABLE foods (
+    fdc_id INTEGER PRIMARY KEY,
+    data_type VARCHAR(32) NOT NULL,
+    description TEXT NOT NULL,
+    food_category VARCHAR(255),
+    calories_kcal REAL,
+    protein_g REAL,
+    carbohydrates_g REAL,
+    fat_g REAL,
+    fiber_g REAL,
+    sugar_g REAL,
+    sodium_mg REAL,
+    imported_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX idx_foods_description ON foods(lower(description));
+
+-- Micronutrients, stored under the names and units used by the dietary reference intakes
+CREATE TABLE food_nutrients (
+    fdc_id INTEGER NOT NULL REFERENCES foods(fdc_id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    unit VARCHAR(10) NOT NULL,
+    amount_per_100g REAL NOT NULL,
+    PRIMARY KEY (fdc_id, name)
+);
+
+-- Household measures ("1 cup, chopped", "1 large") and their weight
+CREATE TABLE food_portions (
+    id SERIAL PRIMARY KEY,
+    fdc_id INTEGER NOT NULL REFERENCES foods(fdc_id) ON DELETE CASCADE,
+    amount REAL,
+    measure_unit VARCHAR(50),
+    modifier TEXT,
+    gram_weight REAL NOT NULL
+);
+
+CREATE INDEX idx_food_portions_fdc_id ON food_portions(fdc_id);
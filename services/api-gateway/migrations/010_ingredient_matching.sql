@@ -0,0 +1,55 @@
+-- Fuzzy ingredient matching
+-- Trigram index for candidate search, and synonyms for names that share no words with the
+-- FoodData Central description. A synonym either pins a food or rewrites the name.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX idx_foods_description_trgm ON foods USING gin (lower(description) gin_trgm_ops);
+
+CREATE TABLE ingredient_synonyms (
+    synonym TEXT PRIMARY KEY,
+    canonical_name TEXT NOT NULL,
+    fdc_id INTEGER REFERENCES foods(fdc_id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+INSERT INTO ingredient_synonyms (synonym, canonical_name) VALUES
+    ('scallion', 'onions spring or scallions'),
+    ('green onion', 'onions spring or scallions'),
+    ('spring onion', 'onions spring or scallions'),
+    ('cilantro', 'coriander leaves'),
+    ('courgette', 'squash summer zucchini'),
+    ('zucchini', 'squash summer zucchini'),
+    ('aubergine', 'eggplant'),
+    ('garbanzo bean', 'chickpeas'),
+    ('garbanzo', 'chickpeas'),
+    ('chickpea', 'chickpeas'),
+    ('capsicum', 'peppers sweet'),
+    ('bell pepper', 'peppers sweet'),
+    ('rocket', 'arugula'),
+    ('prawn', 'crustaceans shrimp'),
+    ('shrimp', 'crustaceans shrimp'),
+    ('ground beef', 'beef ground'),
+    ('minced beef', 'beef ground'),
+    ('beef mince', 'beef ground'),
+    ('chicken breast', 'chicken broilers or fryers breast meat only'),
+    ('chicken thigh', 'chicken broilers or fryers thigh meat only'),
+    ('olive oil', 'oil olive salad or cooking'),
+    ('egg', 'egg whole'),
+    ('butter', 'butter salted'),
+    ('milk', 'milk whole'),
+    ('oat', 'oats'),
+    ('rolled oat', 'oats'),
+    ('white rice', 'rice white long-grain regular'),
+    ('brown rice', 'rice brown long-grain'),
+    ('all-purpose flour', 'wheat flour white all-purpose'),
+    ('plain flour', 'wheat flour white all-purpose'),
+    ('icing sugar', 'sugars powdered'),
+    ('powdered sugar', 'sugars powdered'),
+    ('caster sugar', 'sugars granulated'),
+    ('sugar', 'sugars granulated'),
+    ('beetroot', 'beets'),
+    ('swede', 'rutabagas'),
+    ('mangetout', 'peas edible-podded'),
+    ('snow pea', 'peas edible-podded'),
+    ('coriander seed', 'spices coriander seed');
@@ -0,0 +1,7 @@
+-- Glycemic index per food (glucose = 100)
+-- Filled by `nutrition-service import-fdc` from the reference values bundled with the service
+-- (source 'reference' or 'category'); rows with any other source, e.g. 'manual', are left alone.
+
+ALTER TABLE foods
+    ADD COLUMN glycemic_index REAL CHECK (glycemic_index >= 0 AND glycemic_index <= 150),
+    ADD COLUMN glycemic_index_source VARCHAR(32);
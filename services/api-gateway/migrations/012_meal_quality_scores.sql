@@ -0,0 +1,12 @@
+-- Meal quality scoring
+-- Saturated fat per 100 g (FDC nutrient 606), filled by `nutrition-service import-fdc`; the
+-- Nutri-Score and NRF9.3 both penalise it.
+-- recipe_nutrition.nutrition_score holds the 0-100 score computed by the nutrition service,
+-- which DECIMAL(3,2) could not.
+
+ALTER TABLE foods
+    ADD COLUMN saturated_fat_g REAL;
+
+ALTER TABLE recipe_nutrition
+    ALTER COLUMN nutrition_score TYPE DECIMAL(5,2),
+    ADD CONSTRAINT recipe_nutrition_score_range CHECK (nutrition_score >= 0 AND nutrition_score <= 100);
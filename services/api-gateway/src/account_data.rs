@@ -0,0 +1,162 @@
+// Account data portability and erasure: zip export of everything stored about a user,
+// and scheduled deletion that purges the account once its grace period has passed
+
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::io::{Cursor, Write};
+use uuid::Uuid;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+/// Days between a deletion request and the purge; the user can cancel until then.
+pub const DELETION_GRACE_DAYS: i64 = 30;
+const PURGE_INTERVAL_SECONDS: u64 = 3600;
+
+/// One file per table. Credentials (password and token hashes) are left out.
+const EXPORT_QUERIES: &[(&str, &str)] = &[
+    ("users", "SELECT id, username, email, email_verified, is_active, role, created_at, updated_at, deletion_requested_at, deletion_scheduled_for FROM users WHERE id = $1"),
+    ("user_profiles", "SELECT * FROM user_profiles WHERE user_id = $1"),
+    ("user_preferences", "SELECT * FROM user_preferences WHERE user_id = $1"),
+    ("nutrition_goals", "SELECT * FROM nutrition_goals WHERE user_id = $1 ORDER BY start_date"),
+    ("daily_nutrition", "SELECT * FROM daily_nutrition WHERE user_id = $1 ORDER BY date"),
+    ("meal_logs", "SELECT * FROM meal_logs WHERE user_id = $1 ORDER BY logged_at"),
+    ("meal_plans", "SELECT * FROM meal_plans WHERE user_id = $1 ORDER BY start_date"),
+    ("planned_meals", "SELECT pm.* FROM planned_meals pm JOIN meal_plans mp ON mp.id = pm.meal_plan_id WHERE mp.user_id = $1 ORDER BY pm.scheduled_date"),
+    ("recipes", "SELECT * FROM recipes WHERE user_id = $1 ORDER BY created_at"),
+    ("recipe_ingredients", "SELECT ri.* FROM recipe_ingredients ri JOIN recipes r ON r.id = ri.recipe_id WHERE r.user_id = $1 ORDER BY ri.recipe_id, ri.order_index"),
+    ("recipe_steps", "SELECT rs.* FROM recipe_steps rs JOIN recipes r ON r.id = rs.recipe_id WHERE r.user_id = $1 ORDER BY rs.recipe_id, rs.step_number"),
+    ("recipe_nutrition", "SELECT rn.* FROM recipe_nutrition rn JOIN recipes r ON r.id = rn.recipe_id WHERE r.user_id = $1"),
+    ("shopping_lists", "SELECT * FROM shopping_lists WHERE user_id = $1 ORDER BY created_at"),
+    ("shopping_items", "SELECT si.* FROM shopping_items si JOIN shopping_lists sl ON sl.id = si.shopping_list_id WHERE sl.user_id = $1 ORDER BY si.created_at"),
+    ("coach_client_grants", "SELECT * FROM coach_client_grants WHERE client_id = $1 OR coach_id = $1"),
+    ("user_sessions", "SELECT id, ip_address, user_agent, expires_at, is_active, created_at, last_accessed FROM user_sessions WHERE user_id = $1 ORDER BY created_at"),
+    ("api_keys", "SELECT id, name, prefix, scopes, expires_at, last_used_at, revoked_at, created_at FROM api_keys WHERE user_id = $1 ORDER BY created_at"),
+    ("user_activities", "SELECT * FROM user_activities WHERE user_id = $1 ORDER BY created_at"),
+    ("api_requests", "SELECT * FROM api_requests WHERE user_id = $1 ORDER BY created_at"),
+];
+
+/// Log-like tables are also exported as CSV for spreadsheets.
+const CSV_TABLES: &[&str] = &["daily_nutrition", "meal_logs", "user_activities", "api_requests"];
+
+async fn table_rows(db: &PgPool, query: &str, user_id: Uuid) -> Result<Vec<Value>> {
+    let (rows,): (Value,) = sqlx::query_as(&format!("SELECT COALESCE(json_agg(t), '[]'::json) FROM ({}) t", query))
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+    Ok(serde_json::from_value(rows)?)
+}
+
+fn to_csv(rows: &[Value]) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let Some(Value::Object(first)) = rows.first() else {
+        return Ok(writer.into_inner()?);
+    };
+    let columns: Vec<&String> = first.keys().collect();
+    writer.write_record(&columns)?;
+
+    for row in rows {
+        writer.write_record(columns.iter().map(|column| match row.get(column.as_str()) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+        }))?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// Builds a zip with `<table>.json` for every table and `<table>.csv` for the logs.
+pub async fn export_archive(db: &PgPool, user_id: Uuid) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut files = Vec::new();
+
+    for (table, query) in EXPORT_QUERIES {
+        let rows = table_rows(db, query, user_id).await?;
+
+        let name = format!("{}.json", table);
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&rows)?)?;
+        files.push(name);
+
+        if CSV_TABLES.contains(table) {
+            let name = format!("{}.csv", table);
+            zip.start_file(name.as_str(), options)?;
+            zip.write_all(&to_csv(&rows)?)?;
+            files.push(name);
+        }
+    }
+
+    let manifest = json!({
+        "user_id": user_id,
+        "exported_at": Utc::now(),
+        "files": files,
+    });
+    zip.start_file("manifest.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Erases a user: log rows in the partitioned `api_requests` / `user_activities` tables are
+/// kept for aggregate statistics but stripped of anything identifying, then deleting the
+/// user row cascades to everything else they own.
+pub async fn purge_account(db: &PgPool, user_id: Uuid) -> Result<()> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE api_requests SET user_id = NULL, ip_address = NULL, user_agent = NULL WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "UPDATE user_activities SET user_id = NULL, activity_data = NULL, ip_address = NULL, user_agent = NULL \
+         WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Purges every account whose grace period has ended. Returns how many were removed.
+///
+/// A failing account is logged and skipped so it cannot hold up everyone queued after it;
+/// it is retried on the next run.
+pub async fn purge_due_accounts(db: &PgPool) -> Result<usize> {
+    let due: Vec<(Uuid,)> = sqlx::query_as("SELECT id FROM users WHERE deletion_scheduled_for <= NOW()")
+        .fetch_all(db)
+        .await?;
+
+    let mut purged = 0;
+    for (user_id,) in &due {
+        match purge_account(db, *user_id).await {
+            Ok(()) => {
+                purged += 1;
+                tracing::info!("Purged account {} after its deletion grace period", user_id);
+            }
+            Err(e) => tracing::error!("Failed to purge account {}: {}", user_id, e),
+        }
+    }
+    Ok(purged)
+}
+
+/// Runs `purge_due_accounts` hourly for the lifetime of the process.
+pub fn spawn_purge_task(db: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(PURGE_INTERVAL_SECONDS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = purge_due_accounts(&db).await {
+                tracing::error!("Account purge failed: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,134 @@
+// Signed, expiring, one-time tokens for links sent by email (verify email, reset password)
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::jwt_keys;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl TokenPurpose {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::VerifyEmail => "verify_email",
+            TokenPurpose::ResetPassword => "reset_password",
+        }
+    }
+
+    fn ttl(&self) -> Duration {
+        match self {
+            TokenPurpose::VerifyEmail => Duration::hours(24),
+            TokenPurpose::ResetPassword => Duration::hours(1),
+        }
+    }
+}
+
+/// Email links are not JWTs, so they keep a symmetric key of their own (`ACTION_TOKEN_SECRET`).
+fn secret() -> Result<String> {
+    match std::env::var("ACTION_TOKEN_SECRET") {
+        Ok(secret) if !secret.is_empty() => Ok(secret),
+        _ if jwt_keys::dev_mode() => Ok("action_token_secret_for_development".to_string()),
+        _ => anyhow::bail!("ACTION_TOKEN_SECRET must be set outside development"),
+    }
+}
+
+/// Fails at startup rather than on the first verification email.
+pub fn ensure_configured() -> Result<()> {
+    secret().map(|_| ())
+}
+
+fn mac() -> Result<HmacSha256> {
+    Ok(HmacSha256::new_from_slice(secret()?.as_bytes()).expect("HMAC accepts any key length"))
+}
+
+fn sign(payload: &str) -> Result<String> {
+    let mut mac = mac()?;
+    mac.update(payload.as_bytes());
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Signs a fresh token for `purpose`, returning its id, expiry and wire form.
+///
+/// Format: `<token id>.<expiry unix seconds>.<hmac>`; the signature covers the purpose too,
+/// so a verify-email token cannot be replayed as a password reset.
+pub(crate) fn mint(purpose: TokenPurpose) -> Result<(Uuid, DateTime<Utc>, String)> {
+    let id = Uuid::new_v4();
+    let expires_at = Utc::now() + purpose.ttl();
+    let payload = format!("{}.{}", id, expires_at.timestamp());
+    let token = format!("{}.{}", payload, sign(&format!("{}:{}", purpose.as_str(), payload))?);
+    Ok((id, expires_at, token))
+}
+
+/// Checks signature and expiry without touching the database. Returns the token id.
+pub(crate) fn verify(token: &str, purpose: TokenPurpose) -> Result<Option<Uuid>> {
+    let mut parts = token.rsplitn(2, '.');
+    let (Some(signature), Some(payload)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+
+    let mut mac = mac()?;
+    mac.update(format!("{}:{}", purpose.as_str(), payload).as_bytes());
+    let Ok(signature) = hex::decode(signature) else {
+        return Ok(None);
+    };
+    if mac.verify_slice(&signature).is_err() {
+        return Ok(None);
+    }
+
+    let Some((id, expires)) = payload.split_once('.') else {
+        return Ok(None);
+    };
+    let (Ok(id), Ok(expires)) = (id.parse::<Uuid>(), expires.parse::<i64>()) else {
+        return Ok(None);
+    };
+    if expires <= Utc::now().timestamp() {
+        return Ok(None);
+    }
+    Ok(Some(id))
+}
+
+/// Creates a token for `user_id` and records it so it can be redeemed exactly once.
+pub async fn issue(db: &PgPool, user_id: Uuid, purpose: TokenPurpose) -> Result<String> {
+    let (id, expires_at, token) = mint(purpose)?;
+
+    sqlx::query("INSERT INTO action_tokens (id, user_id, purpose, expires_at) VALUES ($1, $2, $3, $4)")
+        .bind(id)
+        .bind(user_id)
+        .bind(purpose.as_str())
+        .bind(expires_at)
+        .execute(db)
+        .await?;
+
+    Ok(token)
+}
+
+/// Checks signature and expiry, then marks the token used. Returns the user it was issued for,
+/// or `None` if the token is forged, expired or already used.
+pub async fn redeem(db: &PgPool, token: &str, purpose: TokenPurpose) -> Result<Option<Uuid>> {
+    let Some(id) = verify(token, purpose)? else {
+        return Ok(None);
+    };
+
+    let user: Option<(Uuid,)> = sqlx::query_as(
+        "UPDATE action_tokens SET used_at = NOW() \
+         WHERE id = $1 AND purpose = $2 AND used_at IS NULL AND expires_at > NOW() \
+         RETURNING user_id",
+    )
+    .bind(id)
+    .bind(purpose.as_str())
+    .fetch_optional(db)
+    .await?;
+
+    Ok(user.map(|(user_id,)| user_id))
+}
+
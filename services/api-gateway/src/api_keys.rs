@@ -0,0 +1,170 @@
+// Personal API keys: long-lived, scoped credentials for scripts and integrations
+//
+// A key is accepted by `auth_middleware` in place of a Bearer JWT, but only for the
+// data areas it was granted (`nutrition:write`, `inventory:read`, ...). Account, session,
+// key management, coach and admin routes always require a real login.
+
+use anyhow::Result;
+use axum::http::Method;
+use rand::RngCore;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::{hash_token, AuthUser, Role};
+
+pub const KEY_PREFIX: &str = "mpk_";
+
+/// Route prefixes that API keys can be scoped to, and the area name used in scopes.
+const AREAS: &[(&str, &str)] = &[
+    ("/api/meals", "meals"),
+    ("/api/meal-plans", "meal_plans"),
+    ("/api/nutrition", "nutrition"),
+    ("/api/shopping-lists", "shopping"),
+    ("/api/analytics", "analytics"),
+    ("/api/recipes", "recipes"),
+    ("/api/inventory", "inventory"),
+    ("/api/users/profile", "profile"),
+    ("/api/users/preferences", "profile"),
+];
+
+fn matches_prefix(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// `area:read` or `area:write`; write access includes read.
+pub fn is_valid_scope(scope: &str) -> bool {
+    let Some((area, access)) = scope.split_once(':') else {
+        return false;
+    };
+    AREAS.iter().any(|(_, a)| *a == area) && matches!(access, "read" | "write")
+}
+
+/// Scope a request needs, or `None` if the route is off limits to API keys.
+pub fn required_scope(method: &Method, path: &str) -> Option<String> {
+    let (_, area) = AREAS.iter().find(|(prefix, _)| matches_prefix(path, prefix))?;
+    let access = if matches!(*method, Method::GET | Method::HEAD) { "read" } else { "write" };
+    Some(format!("{}:{}", area, access))
+}
+
+fn has_scope(scopes: &[String], required: &str) -> bool {
+    scopes.iter().any(|s| s == required)
+        || required
+            .strip_suffix(":read")
+            .is_some_and(|area| scopes.iter().any(|s| *s == format!("{}:write", area)))
+}
+
+/// Returns a new plaintext key; it is shown to the user once and only its hash is stored.
+pub fn generate_key() -> String {
+    let mut bytes = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}{}", KEY_PREFIX, hex::encode(bytes))
+}
+
+/// Leading characters stored in the clear so users can recognise their keys.
+pub fn display_prefix(key: &str) -> String {
+    key.chars().take(12).collect()
+}
+
+pub enum KeyCheck {
+    Allowed(AuthUser),
+    /// The key is valid but lacks the scope for this route.
+    Forbidden,
+    Invalid,
+}
+
+/// Looks up `key` and checks that it may be used for `method` + `path`.
+pub async fn authenticate(db: &PgPool, key: &str, method: &Method, path: &str) -> Result<KeyCheck> {
+    let row: Option<(Uuid, Uuid, String, String, Vec<String>)> = sqlx::query_as(
+        "SELECT k.id, u.id, u.email, u.role, k.scopes \
+         FROM api_keys k JOIN users u ON u.id = k.user_id \
+         WHERE k.key_hash = $1 AND k.revoked_at IS NULL \
+           AND (k.expires_at IS NULL OR k.expires_at > NOW()) \
+           AND u.is_active IS NOT FALSE",
+    )
+    .bind(hash_token(key))
+    .fetch_optional(db)
+    .await?;
+
+    let Some((key_id, user_id, email, role, scopes)) = row else {
+        return Ok(KeyCheck::Invalid);
+    };
+
+    let allowed = required_scope(method, path).is_some_and(|required| has_scope(&scopes, &required));
+    if !allowed {
+        return Ok(KeyCheck::Forbidden);
+    }
+
+    sqlx::query("UPDATE api_keys SET last_used_at = NOW() WHERE id = $1")
+        .bind(key_id)
+        .execute(db)
+        .await?;
+
+    Ok(KeyCheck::Allowed(AuthUser {
+        id: user_id,
+        email,
+        role: Role::from(role),
+        session_id: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(granted: &[&str]) -> Vec<String> {
+        granted.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn reads_need_read_and_changes_need_write() {
+        assert_eq!(required_scope(&Method::GET, "/api/nutrition/daily").as_deref(), Some("nutrition:read"));
+        assert_eq!(required_scope(&Method::HEAD, "/api/inventory").as_deref(), Some("inventory:read"));
+        assert_eq!(required_scope(&Method::POST, "/api/nutrition/log").as_deref(), Some("nutrition:write"));
+        assert_eq!(required_scope(&Method::DELETE, "/api/meal-plans/42").as_deref(), Some("meal_plans:write"));
+        assert_eq!(required_scope(&Method::PUT, "/api/users/preferences").as_deref(), Some("profile:write"));
+        // Whole path segments only: /api/meals does not cover /api/meal-plans
+        assert_eq!(required_scope(&Method::GET, "/api/meal-plans").as_deref(), Some("meal_plans:read"));
+        assert_eq!(required_scope(&Method::GET, "/api/mealsx"), None);
+    }
+
+    #[test]
+    fn account_and_admin_routes_are_refused_for_keys() {
+        for path in [
+            "/api/auth/login",
+            "/api/auth/sessions",
+            "/api/auth/2fa/disable",
+            "/api/users/api-keys",
+            "/api/users/api-keys/7",
+            "/api/users/me/export",
+            "/api/users/me/deletion",
+            "/api/users/coaches",
+            "/api/coach/clients",
+            "/api/admin/users",
+            "/api/admin/api-requests",
+        ] {
+            assert_eq!(required_scope(&Method::GET, path), None, "{}", path);
+            assert_eq!(required_scope(&Method::POST, path), None, "{}", path);
+        }
+    }
+
+    #[test]
+    fn write_scope_implies_read() {
+        let granted = scopes(&["nutrition:write", "inventory:read"]);
+        assert!(has_scope(&granted, "nutrition:write"));
+        assert!(has_scope(&granted, "nutrition:read"));
+        assert!(has_scope(&granted, "inventory:read"));
+        assert!(!has_scope(&granted, "inventory:write"));
+        assert!(!has_scope(&granted, "recipes:read"));
+        assert!(!has_scope(&[], "nutrition:read"));
+    }
+
+    #[test]
+    fn validates_scope_names() {
+        assert!(is_valid_scope("meal_plans:read"));
+        assert!(is_valid_scope("profile:write"));
+        assert!(!is_valid_scope("admin:write"));
+        assert!(!is_valid_scope("nutrition:delete"));
+        assert!(!is_valid_scope("nutrition"));
+    }
+}
@@ -1,23 +1,287 @@
-// Authentication module placeholder
+// Authentication helpers: access JWTs and rotating refresh tokens
 
-use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm, TokenData};
+use anyhow::Result;
+use axum::{async_trait, extract::FromRequestParts, http::{request::Parts, StatusCode}};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation, Algorithm, TokenData};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::jwt_keys;
+
+/// Access tokens are short-lived; clients use the refresh token to get a new one.
+pub const ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+pub const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub email: String,
+    #[serde(default)]
+    pub role: Role,
     pub exp: usize,
+    /// Session the token was issued for; revoked sessions are rejected by the middleware.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
+}
+
+/// Value of `users.role`. Coaches (dietitians) get read-only access to clients who granted it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    User,
+    #[serde(alias = "dietitian")]
+    Coach,
+    Admin,
+}
+
+impl From<String> for Role {
+    fn from(value: String) -> Self {
+        match value.to_lowercase().as_str() {
+            "admin" => Role::Admin,
+            "coach" | "dietitian" => Role::Coach,
+            _ => Role::User,
+        }
+    }
+}
+
+/// The authenticated caller, put into the request extensions by `auth_middleware`.
+///
+/// Taking an `AuthUser` argument in a handler makes the route reject unauthenticated requests.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub id: Uuid,
+    pub email: String,
+    pub role: Role,
+    pub session_id: Option<Uuid>,
+}
+
+impl TryFrom<Claims> for AuthUser {
+    type Error = uuid::Error;
+
+    fn try_from(claims: Claims) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: claims.sub.parse()?,
+            email: claims.email,
+            role: claims.role,
+            session_id: claims.sid,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthUser>()
+            .cloned()
+            .ok_or(StatusCode::UNAUTHORIZED)
+    }
+}
+
+fn signing_header() -> Header {
+    let mut header = Header::new(Algorithm::EdDSA);
+    header.kid = Some(jwt_keys::keys().signing_kid().to_string());
+    header
 }
 
-pub fn jwt_secret() -> String {
-    std::env::var("JWT_SECRET").unwrap_or_else(|_| "super_secret_key_for_development".to_string())
+/// Verifies a gateway-issued token with the key named by its `kid` header.
+fn verify<T: serde::de::DeserializeOwned>(token: &str, validation: &Validation) -> Option<TokenData<T>> {
+    let kid = decode_header(token).ok()?.kid?;
+    let key = jwt_keys::keys().decoding_key(&kid)?;
+    decode::<T>(token, key, validation).ok()
 }
 
 pub fn validate_jwt(token: &str) -> Option<TokenData<Claims>> {
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(jwt_secret().as_bytes()),
-        &Validation::new(Algorithm::HS256),
-    ).ok()
+    verify(token, &Validation::new(Algorithm::EdDSA))
+}
+
+/// Issues a signed access JWT and returns it together with its lifetime in seconds.
+pub fn issue_access_token(user_id: Uuid, email: &str, role: Role, sid: Option<Uuid>) -> Result<(String, i64)> {
+    let ttl = Duration::minutes(ACCESS_TOKEN_TTL_MINUTES);
+    let claims = Claims {
+        sub: user_id.to_string(),
+        email: email.to_string(),
+        role,
+        exp: (Utc::now() + ttl).timestamp() as usize,
+        sid,
+    };
+    let token = encode(&signing_header(), &claims, jwt_keys::keys().signing_key())?;
+    Ok((token, ttl.num_seconds()))
+}
+
+pub const TWO_FACTOR_CHALLENGE_TTL_MINUTES: i64 = 5;
+const TWO_FACTOR_AUDIENCE: &str = "2fa_challenge";
+
+/// Proof that the password step succeeded, exchanged for real tokens once the TOTP code checks out.
+///
+/// The audience claim keeps it from ever being accepted as an access token by `validate_jwt`.
+#[derive(Debug, Serialize, Deserialize)]
+struct TwoFactorChallengeClaims {
+    sub: String,
+    aud: String,
+    exp: usize,
+}
+
+pub fn issue_two_factor_challenge(user_id: Uuid) -> Result<(String, i64)> {
+    let ttl = Duration::minutes(TWO_FACTOR_CHALLENGE_TTL_MINUTES);
+    let claims = TwoFactorChallengeClaims {
+        sub: user_id.to_string(),
+        aud: TWO_FACTOR_AUDIENCE.to_string(),
+        exp: (Utc::now() + ttl).timestamp() as usize,
+    };
+    let token = encode(&signing_header(), &claims, jwt_keys::keys().signing_key())?;
+    Ok((token, ttl.num_seconds()))
+}
+
+pub fn validate_two_factor_challenge(token: &str) -> Option<Uuid> {
+    let mut validation = Validation::new(Algorithm::EdDSA);
+    validation.set_audience(&[TWO_FACTOR_AUDIENCE]);
+    verify::<TwoFactorChallengeClaims>(token, &validation)
+        .and_then(|data| data.claims.sub.parse().ok())
+}
+
+/// Only the SHA-256 of opaque tokens is stored, so a database leak does not leak live tokens.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn generate_refresh_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Starts a new token family for a fresh login and returns the opaque refresh token.
+///
+/// The family id doubles as the id of the login's `user_sessions` row.
+pub async fn issue_refresh_token(db: &PgPool, user_id: Uuid, family_id: Uuid) -> Result<String> {
+    let mut tx = db.begin().await?;
+    let (token, _) = insert_refresh_token(&mut tx, user_id, family_id).await?;
+    tx.commit().await?;
+    Ok(token)
+}
+
+async fn insert_refresh_token(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    user_id: Uuid,
+    family_id: Uuid,
+) -> Result<(String, Uuid)> {
+    let token = generate_refresh_token();
+    let id = Uuid::new_v4();
+    let expires_at = Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS);
+
+    sqlx::query("INSERT INTO refresh_tokens (id, user_id, family_id, token_hash, expires_at) VALUES ($1, $2, $3, $4, $5)")
+        .bind(id)
+        .bind(user_id)
+        .bind(family_id)
+        .bind(hash_token(&token))
+        .bind(expires_at)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok((token, id))
+}
+
+#[derive(Debug)]
+pub enum RefreshOutcome {
+    Rotated { user_id: Uuid, family_id: Uuid, refresh_token: String },
+    /// The token was already rotated once; the whole family has been revoked.
+    ReuseDetected { family_id: Uuid },
+    Invalid,
+}
+
+/// Exchanges a refresh token for a new one in the same family.
+///
+/// A refresh token can be used exactly once. Presenting an already-rotated token
+/// means it was copied somewhere, so every token descended from the same login is revoked.
+pub async fn rotate_refresh_token(db: &PgPool, presented: &str) -> Result<RefreshOutcome> {
+    let mut tx = db.begin().await?;
+
+    let row = sqlx::query(
+        "SELECT id, user_id, family_id, expires_at, is_revoked, replaced_by FROM refresh_tokens WHERE token_hash = $1 FOR UPDATE",
+    )
+    .bind(hash_token(presented))
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let row = match row {
+        Some(r) => r,
+        None => return Ok(RefreshOutcome::Invalid),
+    };
+
+    let id: Uuid = row.get("id");
+    let user_id: Uuid = row.get("user_id");
+    let family_id: Uuid = row.get("family_id");
+    let expires_at: chrono::DateTime<Utc> = row.get("expires_at");
+    let is_revoked: Option<bool> = row.get("is_revoked");
+    let replaced_by: Option<Uuid> = row.get("replaced_by");
+
+    // Tokens revoked by logout were never replaced, so they are simply invalid
+    if is_revoked.unwrap_or(false) && replaced_by.is_none() {
+        return Ok(RefreshOutcome::Invalid);
+    }
+
+    if is_revoked.unwrap_or(false) {
+        sqlx::query("UPDATE refresh_tokens SET is_revoked = TRUE, revoked_at = COALESCE(revoked_at, NOW()) WHERE family_id = $1")
+            .bind(family_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        tracing::warn!("Refresh token reuse detected for user {}, revoked family {}", user_id, family_id);
+        return Ok(RefreshOutcome::ReuseDetected { family_id });
+    }
+
+    if expires_at <= Utc::now() {
+        return Ok(RefreshOutcome::Invalid);
+    }
+
+    let (refresh_token, new_id) = insert_refresh_token(&mut tx, user_id, family_id).await?;
+
+    sqlx::query("UPDATE refresh_tokens SET is_revoked = TRUE, revoked_at = NOW(), replaced_by = $2 WHERE id = $1")
+        .bind(id)
+        .bind(new_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE user_sessions SET session_token = $2, last_accessed = NOW(), expires_at = $3 WHERE id = $1")
+        .bind(family_id)
+        .bind(hash_token(&refresh_token))
+        .bind(Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS))
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(RefreshOutcome::Rotated { user_id, family_id, refresh_token })
+}
+
+/// Checks whether `actor` may read data owned by `owner_id`.
+///
+/// Owners and admins may always read; a coach only for clients who have an active
+/// grant in `coach_client_grants`. This is the only way to reach another user's data:
+/// every write endpoint acts on the caller's own id from the token, so coaches never
+/// get a path to modify client data.
+pub async fn authorize_read_access(db: &PgPool, actor: &AuthUser, owner_id: Uuid) -> Result<(), StatusCode> {
+    if actor.id == owner_id || actor.role == Role::Admin {
+        return Ok(());
+    }
+    if actor.role != Role::Coach {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let granted: Option<(Uuid,)> = sqlx::query_as("SELECT client_id FROM coach_client_grants WHERE coach_id = $1 AND client_id = $2")
+        .bind(actor.id)
+        .bind(owner_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    granted.map(|_| ()).ok_or(StatusCode::FORBIDDEN)
 }
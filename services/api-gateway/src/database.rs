@@ -1,8 +1,241 @@
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool, Row};
 use anyhow::Result;
+use chrono::{Datelike, NaiveDate, Utc};
+
+use crate::models::{
+    ActivityLevel, BudgetRange, CookingTimePreference, MealPrepFrequency, NotificationSettings,
+    UserPreferences, UserProfile,
+};
 
 pub async fn initialize_database(pool: &PgPool) -> Result<()> {
     // Database initialization logic would go here
     sqlx::query("SELECT 1").execute(pool).await?;
     Ok(())
 }
+
+pub const DEMO_EMAIL: &str = "demo@mealprep.com";
+pub const DEMO_PASSWORD: &str = "demopass";
+
+/// Demo mode is opt-in via `DEMO_MODE=true` so a production deployment never has a known login.
+pub fn demo_mode_enabled() -> bool {
+    std::env::var("DEMO_MODE")
+        .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Creates (or re-activates) the demo account in demo mode, and disables it otherwise
+/// so an account seeded by an earlier demo deployment cannot be logged into.
+pub async fn sync_demo_account(pool: &PgPool, enabled: bool) -> Result<()> {
+    if !enabled {
+        sqlx::query("UPDATE users SET is_active = FALSE WHERE email = $1")
+            .bind(DEMO_EMAIL)
+            .execute(pool)
+            .await?;
+        return Ok(());
+    }
+
+    let password_hash = bcrypt::hash(DEMO_PASSWORD, bcrypt::DEFAULT_COST)?;
+    sqlx::query(
+        "INSERT INTO users (username, email, password_hash, email_verified, is_active) VALUES ($1, $2, $3, TRUE, TRUE) \
+         ON CONFLICT (email) DO UPDATE SET password_hash = EXCLUDED.password_hash, is_active = TRUE",
+    )
+    .bind("demo")
+    .bind(DEMO_EMAIL)
+    .bind(&password_hash)
+    .execute(pool)
+    .await?;
+
+    tracing::warn!("Demo mode enabled: {} can log in with the published demo password", DEMO_EMAIL);
+    Ok(())
+}
+
+/// Appends an entry to the `user_activities` audit log.
+pub async fn record_user_activity(
+    pool: &PgPool,
+    user_id: uuid::Uuid,
+    activity_type: &str,
+    activity_data: serde_json::Value,
+    client: &crate::sessions::ClientInfo,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO user_activities (user_id, activity_type, activity_data, ip_address, user_agent) VALUES ($1, $2, $3, $4::inet, $5)",
+    )
+    .bind(user_id)
+    .bind(activity_type)
+    .bind(activity_data)
+    .bind(&client.ip_address)
+    .bind(&client.user_agent)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+fn age_on(date_of_birth: NaiveDate, today: NaiveDate) -> i32 {
+    let mut age = today.year() - date_of_birth.year();
+    if (today.month(), today.day()) < (date_of_birth.month(), date_of_birth.day()) {
+        age -= 1;
+    }
+    age
+}
+
+/// Reads a user's profile; users without a `user_profiles` row get the column defaults.
+pub async fn load_user_profile(pool: &PgPool, user_id: uuid::Uuid) -> Result<UserProfile> {
+    type Row = (
+        Option<String>,
+        Option<NaiveDate>,
+        Option<f32>,
+        Option<f32>,
+        Option<ActivityLevel>,
+        Option<Vec<String>>,
+        Option<Vec<String>>,
+        Option<Vec<String>>,
+    );
+    let row: Option<Row> = sqlx::query_as(
+        "SELECT full_name, date_of_birth, height_cm::float4, weight_kg::float4, activity_level, \
+                dietary_restrictions, allergies, health_goals \
+         FROM user_profiles WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some((full_name, date_of_birth, height_cm, weight_kg, activity_level, dietary_restrictions, allergies, health_goals)) = row else {
+        return Ok(UserProfile::default());
+    };
+
+    Ok(UserProfile {
+        full_name: full_name.unwrap_or_default(),
+        age: date_of_birth.map(|dob| age_on(dob, Utc::now().date_naive())),
+        date_of_birth,
+        height_cm,
+        weight_kg,
+        activity_level: activity_level.unwrap_or_default(),
+        dietary_restrictions: dietary_restrictions.unwrap_or_default(),
+        allergies: allergies.unwrap_or_default(),
+        // Unknown goals (e.g. written by an older client) are dropped rather than failing the read
+        health_goals: health_goals
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|goal| serde_json::from_value(serde_json::Value::String(goal)).ok())
+            .collect(),
+    })
+}
+
+pub async fn save_user_profile(executor: impl PgExecutor<'_>, user_id: uuid::Uuid, profile: &UserProfile) -> Result<()> {
+    let health_goals: Vec<String> = profile
+        .health_goals
+        .iter()
+        .filter_map(|goal| serde_json::to_value(goal).ok()?.as_str().map(str::to_string))
+        .collect();
+
+    sqlx::query(
+        "INSERT INTO user_profiles (user_id, full_name, date_of_birth, height_cm, weight_kg, activity_level, \
+                                    dietary_restrictions, allergies, health_goals) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+         ON CONFLICT (user_id) DO UPDATE SET \
+             full_name = EXCLUDED.full_name, date_of_birth = EXCLUDED.date_of_birth, \
+             height_cm = EXCLUDED.height_cm, weight_kg = EXCLUDED.weight_kg, \
+             activity_level = EXCLUDED.activity_level, dietary_restrictions = EXCLUDED.dietary_restrictions, \
+             allergies = EXCLUDED.allergies, health_goals = EXCLUDED.health_goals, updated_at = NOW()",
+    )
+    .bind(user_id)
+    .bind(&profile.full_name)
+    .bind(profile.date_of_birth)
+    .bind(profile.height_cm)
+    .bind(profile.weight_kg)
+    .bind(profile.activity_level)
+    .bind(&profile.dietary_restrictions)
+    .bind(&profile.allergies)
+    .bind(&health_goals)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Reads a user's preferences; users without a `user_preferences` row get the column defaults.
+pub async fn load_user_preferences(pool: &PgPool, user_id: uuid::Uuid) -> Result<UserPreferences> {
+    let row = sqlx::query(
+        "SELECT preferred_cuisines, cooking_time_preference, meal_prep_frequency, \
+                budget_min_per_meal::float4 AS budget_min_per_meal, budget_max_per_meal::float4 AS budget_max_per_meal, \
+                weekly_budget_limit::float4 AS weekly_budget_limit, kitchen_equipment, \
+                notification_meal_reminders, notification_prep_reminders, notification_shopping_reminders, \
+                notification_goal_updates, notification_weekly_summary \
+         FROM user_preferences WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(UserPreferences::default());
+    };
+
+    let flag = |column: &str| -> Result<bool> { Ok(row.try_get::<Option<bool>, _>(column)?.unwrap_or(true)) };
+
+    Ok(UserPreferences {
+        preferred_cuisines: row.try_get::<Option<Vec<String>>, _>("preferred_cuisines")?.unwrap_or_default(),
+        cooking_time_preference: row
+            .try_get::<Option<String>, _>("cooking_time_preference")?
+            .map(CookingTimePreference::from)
+            .unwrap_or_default(),
+        meal_prep_frequency: row
+            .try_get::<Option<String>, _>("meal_prep_frequency")?
+            .map(MealPrepFrequency::from)
+            .unwrap_or_default(),
+        budget_range: BudgetRange {
+            min_per_meal: row.try_get::<Option<f32>, _>("budget_min_per_meal")?.unwrap_or_default(),
+            max_per_meal: row.try_get::<Option<f32>, _>("budget_max_per_meal")?.unwrap_or_default(),
+            weekly_limit: row.try_get("weekly_budget_limit")?,
+        },
+        kitchen_equipment: row.try_get::<Option<Vec<String>>, _>("kitchen_equipment")?.unwrap_or_default(),
+        notification_settings: NotificationSettings {
+            meal_reminders: flag("notification_meal_reminders")?,
+            prep_reminders: flag("notification_prep_reminders")?,
+            shopping_reminders: flag("notification_shopping_reminders")?,
+            goal_updates: flag("notification_goal_updates")?,
+            weekly_summary: flag("notification_weekly_summary")?,
+        },
+    })
+}
+
+pub async fn save_user_preferences(executor: impl PgExecutor<'_>, user_id: uuid::Uuid, preferences: &UserPreferences) -> Result<()> {
+    let notifications = &preferences.notification_settings;
+    sqlx::query(
+        "INSERT INTO user_preferences (user_id, preferred_cuisines, cooking_time_preference, meal_prep_frequency, \
+                                       budget_min_per_meal, budget_max_per_meal, weekly_budget_limit, kitchen_equipment, \
+                                       notification_meal_reminders, notification_prep_reminders, \
+                                       notification_shopping_reminders, notification_goal_updates, \
+                                       notification_weekly_summary) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) \
+         ON CONFLICT (user_id) DO UPDATE SET \
+             preferred_cuisines = EXCLUDED.preferred_cuisines, \
+             cooking_time_preference = EXCLUDED.cooking_time_preference, \
+             meal_prep_frequency = EXCLUDED.meal_prep_frequency, \
+             budget_min_per_meal = EXCLUDED.budget_min_per_meal, \
+             budget_max_per_meal = EXCLUDED.budget_max_per_meal, \
+             weekly_budget_limit = EXCLUDED.weekly_budget_limit, \
+             kitchen_equipment = EXCLUDED.kitchen_equipment, \
+             notification_meal_reminders = EXCLUDED.notification_meal_reminders, \
+             notification_prep_reminders = EXCLUDED.notification_prep_reminders, \
+             notification_shopping_reminders = EXCLUDED.notification_shopping_reminders, \
+             notification_goal_updates = EXCLUDED.notification_goal_updates, \
+             notification_weekly_summary = EXCLUDED.notification_weekly_summary, \
+             updated_at = NOW()",
+    )
+    .bind(user_id)
+    .bind(&preferences.preferred_cuisines)
+    .bind(preferences.cooking_time_preference.as_str())
+    .bind(preferences.meal_prep_frequency.as_str())
+    .bind(preferences.budget_range.min_per_meal)
+    .bind(preferences.budget_range.max_per_meal)
+    .bind(preferences.budget_range.weekly_limit)
+    .bind(&preferences.kitchen_equipment)
+    .bind(notifications.meal_reminders)
+    .bind(notifications.prep_reminders)
+    .bind(notifications.shopping_reminders)
+    .bind(notifications.goal_updates)
+    .bind(notifications.weekly_summary)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
@@ -1,35 +1,102 @@
 use axum::{extract::State, http::StatusCode, Json};
 use serde_json::Value;
 use crate::AppState;
+use crate::auth::AuthUser;
 
 pub mod auth_handlers {
     use super::*;
-    use crate::models::{CreateUserRequest, LoginRequest};
-    use axum::extract::Json;
+    use crate::auth::{self, RefreshOutcome, Role};
+    use crate::action_tokens::{self, TokenPurpose};
+    use crate::database;
+    use crate::login_guard;
+    use crate::mailer::EmailMessage;
+    use crate::models::{
+        AuthResponse, CreateUserRequest, ForgotPasswordRequest, LoginRequest, LoginResponse,
+        RefreshTokenRequest, ResetPasswordRequest, TwoFactorChallengeResponse, UserSummary,
+        VerifyEmailRequest,
+    };
+    use crate::sessions::{self, ClientInfo, SessionInfo};
+    use axum::extract::{Json, Path};
     use axum::http::StatusCode;
     use crate::AppState;
     use serde_json::json;
     use sqlx::Row;
     use bcrypt::{hash, verify, DEFAULT_COST};
-    use jsonwebtoken::{encode, Header, EncodingKey};
-    use chrono::{Utc, Duration};
+    use chrono::Utc;
     use uuid::Uuid;
+    use std::sync::OnceLock;
+    use validator::Validate;
 
-    #[derive(serde::Serialize, serde::Deserialize)]
-    struct Claims {
-        sub: String,
-        email: String,
-        exp: usize,
+    fn dummy_password_hash() -> &'static str {
+        static HASH: OnceLock<String> = OnceLock::new();
+        HASH.get_or_init(|| hash("not-a-real-password", DEFAULT_COST).expect("bcrypt hashing a constant"))
     }
 
-    fn jwt_secret() -> String {
-        std::env::var("JWT_SECRET").unwrap_or_else(|_| "super_secret_key_for_development".to_string())
+    fn app_base_url() -> String {
+        std::env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string())
+    }
+
+    fn verification_email(to: &str, token: &str) -> EmailMessage {
+        EmailMessage {
+            to: to.to_string(),
+            subject: "Verify your Meal Prep Pro email".to_string(),
+            body: format!(
+                "Welcome to Meal Prep Pro!\n\nConfirm your email address by opening this link within 24 hours:\n{}/verify-email?token={}\n",
+                app_base_url(),
+                token
+            ),
+        }
+    }
+
+    fn password_reset_email(to: &str, token: &str) -> EmailMessage {
+        EmailMessage {
+            to: to.to_string(),
+            subject: "Reset your Meal Prep Pro password".to_string(),
+            body: format!(
+                "Someone asked to reset the password for this account.\n\nOpen this link within an hour to choose a new one:\n{}/reset-password?token={}\n\nIf it wasn't you, you can ignore this email.\n",
+                app_base_url(),
+                token
+            ),
+        }
+    }
+
+    async fn send_verification_email(state: &AppState, user_id: Uuid, email: &str) -> anyhow::Result<()> {
+        let token = action_tokens::issue(&state.db, user_id, TokenPurpose::VerifyEmail).await?;
+        state.mailer.send(&verification_email(email, &token)).await
+    }
+
+    async fn send_password_reset_email(state: &AppState, user_id: Uuid, email: &str) -> anyhow::Result<()> {
+        let token = action_tokens::issue(&state.db, user_id, TokenPurpose::ResetPassword).await?;
+        state.mailer.send(&password_reset_email(email, &token)).await
+    }
+
+    /// Starts a new session and issues its access JWT plus a refresh token.
+    pub(super) async fn issue_tokens(state: &AppState, user: UserSummary, client: &ClientInfo) -> Result<AuthResponse, StatusCode> {
+        let session_id = Uuid::new_v4();
+        let refresh_token = auth::issue_refresh_token(&state.db, user.id, session_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        sessions::create_session(&state.db, session_id, user.id, &refresh_token, client)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let (access_token, expires_in) = auth::issue_access_token(user.id, &user.email, user.role, Some(session_id))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(AuthResponse {
+            access_token,
+            refresh_token,
+            user,
+            expires_in,
+        })
     }
 
     pub async fn register(
         State(state): State<AppState>,
+        client: ClientInfo,
         Json(payload): Json<CreateUserRequest>,
-    ) -> Result<Json<Value>, StatusCode> {
+    ) -> Result<Json<AuthResponse>, StatusCode> {
+        payload.validate().map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+
         // Check if user exists
         let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM users WHERE email = $1")
             .bind(&payload.email)
@@ -47,75 +114,57 @@ pub mod auth_handlers {
         let user_id = Uuid::new_v4();
         let now = Utc::now();
 
+        // One transaction, so a failure part-way cannot leave an account that can neither
+        // log in properly nor register again
+        let mut tx = state.db.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         sqlx::query("INSERT INTO users (id, username, email, password_hash, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $5)")
             .bind(user_id)
             .bind(&payload.username)
             .bind(&payload.email)
             .bind(&hashed)
             .bind(now)
-            .execute(&state.db)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        database::save_user_profile(&mut *tx, user_id, &payload.profile.unwrap_or_default())
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        database::save_user_preferences(&mut *tx, user_id, &payload.preferences.unwrap_or_default())
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
         // Fetch user
-        let user: (Uuid, String, String) = sqlx::query_as("SELECT id, username, email FROM users WHERE id = $1")
+        let user: UserSummary = sqlx::query_as("SELECT id, username, email, COALESCE(role, 'user') AS role FROM users WHERE id = $1")
             .bind(user_id)
             .fetch_one(&state.db)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        // JWT
-        let exp = (Utc::now() + Duration::hours(24)).timestamp() as usize;
-        let claims = Claims {
-            sub: user.0.to_string(),
-            email: user.2.clone(),
-            exp,
-        };
-        let token = encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(jwt_secret().as_bytes()),
-        ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-        Ok(Json(json!({
-            "token": token,
-            "user": {
-                "id": user.0,
-                "username": user.1,
-                "email": user.2,
-            }
-        })))
+        // A mail outage should not block sign-up; the user can ask for a new link later
+        if let Err(e) = send_verification_email(&state, user.id, &user.email).await {
+            tracing::warn!("Failed to send verification email to {}: {}", user.email, e);
+        }
+
+        Ok(Json(issue_tokens(&state, user, &client).await?))
     }
 
     pub async fn login(
         State(state): State<AppState>,
+        client: ClientInfo,
         Json(payload): Json<LoginRequest>,
-    ) -> Result<Json<Value>, StatusCode> {
-        // Demo login
-        if payload.email == "demo@mealprep.com" && payload.password == "demopass" {
-            let exp = (Utc::now() + Duration::hours(24)).timestamp() as usize;
-            let claims = Claims {
-                sub: "demo".to_string(),
-                email: "demo@mealprep.com".to_string(),
-                exp,
-            };
-            let token = encode(
-                &Header::default(),
-                &claims,
-                &EncodingKey::from_secret(jwt_secret().as_bytes()),
-            ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            return Ok(Json(json!({
-                "token": token,
-                "user": {
-                    "id": "demo",
-                    "username": "Demo User",
-                    "email": "demo@mealprep.com"
-                }
-            })));
+    ) -> Result<Json<LoginResponse>, StatusCode> {
+        // Refuse before touching bcrypt, so a locked-out attacker cannot burn CPU either
+        if login_guard::locked_for(&state, &payload.email, &client).await.is_some() {
+            return Err(StatusCode::TOO_MANY_REQUESTS);
         }
 
-        // Real login
-        let row = sqlx::query("SELECT id, username, email, password_hash FROM users WHERE email = $1")
+        let row = sqlx::query(
+            "SELECT id, username, email, COALESCE(role, 'user') AS role, password_hash, \
+                    EXISTS(SELECT 1 FROM user_totp t WHERE t.user_id = users.id AND t.enabled) AS totp_enabled \
+             FROM users WHERE email = $1 AND is_active IS NOT FALSE",
+        )
             .bind(&payload.email)
             .fetch_optional(&state.db)
             .await
@@ -123,111 +172,841 @@ pub mod auth_handlers {
 
         let row = match row {
             Some(r) => r,
-            None => return Err(StatusCode::UNAUTHORIZED),
+            None => {
+                // Same bcrypt cost as a real account, so timing does not reveal which emails exist
+                let _ = verify(&payload.password, dummy_password_hash());
+                login_guard::record_failure(&state, &payload.email, &client, None).await;
+                return Err(StatusCode::UNAUTHORIZED);
+            }
         };
 
-        let id: Uuid = row.get("id");
-        let username: String = row.get("username");
-        let email: String = row.get("email");
         let password_hash: String = row.get("password_hash");
 
         if !verify(&payload.password, &password_hash).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+            login_guard::record_failure(&state, &payload.email, &client, Some(row.get("id"))).await;
             return Err(StatusCode::UNAUTHORIZED);
         }
+        login_guard::record_success(&state, &payload.email, &client).await;
 
-        let exp = (Utc::now() + Duration::hours(24)).timestamp() as usize;
-        let claims = Claims {
-            sub: id.to_string(),
-            email: email.clone(),
-            exp,
+        let user = UserSummary {
+            id: row.get("id"),
+            username: row.get("username"),
+            email: row.get("email"),
+            role: Role::from(row.get::<String, _>("role")),
         };
-        let token = encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(jwt_secret().as_bytes()),
-        ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-        Ok(Json(json!({
-            "token": token,
-            "user": {
-                "id": id,
-                "username": username,
-                "email": email,
-            }
-        })))
+
+        if row.get::<bool, _>("totp_enabled") {
+            let (challenge_token, expires_in) = auth::issue_two_factor_challenge(user.id)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            return Ok(Json(LoginResponse::TwoFactorRequired(TwoFactorChallengeResponse {
+                status: "2fa_required",
+                challenge_token,
+                expires_in,
+            })));
+        }
+
+        let response = issue_tokens(&state, user, &client).await?;
+        Ok(Json(LoginResponse::Authenticated(response)))
     }
 
     pub async fn refresh_token(
-        State(_state): State<AppState>,
+        State(state): State<AppState>,
+        Json(payload): Json<RefreshTokenRequest>,
+    ) -> Result<Json<AuthResponse>, StatusCode> {
+        let outcome = auth::rotate_refresh_token(&state.db, &payload.refresh_token)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let (user_id, session_id, refresh_token) = match outcome {
+            RefreshOutcome::Rotated { user_id, family_id, refresh_token } => (user_id, family_id, refresh_token),
+            RefreshOutcome::ReuseDetected { family_id } => {
+                // The stolen copy may already have minted access tokens, so end the session too
+                let owner: Option<(Uuid,)> = sqlx::query_as("SELECT user_id FROM user_sessions WHERE id = $1")
+                    .bind(family_id)
+                    .fetch_optional(&state.db)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                if let Some((owner,)) = owner {
+                    sessions::revoke_session(&state, owner, family_id)
+                        .await
+                        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                }
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+            RefreshOutcome::Invalid => return Err(StatusCode::UNAUTHORIZED),
+        };
+
+        let user: UserSummary = sqlx::query_as("SELECT id, username, email, COALESCE(role, 'user') AS role FROM users WHERE id = $1 AND is_active IS NOT FALSE")
+            .bind(user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        let (access_token, expires_in) = auth::issue_access_token(user.id, &user.email, user.role, Some(session_id))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(Json(AuthResponse {
+            access_token,
+            refresh_token,
+            user,
+            expires_in,
+        }))
+    }
+
+    /// Ends the session the calling access token belongs to.
+    pub async fn logout(
+        State(state): State<AppState>,
+        user: AuthUser,
+    ) -> Result<StatusCode, StatusCode> {
+        let session_id = user.session_id.ok_or(StatusCode::BAD_REQUEST)?;
+
+        sessions::revoke_session(&state, user.id, session_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    pub async fn list_sessions(
+        State(state): State<AppState>,
+        user: AuthUser,
     ) -> Result<Json<Value>, StatusCode> {
-        Ok(Json(serde_json::json!({"message": "Refresh token endpoint"})))
+        let sessions: Vec<SessionInfo> = sessions::list_active_sessions(&state.db, user.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let sessions: Vec<Value> = sessions
+            .into_iter()
+            .map(|s| {
+                let is_current = Some(s.id) == user.session_id;
+                let mut value = json!(s);
+                value["current"] = json!(is_current);
+                value
+            })
+            .collect();
+
+        Ok(Json(json!({ "sessions": sessions })))
+    }
+
+    pub async fn revoke_session(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Path(session_id): Path<Uuid>,
+    ) -> Result<StatusCode, StatusCode> {
+        let revoked = sessions::revoke_session(&state, user.id, session_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if revoked {
+            Ok(StatusCode::NO_CONTENT)
+        } else {
+            Err(StatusCode::NOT_FOUND)
+        }
+    }
+
+    pub async fn verify_email(
+        State(state): State<AppState>,
+        Json(payload): Json<VerifyEmailRequest>,
+    ) -> Result<StatusCode, StatusCode> {
+        let user_id = action_tokens::redeem(&state.db, &payload.token, TokenPurpose::VerifyEmail)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::BAD_REQUEST)?;
+
+        sqlx::query("UPDATE users SET email_verified = TRUE WHERE id = $1")
+            .bind(user_id)
+            .execute(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    pub async fn resend_verification(
+        State(state): State<AppState>,
+        user: AuthUser,
+    ) -> Result<StatusCode, StatusCode> {
+        let verified: (Option<bool>,) = sqlx::query_as("SELECT email_verified FROM users WHERE id = $1")
+            .bind(user.id)
+            .fetch_one(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if verified.0.unwrap_or(false) {
+            return Err(StatusCode::CONFLICT);
+        }
+
+        send_verification_email(&state, user.id, &user.email)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::ACCEPTED)
+    }
+
+    /// Always answers 202, after the same work, so the endpoint cannot be used to probe
+    /// which emails have accounts.
+    pub async fn forgot_password(
+        State(state): State<AppState>,
+        Json(payload): Json<ForgotPasswordRequest>,
+    ) -> Result<StatusCode, StatusCode> {
+        let user: Option<(Uuid, String)> = sqlx::query_as("SELECT id, email FROM users WHERE email = $1 AND is_active IS NOT FALSE")
+            .bind(&payload.email)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        // Issue and send off the request path so known and unknown emails take the same time
+        if let Some((user_id, email)) = user {
+            tokio::spawn(async move {
+                if let Err(e) = send_password_reset_email(&state, user_id, &email).await {
+                    tracing::warn!("Failed to send password reset email to {}: {}", email, e);
+                }
+            });
+        }
+
+        Ok(StatusCode::ACCEPTED)
+    }
+
+    /// Sets a new password and signs the account out everywhere.
+    pub async fn reset_password(
+        State(state): State<AppState>,
+        Json(payload): Json<ResetPasswordRequest>,
+    ) -> Result<StatusCode, StatusCode> {
+        payload.validate().map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+
+        let user_id = action_tokens::redeem(&state.db, &payload.token, TokenPurpose::ResetPassword)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::BAD_REQUEST)?;
+
+        let hashed = hash(&payload.new_password, DEFAULT_COST)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        // Receiving the reset link also proves ownership of the address
+        sqlx::query("UPDATE users SET password_hash = $2, email_verified = TRUE WHERE id = $1")
+            .bind(user_id)
+            .bind(&hashed)
+            .execute(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        sessions::revoke_all_sessions(&state, user_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::mailer::{CapturingMailer, Mailer};
+
+        fn token_from_link(body: &str, path: &str) -> String {
+            let marker = format!("{}?token=", path);
+            let start = body.find(&marker).expect("link in body") + marker.len();
+            body[start..].split_whitespace().next().unwrap().to_string()
+        }
+
+        #[tokio::test]
+        async fn verification_email_carries_a_redeemable_token() {
+            std::env::set_var("ACTION_TOKEN_SECRET", "action_token_secret_for_tests");
+            let mailer = CapturingMailer::default();
+            let (token_id, _, token) = action_tokens::mint(TokenPurpose::VerifyEmail).unwrap();
+
+            mailer.send(&verification_email("cook@example.com", &token)).await.unwrap();
+
+            let sent = mailer.sent.lock().unwrap();
+            assert_eq!(sent.len(), 1);
+            assert_eq!(sent[0].to, "cook@example.com");
+            let linked = token_from_link(&sent[0].body, "/verify-email");
+            assert_eq!(action_tokens::verify(&linked, TokenPurpose::VerifyEmail).unwrap(), Some(token_id));
+            assert_eq!(action_tokens::verify(&linked, TokenPurpose::ResetPassword).unwrap(), None);
+        }
+
+        #[tokio::test]
+        async fn reset_email_token_is_bound_to_its_purpose() {
+            std::env::set_var("ACTION_TOKEN_SECRET", "action_token_secret_for_tests");
+            let mailer = CapturingMailer::default();
+            let (token_id, _, token) = action_tokens::mint(TokenPurpose::ResetPassword).unwrap();
+
+            mailer.send(&password_reset_email("cook@example.com", &token)).await.unwrap();
+
+            let sent = mailer.sent.lock().unwrap();
+            let linked = token_from_link(&sent[0].body, "/reset-password");
+            assert_eq!(action_tokens::verify(&linked, TokenPurpose::ResetPassword).unwrap(), Some(token_id));
+            assert_eq!(action_tokens::verify(&linked, TokenPurpose::VerifyEmail).unwrap(), None);
+
+            let mut tampered = linked.clone();
+            tampered.replace_range(..1, if linked.starts_with('0') { "1" } else { "0" });
+            assert_eq!(action_tokens::verify(&tampered, TokenPurpose::ResetPassword).unwrap(), None);
+        }
+    }
+}
+
+pub mod two_factor_handlers {
+    use super::*;
+    use super::auth_handlers::issue_tokens;
+    use crate::auth::{self, hash_token};
+    use crate::models::{
+        AuthResponse, RecoveryCodesResponse, TwoFactorCodeRequest, TwoFactorEnrollment,
+        TwoFactorLoginRequest, UserSummary,
+    };
+    use crate::login_guard;
+    use crate::sessions::ClientInfo;
+    use crate::totp;
+    use sqlx::{PgConnection, PgPool};
+    use uuid::Uuid;
+
+    /// Checks a TOTP code (or, failing that, an unused recovery code) for an enabled account.
+    /// Both are consumed on success so they cannot be replayed.
+    async fn verify_second_factor(
+        db: &PgPool,
+        user_id: Uuid,
+        code: Option<&str>,
+        recovery_code: Option<&str>,
+    ) -> Result<bool, StatusCode> {
+        if let Some(code) = code {
+            let totp: Option<(String, Option<i64>)> =
+                sqlx::query_as("SELECT secret, last_used_step FROM user_totp WHERE user_id = $1 AND enabled")
+                    .bind(user_id)
+                    .fetch_optional(db)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            let Some((secret, last_used_step)) = totp else {
+                return Ok(false);
+            };
+            let Some(step) = totp::verify_code(&secret, code, chrono::Utc::now().timestamp(), last_used_step) else {
+                return Ok(false);
+            };
+
+            let claimed = sqlx::query(
+                "UPDATE user_totp SET last_used_step = $2 WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)",
+            )
+            .bind(user_id)
+            .bind(step)
+            .execute(db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            return Ok(claimed.rows_affected() == 1);
+        }
+
+        if let Some(recovery_code) = recovery_code {
+            let used: Option<(Uuid,)> = sqlx::query_as(
+                "UPDATE user_recovery_codes SET used_at = NOW() WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL RETURNING id",
+            )
+            .bind(user_id)
+            .bind(hash_token(&totp::normalize_recovery_code(recovery_code)))
+            .fetch_optional(db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            return Ok(used.is_some());
+        }
+
+        Ok(false)
+    }
+
+    /// Replaces all recovery codes of a user and returns the new plaintext codes.
+    /// Swaps the user's recovery codes for a fresh set inside the caller's transaction.
+    async fn replace_recovery_codes(tx: &mut PgConnection, user_id: Uuid) -> Result<Vec<String>, StatusCode> {
+        let codes = totp::generate_recovery_codes();
+
+        sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        for code in &codes {
+            sqlx::query("INSERT INTO user_recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+                .bind(user_id)
+                .bind(hash_token(code))
+                .execute(&mut *tx)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+
+        Ok(codes)
+    }
+
+    /// Second login step: trades the challenge token plus a valid code for a session.
+    pub async fn verify_login(
+        State(state): State<AppState>,
+        client: ClientInfo,
+        Json(payload): Json<TwoFactorLoginRequest>,
+    ) -> Result<Json<AuthResponse>, StatusCode> {
+        let user_id = auth::validate_two_factor_challenge(&payload.challenge_token).ok_or(StatusCode::UNAUTHORIZED)?;
+
+        let user: UserSummary = sqlx::query_as("SELECT id, username, email, COALESCE(role, 'user') AS role FROM users WHERE id = $1 AND is_active IS NOT FALSE")
+            .bind(user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        // Six-digit codes are guessable too, so they share the password step's lockout
+        if login_guard::locked_for(&state, &user.email, &client).await.is_some() {
+            return Err(StatusCode::TOO_MANY_REQUESTS);
+        }
+
+        if !verify_second_factor(&state.db, user_id, payload.code.as_deref(), payload.recovery_code.as_deref()).await? {
+            login_guard::record_failure(&state, &user.email, &client, Some(user_id)).await;
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        login_guard::record_success(&state, &user.email, &client).await;
+
+        Ok(Json(issue_tokens(&state, user, &client).await?))
+    }
+
+    /// Starts (or restarts) enrollment. The secret is not enforced until `confirm` succeeds.
+    pub async fn enroll(
+        State(state): State<AppState>,
+        user: AuthUser,
+    ) -> Result<Json<TwoFactorEnrollment>, StatusCode> {
+        let secret = totp::generate_secret();
+
+        let stored = sqlx::query(
+            "INSERT INTO user_totp (user_id, secret) VALUES ($1, $2) \
+             ON CONFLICT (user_id) DO UPDATE SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = NOW() \
+             WHERE NOT user_totp.enabled",
+        )
+        .bind(user.id)
+        .bind(&secret)
+        .execute(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if stored.rows_affected() == 0 {
+            return Err(StatusCode::CONFLICT);
+        }
+
+        Ok(Json(TwoFactorEnrollment {
+            otpauth_uri: totp::otpauth_uri(&secret, &user.email),
+            secret,
+        }))
+    }
+
+    /// Turns 2FA on once the user proves their authenticator produces valid codes.
+    /// The recovery codes are only ever shown in this response.
+    pub async fn confirm(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Json(payload): Json<TwoFactorCodeRequest>,
+    ) -> Result<Json<RecoveryCodesResponse>, StatusCode> {
+        let pending: Option<(String,)> = sqlx::query_as("SELECT secret FROM user_totp WHERE user_id = $1 AND NOT enabled")
+            .bind(user.id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let (secret,) = pending.ok_or(StatusCode::NOT_FOUND)?;
+        let code = payload.code.as_deref().ok_or(StatusCode::BAD_REQUEST)?;
+        let step = totp::verify_code(&secret, code, chrono::Utc::now().timestamp(), None)
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        // `NOT enabled` lets only one of two concurrent confirmations through
+        let mut tx = state.db.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let enabled = sqlx::query(
+            "UPDATE user_totp SET enabled = TRUE, enabled_at = NOW(), last_used_step = $2 \
+             WHERE user_id = $1 AND NOT enabled",
+        )
+        .bind(user.id)
+        .bind(step)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if enabled.rows_affected() == 0 {
+            return Err(StatusCode::CONFLICT);
+        }
+
+        let recovery_codes = replace_recovery_codes(&mut tx, user.id).await?;
+        tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(Json(RecoveryCodesResponse { recovery_codes }))
+    }
+
+    pub async fn regenerate_recovery_codes(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Json(payload): Json<TwoFactorCodeRequest>,
+    ) -> Result<Json<RecoveryCodesResponse>, StatusCode> {
+        if !verify_second_factor(&state.db, user.id, payload.code.as_deref(), None).await? {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        let mut tx = state.db.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let recovery_codes = replace_recovery_codes(&mut tx, user.id).await?;
+        tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(Json(RecoveryCodesResponse { recovery_codes }))
+    }
+
+    /// Requires a current code (or recovery code) so a hijacked session cannot silently drop 2FA.
+    pub async fn disable(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Json(payload): Json<TwoFactorCodeRequest>,
+    ) -> Result<StatusCode, StatusCode> {
+        if !verify_second_factor(&state.db, user.id, payload.code.as_deref(), payload.recovery_code.as_deref()).await? {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        let mut tx = state.db.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        sqlx::query("DELETE FROM user_totp WHERE user_id = $1")
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
     }
 }
 
 pub mod user_handlers {
     use super::*;
+    use crate::database;
+    use crate::models::{
+        CoachClientLink, GrantCoachAccessRequest, UserPreferences, UserPreferencesUpdate, UserProfile,
+        UserProfileUpdate,
+    };
+    use validator::Validate;
+    use axum::extract::Path;
+    use uuid::Uuid;
 
     pub async fn get_profile(
-        State(_state): State<AppState>,
-    ) -> Result<Json<Value>, StatusCode> {
-        Ok(Json(serde_json::json!({"message": "Get profile endpoint"})))
+        State(state): State<AppState>,
+        user: AuthUser,
+    ) -> Result<Json<UserProfile>, StatusCode> {
+        let profile = database::load_user_profile(&state.db, user.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(Json(profile))
     }
 
+    /// Merges the fields present in the body into the stored profile.
     pub async fn update_profile(
-        State(_state): State<AppState>,
-        Json(_payload): Json<Value>,
-    ) -> Result<Json<Value>, StatusCode> {
-        Ok(Json(serde_json::json!({"message": "Update profile endpoint"})))
+        State(state): State<AppState>,
+        user: AuthUser,
+        Json(payload): Json<UserProfileUpdate>,
+    ) -> Result<Json<UserProfile>, StatusCode> {
+        let mut profile = database::load_user_profile(&state.db, user.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        payload.apply(&mut profile);
+        profile.validate().map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+
+        database::save_user_profile(&state.db, user.id, &profile)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        get_profile(State(state), user).await
     }
 
     pub async fn get_preferences(
-        State(_state): State<AppState>,
-    ) -> Result<Json<Value>, StatusCode> {
-        Ok(Json(serde_json::json!({"message": "Get preferences endpoint"})))
+        State(state): State<AppState>,
+        user: AuthUser,
+    ) -> Result<Json<UserPreferences>, StatusCode> {
+        let preferences = database::load_user_preferences(&state.db, user.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(Json(preferences))
     }
 
+    /// Merges the fields present in the body into the stored preferences.
     pub async fn update_preferences(
-        State(_state): State<AppState>,
-        Json(_payload): Json<Value>,
-    ) -> Result<Json<Value>, StatusCode> {
-        Ok(Json(serde_json::json!({"message": "Update preferences endpoint"})))
+        State(state): State<AppState>,
+        user: AuthUser,
+        Json(payload): Json<UserPreferencesUpdate>,
+    ) -> Result<Json<UserPreferences>, StatusCode> {
+        let mut preferences = database::load_user_preferences(&state.db, user.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        payload.apply(&mut preferences);
+        preferences.validate().map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+
+        database::save_user_preferences(&state.db, user.id, &preferences)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(Json(preferences))
+    }
+
+    /// Coaches the caller has granted read access to.
+    pub async fn list_coaches(
+        State(state): State<AppState>,
+        user: AuthUser,
+    ) -> Result<Json<Vec<CoachClientLink>>, StatusCode> {
+        let coaches = sqlx::query_as::<_, CoachClientLink>(
+            "SELECT u.id AS user_id, u.username, u.email, g.granted_at \
+             FROM coach_client_grants g JOIN users u ON u.id = g.coach_id \
+             WHERE g.client_id = $1 ORDER BY g.granted_at",
+        )
+        .bind(user.id)
+        .fetch_all(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(Json(coaches))
+    }
+
+    pub async fn grant_coach_access(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Json(payload): Json<GrantCoachAccessRequest>,
+    ) -> Result<StatusCode, StatusCode> {
+        let coach: Option<(Uuid,)> = sqlx::query_as(
+            "SELECT id FROM users WHERE email = $1 AND role IN ('coach', 'dietitian') AND is_active IS NOT FALSE",
+        )
+        .bind(&payload.coach_email)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let (coach_id,) = coach.ok_or(StatusCode::NOT_FOUND)?;
+
+        sqlx::query("INSERT INTO coach_client_grants (coach_id, client_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(coach_id)
+            .bind(user.id)
+            .execute(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    pub async fn revoke_coach_access(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Path(coach_id): Path<Uuid>,
+    ) -> Result<StatusCode, StatusCode> {
+        let deleted = sqlx::query("DELETE FROM coach_client_grants WHERE coach_id = $1 AND client_id = $2")
+            .bind(coach_id)
+            .bind(user.id)
+            .execute(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if deleted.rows_affected() == 0 {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Ok(StatusCode::NO_CONTENT)
+    }
+}
+
+pub mod api_key_handlers {
+    use super::*;
+    use crate::api_keys;
+    use crate::models::{ApiKeyInfo, CreateApiKeyRequest, CreatedApiKey};
+    use axum::extract::Path;
+    use uuid::Uuid;
+    use validator::Validate;
+
+    /// The caller's keys that have not been revoked, newest first.
+    pub async fn list_api_keys(
+        State(state): State<AppState>,
+        user: AuthUser,
+    ) -> Result<Json<Vec<ApiKeyInfo>>, StatusCode> {
+        let keys = sqlx::query_as::<_, ApiKeyInfo>(
+            "SELECT id, name, prefix, scopes, expires_at, last_used_at, created_at \
+             FROM api_keys WHERE user_id = $1 AND revoked_at IS NULL ORDER BY created_at DESC",
+        )
+        .bind(user.id)
+        .fetch_all(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(Json(keys))
+    }
+
+    pub async fn create_api_key(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Json(payload): Json<CreateApiKeyRequest>,
+    ) -> Result<(StatusCode, Json<CreatedApiKey>), StatusCode> {
+        payload.validate().map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+        if !payload.scopes.iter().all(|s| api_keys::is_valid_scope(s)) {
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        if payload.expires_at.is_some_and(|t| t <= chrono::Utc::now()) {
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        let key = api_keys::generate_key();
+        let info = sqlx::query_as::<_, ApiKeyInfo>(
+            "INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at) \
+             VALUES ($1, $2, $3, $4, $5, $6) \
+             RETURNING id, name, prefix, scopes, expires_at, last_used_at, created_at",
+        )
+        .bind(user.id)
+        .bind(&payload.name)
+        .bind(api_keys::display_prefix(&key))
+        .bind(crate::auth::hash_token(&key))
+        .bind(&payload.scopes)
+        .bind(payload.expires_at)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok((StatusCode::CREATED, Json(CreatedApiKey { key, info })))
+    }
+
+    pub async fn revoke_api_key(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Path(key_id): Path<Uuid>,
+    ) -> Result<StatusCode, StatusCode> {
+        let revoked = sqlx::query(
+            "UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+        )
+        .bind(key_id)
+        .bind(user.id)
+        .execute(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if revoked.rows_affected() == 0 {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Ok(StatusCode::NO_CONTENT)
+    }
+}
+
+pub mod account_handlers {
+    use super::*;
+    use crate::account_data::{self, DELETION_GRACE_DAYS};
+    use crate::mailer::EmailMessage;
+    use crate::models::{AccountDeletionStatus, DeleteAccountRequest};
+    use axum::http::header;
+    use axum::response::IntoResponse;
+    use chrono::{DateTime, Duration, Utc};
+
+    /// Everything stored about the caller as a zip of JSON (and CSV for logs).
+    pub async fn export_data(
+        State(state): State<AppState>,
+        user: AuthUser,
+    ) -> Result<impl IntoResponse, StatusCode> {
+        let archive = account_data::export_archive(&state.db, user.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let file_name = format!("meal-prep-pro-export-{}.zip", Utc::now().format("%Y%m%d"));
+        Ok((
+            [
+                (header::CONTENT_TYPE, "application/zip".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+            ],
+            archive,
+        ))
+    }
+
+    /// Schedules the account for deletion after the grace period; until then it can be restored.
+    pub async fn request_deletion(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Json(payload): Json<DeleteAccountRequest>,
+    ) -> Result<(StatusCode, Json<AccountDeletionStatus>), StatusCode> {
+        let (password_hash,): (String,) = sqlx::query_as("SELECT password_hash FROM users WHERE id = $1")
+            .bind(user.id)
+            .fetch_one(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if !bcrypt::verify(&payload.password, &password_hash).unwrap_or(false) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        let scheduled_for = Utc::now() + Duration::days(DELETION_GRACE_DAYS);
+        let (deletion_scheduled_for,): (DateTime<Utc>,) = sqlx::query_as(
+            "UPDATE users SET deletion_requested_at = NOW(), \
+                 deletion_scheduled_for = COALESCE(deletion_scheduled_for, $2) \
+             WHERE id = $1 RETURNING deletion_scheduled_for",
+        )
+        .bind(user.id)
+        .bind(scheduled_for)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let message = EmailMessage {
+            to: user.email.clone(),
+            subject: "Your Meal Prep Pro account will be deleted".to_string(),
+            body: format!(
+                "Your account and all of its data will be permanently deleted on {}.\n\nIf you change your mind, sign in before then and cancel the deletion from your account settings.\n",
+                deletion_scheduled_for.format("%Y-%m-%d %H:%M UTC")
+            ),
+        };
+        if let Err(e) = state.mailer.send(&message).await {
+            tracing::warn!("Failed to send deletion notice to {}: {}", user.email, e);
+        }
+
+        Ok((StatusCode::ACCEPTED, Json(AccountDeletionStatus { deletion_scheduled_for })))
+    }
+
+    pub async fn cancel_deletion(
+        State(state): State<AppState>,
+        user: AuthUser,
+    ) -> Result<StatusCode, StatusCode> {
+        let cancelled = sqlx::query(
+            "UPDATE users SET deletion_requested_at = NULL, deletion_scheduled_for = NULL \
+             WHERE id = $1 AND deletion_scheduled_for IS NOT NULL",
+        )
+        .bind(user.id)
+        .execute(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if cancelled.rows_affected() == 0 {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Ok(StatusCode::NO_CONTENT)
     }
 }
 
 pub mod meal_handlers {
     use super::*;
 
-    pub async fn list_meals(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn list_meals(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "List meals endpoint"})))
     }
 
     pub async fn create_meal(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Create meal endpoint"})))
     }
 
-    pub async fn get_meal(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_meal(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get meal endpoint"})))
     }
 
     pub async fn update_meal(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Update meal endpoint"})))
     }
 
-    pub async fn delete_meal(State(_state): State<AppState>) -> Result<StatusCode, StatusCode> {
+    pub async fn delete_meal(State(_state): State<AppState>, _user: AuthUser) -> Result<StatusCode, StatusCode> {
         Ok(StatusCode::NO_CONTENT)
     }
 
-    pub async fn search_meals(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn search_meals(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Search meals endpoint"})))
     }
 
-    pub async fn get_recommendations(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_recommendations(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get recommendations endpoint"})))
     }
 }
@@ -235,23 +1014,25 @@ pub mod meal_handlers {
 pub mod meal_plan_handlers {
     use super::*;
 
-    pub async fn list_plans(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn list_plans(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "List plans endpoint"})))
     }
 
     pub async fn create_plan(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Create plan endpoint"})))
     }
 
-    pub async fn get_plan(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_plan(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get plan endpoint"})))
     }
 
     pub async fn update_plan(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Update plan endpoint"})))
@@ -259,6 +1040,7 @@ pub mod meal_plan_handlers {
 
     pub async fn generate_ai_plan(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Generate AI plan endpoint"})))
@@ -270,31 +1052,33 @@ pub mod nutrition_handlers {
 
     pub async fn log_meal(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Log meal endpoint"})))
     }
 
-    pub async fn get_daily_nutrition(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_daily_nutrition(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get daily nutrition endpoint"})))
     }
 
-    pub async fn get_weekly_nutrition(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_weekly_nutrition(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get weekly nutrition endpoint"})))
     }
 
-    pub async fn get_goals(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_goals(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get goals endpoint"})))
     }
 
     pub async fn update_goals(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Update goals endpoint"})))
     }
 
-    pub async fn get_nutritional_analysis(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_nutritional_analysis(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get nutritional analysis endpoint"})))
     }
 }
@@ -302,23 +1086,25 @@ pub mod nutrition_handlers {
 pub mod shopping_handlers {
     use super::*;
 
-    pub async fn list_shopping_lists(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn list_shopping_lists(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "List shopping lists endpoint"})))
     }
 
     pub async fn create_shopping_list(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Create shopping list endpoint"})))
     }
 
-    pub async fn get_shopping_list(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_shopping_list(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get shopping list endpoint"})))
     }
 
     pub async fn add_items(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Add items endpoint"})))
@@ -326,6 +1112,7 @@ pub mod shopping_handlers {
 
     pub async fn optimize_list(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Optimize list endpoint"})))
@@ -335,65 +1122,193 @@ pub mod shopping_handlers {
 pub mod analytics_handlers {
     use super::*;
 
-    pub async fn get_dashboard(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_dashboard(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get dashboard endpoint"})))
     }
 
-    pub async fn get_trends(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_trends(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get trends endpoint"})))
     }
 
-    pub async fn get_predictions(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_predictions(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get predictions endpoint"})))
     }
 
-    pub async fn get_insights(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_insights(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get insights endpoint"})))
     }
 }
 
 pub mod recipe_handlers {
     use super::*;
-
-    pub async fn list_recipes(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    use crate::models::{
+        CreateRecipeRequest, CreatedRecipe, ImportRecipeRequest, ImportedRecipeSummary, RecipeCollectionExportQuery,
+        RecipeCollectionImportQuery, RecipeCollectionImportReport, RecipeImportStatus,
+    };
+    use crate::recipe_collections::{self, CollectionFormat};
+    use crate::recipe_import;
+    use crate::recipes::{self, NewRecipe};
+    use axum::body::Bytes;
+    use axum::extract::Query;
+    use axum::http::header;
+    use axum::response::IntoResponse;
+    use chrono::Utc;
+    use validator::Validate;
+
+    pub async fn list_recipes(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "List recipes endpoint"})))
     }
 
+    /// Stores a recipe; ingredient lines are split into amount, unit, name and note.
     pub async fn create_recipe(
-        State(_state): State<AppState>,
-        Json(_payload): Json<Value>,
-    ) -> Result<Json<Value>, StatusCode> {
-        Ok(Json(serde_json::json!({"message": "Create recipe endpoint"})))
+        State(state): State<AppState>,
+        user: AuthUser,
+        Json(payload): Json<CreateRecipeRequest>,
+    ) -> Result<(StatusCode, Json<CreatedRecipe>), StatusCode> {
+        payload.validate().map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+        let ingredients = recipes::into_ingredients(payload.ingredients).map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+
+        let recipe = NewRecipe {
+            name: payload.name,
+            description: payload.description,
+            cuisine_type: payload.cuisine_type,
+            difficulty_level: payload.difficulty_level,
+            prep_time_minutes: payload.prep_time_minutes,
+            cook_time_minutes: payload.cook_time_minutes,
+            servings: payload.servings,
+            tags: payload.tags,
+            source_url: payload.source_url,
+            image_url: payload.image_url,
+            ingredients,
+            steps: recipes::into_steps(payload.instructions),
+        };
+        let id = recipes::insert_recipe(&state.db, user.id, &recipe)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        state.service_orchestrator.analyze_recipes(vec![id]);
+
+        Ok((StatusCode::CREATED, Json(CreatedRecipe { id, ingredients: recipe.ingredients })))
     }
 
-    pub async fn get_recipe(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_recipe(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get recipe endpoint"})))
     }
 
     pub async fn scale_recipe(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Scale recipe endpoint"})))
     }
 
+    /// Stores the schema.org Recipe from a posted page or JSON-LD, or from a fetched URL.
     pub async fn import_recipe(
-        State(_state): State<AppState>,
-        Json(_payload): Json<Value>,
-    ) -> Result<Json<Value>, StatusCode> {
-        Ok(Json(serde_json::json!({"message": "Import recipe endpoint"})))
+        State(state): State<AppState>,
+        user: AuthUser,
+        Json(payload): Json<ImportRecipeRequest>,
+    ) -> Result<(StatusCode, Json<ImportedRecipeSummary>), StatusCode> {
+        let imported = recipe_import::import_request(&payload, state.page_fetcher.as_deref())
+            .await
+            .map_err(|e| {
+                if e.is::<recipe_import::PageFetchFailed>() {
+                    tracing::warn!("{}", e);
+                    StatusCode::BAD_GATEWAY
+                } else {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                }
+            })?;
+
+        let id = recipes::insert_recipe(&state.db, user.id, &imported.recipe)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        state.service_orchestrator.analyze_recipes(vec![id]);
+
+        Ok((
+            StatusCode::CREATED,
+            Json(ImportedRecipeSummary {
+                id,
+                name: imported.recipe.name,
+                steps: imported.recipe.steps.len(),
+                ingredients: imported.recipe.ingredients,
+                unmapped_fields: imported.unmapped_fields,
+                warnings: imported.warnings,
+            }),
+        ))
+    }
+
+    /// Imports a Paprika, MealMaster, JSON or YAML collection. A dry run (the default) only
+    /// reports what would be stored; otherwise all new recipes are stored in one transaction.
+    pub async fn import_collection(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Query(query): Query<RecipeCollectionImportQuery>,
+        body: Bytes,
+    ) -> Result<Json<RecipeCollectionImportReport>, StatusCode> {
+        let format = query.format.unwrap_or_else(|| CollectionFormat::detect(&body));
+        let entries = recipe_collections::read_collection(format, &body).map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+        let existing = recipes::load_recipes(&state.db, user.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let (mut entries, to_import) = recipe_collections::plan_import(entries, &existing, query.import_duplicates);
+
+        if !query.dry_run {
+            let mut tx = state.db.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            for (index, recipe) in &to_import {
+                let id = recipes::insert_recipe_in(&mut tx, user.id, recipe)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                entries[*index].id = Some(id);
+                entries[*index].status = RecipeImportStatus::Imported;
+            }
+            tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            state.service_orchestrator.analyze_recipes(entries.iter().filter_map(|e| e.id).collect());
+        }
+
+        let count = |status: RecipeImportStatus| entries.iter().filter(|e| e.status == status).count();
+        Ok(Json(RecipeCollectionImportReport {
+            format,
+            dry_run: query.dry_run,
+            imported: to_import.len(),
+            duplicates: count(RecipeImportStatus::Duplicate),
+            invalid: count(RecipeImportStatus::Invalid),
+            recipes: entries,
+        }))
+    }
+
+    /// All of the caller's recipes as a Paprika, MealMaster, JSON or YAML file.
+    pub async fn export_collection(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Query(query): Query<RecipeCollectionExportQuery>,
+    ) -> Result<impl IntoResponse, StatusCode> {
+        let recipes = recipes::load_recipes(&state.db, user.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let file = recipe_collections::write_collection(query.format, &recipes)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let file_name = format!("recipes-{}.{}", Utc::now().format("%Y%m%d"), query.format.extension());
+        Ok((
+            [
+                (header::CONTENT_TYPE, query.format.content_type().to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+            ],
+            file,
+        ))
     }
 }
 
 pub mod inventory_handlers {
     use super::*;
 
-    pub async fn get_inventory(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_inventory(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get inventory endpoint"})))
     }
 
     pub async fn add_item(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Add item endpoint"})))
@@ -401,12 +1316,134 @@ pub mod inventory_handlers {
 
     pub async fn update_item(
         State(_state): State<AppState>,
+        _user: AuthUser,
         Json(_payload): Json<Value>,
     ) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Update item endpoint"})))
     }
 
-    pub async fn get_expiring(State(_state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    pub async fn get_expiring(State(_state): State<AppState>, _user: AuthUser) -> Result<Json<Value>, StatusCode> {
         Ok(Json(serde_json::json!({"message": "Get expiring endpoint"})))
     }
 }
+
+pub mod coach_handlers {
+    use super::*;
+    use crate::auth::authorize_read_access;
+    use crate::models::{CoachClientLink, DailyNutritionRecord};
+    use axum::extract::Path;
+    use uuid::Uuid;
+
+    pub async fn list_clients(
+        State(state): State<AppState>,
+        user: AuthUser,
+    ) -> Result<Json<Vec<CoachClientLink>>, StatusCode> {
+        let clients = sqlx::query_as::<_, CoachClientLink>(
+            "SELECT u.id AS user_id, u.username, u.email, g.granted_at \
+             FROM coach_client_grants g JOIN users u ON u.id = g.client_id \
+             WHERE g.coach_id = $1 AND u.is_active IS NOT FALSE ORDER BY u.username",
+        )
+        .bind(user.id)
+        .fetch_all(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(Json(clients))
+    }
+
+    /// Last 30 days of a client's daily nutrition totals.
+    pub async fn get_client_nutrition(
+        State(state): State<AppState>,
+        user: AuthUser,
+        Path(client_id): Path<Uuid>,
+    ) -> Result<Json<Vec<DailyNutritionRecord>>, StatusCode> {
+        authorize_read_access(&state.db, &user, client_id).await?;
+
+        let records = sqlx::query_as::<_, DailyNutritionRecord>(
+            "SELECT date, total_calories::float8 AS total_calories, total_protein_g::float8 AS total_protein_g, \
+                    total_carbs_g::float8 AS total_carbs_g, total_fat_g::float8 AS total_fat_g, \
+                    total_fiber_g::float8 AS total_fiber_g, total_sodium_mg::float8 AS total_sodium_mg, \
+                    goal_adherence_score::float8 AS goal_adherence_score \
+             FROM daily_nutrition WHERE user_id = $1 AND date >= CURRENT_DATE - 30 ORDER BY date",
+        )
+        .bind(client_id)
+        .fetch_all(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(Json(records))
+    }
+}
+
+pub mod admin_handlers {
+    use super::*;
+    use crate::models::{AdminUserView, ApiRequestLog, PageQuery, UpdateUserStatusRequest};
+    use crate::sessions;
+    use axum::extract::{Path, Query};
+    use uuid::Uuid;
+
+    pub async fn list_users(
+        State(state): State<AppState>,
+        Query(page): Query<PageQuery>,
+    ) -> Result<Json<Vec<AdminUserView>>, StatusCode> {
+        let users = sqlx::query_as::<_, AdminUserView>(
+            "SELECT id, username, email, role, is_active, email_verified, created_at \
+             FROM users ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+        )
+        .bind(page.limit())
+        .bind(page.offset())
+        .fetch_all(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(Json(users))
+    }
+
+    /// Activates or deactivates an account; deactivation also ends all of its sessions.
+    pub async fn update_user_status(
+        State(state): State<AppState>,
+        admin: AuthUser,
+        Path(user_id): Path<Uuid>,
+        Json(payload): Json<UpdateUserStatusRequest>,
+    ) -> Result<StatusCode, StatusCode> {
+        if user_id == admin.id && !payload.is_active {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        let updated = sqlx::query("UPDATE users SET is_active = $2 WHERE id = $1")
+            .bind(user_id)
+            .bind(payload.is_active)
+            .execute(&state.db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if updated.rows_affected() == 0 {
+            return Err(StatusCode::NOT_FOUND);
+        }
+
+        if !payload.is_active {
+            sessions::revoke_all_sessions(&state, user_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    pub async fn list_api_requests(
+        State(state): State<AppState>,
+        Query(page): Query<PageQuery>,
+    ) -> Result<Json<Vec<ApiRequestLog>>, StatusCode> {
+        let requests = sqlx::query_as::<_, ApiRequestLog>(
+            "SELECT id, user_id, method, path, status_code, response_time_ms, host(ip_address) AS ip_address, user_agent, created_at \
+             FROM api_requests ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+        )
+        .bind(page.limit())
+        .bind(page.offset())
+        .fetch_all(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(Json(requests))
+    }
+}
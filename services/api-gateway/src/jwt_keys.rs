@@ -0,0 +1,164 @@
+// Ed25519 (EdDSA) keys for signing and verifying access tokens
+//
+// Keys are loaded from `JWT_KEYS_DIR`:
+//   <kid>.pem      PKCS#8 private key; can sign and verify
+//   <kid>.pub.pem  SPKI public key; verify only (a retired signing key kept until its tokens expire)
+// The signing key is `JWT_SIGNING_KID`, or the private key with the greatest kid.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// DER-encoded Ed25519 SubjectPublicKeyInfo is this 12-byte prefix followed by the raw key.
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+static KEYS: OnceLock<KeyStore> = OnceLock::new();
+
+struct VerificationKey {
+    decoding: DecodingKey,
+    public_key: Vec<u8>,
+}
+
+pub struct KeyStore {
+    signing_kid: String,
+    signing_key: EncodingKey,
+    verification_keys: BTreeMap<String, VerificationKey>,
+}
+
+impl KeyStore {
+    pub fn signing_kid(&self) -> &str {
+        &self.signing_kid
+    }
+
+    pub fn signing_key(&self) -> &EncodingKey {
+        &self.signing_key
+    }
+
+    pub fn decoding_key(&self, kid: &str) -> Option<&DecodingKey> {
+        self.verification_keys.get(kid).map(|k| &k.decoding)
+    }
+
+    /// Public half of every verification key, in JWKS format.
+    pub fn jwks(&self) -> Value {
+        let keys: Vec<Value> = self
+            .verification_keys
+            .iter()
+            .map(|(kid, key)| {
+                json!({
+                    "kty": "OKP",
+                    "crv": "Ed25519",
+                    "alg": "EdDSA",
+                    "use": "sig",
+                    "kid": kid,
+                    "x": URL_SAFE_NO_PAD.encode(&key.public_key),
+                })
+            })
+            .collect();
+        json!({ "keys": keys })
+    }
+
+    fn from_dir(dir: &Path, signing_kid: Option<String>) -> Result<Self> {
+        let mut private_keys = BTreeMap::new();
+        let mut verification_keys = BTreeMap::new();
+
+        for entry in std::fs::read_dir(dir).with_context(|| format!("reading JWT key directory {}", dir.display()))? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let contents = std::fs::read(&path)?;
+
+            if let Some(kid) = file_name.strip_suffix(".pub.pem") {
+                let der = pem::parse(&contents)?.into_contents();
+                let public_key = der
+                    .strip_prefix(&ED25519_SPKI_PREFIX[..])
+                    .filter(|raw| raw.len() == 32)
+                    .ok_or_else(|| anyhow!("{} is not an Ed25519 public key", path.display()))?
+                    .to_vec();
+                verification_keys.insert(kid.to_string(), VerificationKey {
+                    decoding: DecodingKey::from_ed_der(&public_key),
+                    public_key,
+                });
+            } else if let Some(kid) = file_name.strip_suffix(".pem") {
+                let der = pem::parse(&contents)?.into_contents();
+                let pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der)
+                    .map_err(|_| anyhow!("{} is not an Ed25519 PKCS#8 private key", path.display()))?;
+                let public_key = pair.public_key().as_ref().to_vec();
+                verification_keys.insert(kid.to_string(), VerificationKey {
+                    decoding: DecodingKey::from_ed_der(&public_key),
+                    public_key,
+                });
+                private_keys.insert(kid.to_string(), EncodingKey::from_ed_der(&der));
+            }
+        }
+
+        let signing_kid = match signing_kid {
+            Some(kid) => kid,
+            None => private_keys
+                .keys()
+                .next_back()
+                .cloned()
+                .ok_or_else(|| anyhow!("no private key (<kid>.pem) in {}", dir.display()))?,
+        };
+        let signing_key = private_keys
+            .remove(&signing_kid)
+            .ok_or_else(|| anyhow!("signing key {} not found in {}", signing_kid, dir.display()))?;
+
+        Ok(Self {
+            signing_kid,
+            signing_key,
+            verification_keys,
+        })
+    }
+
+    /// Throwaway key for local development; tokens stop verifying when the process restarts.
+    fn ephemeral() -> Result<Self> {
+        let der = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(|_| anyhow!("failed to generate Ed25519 key"))?;
+        let pair = Ed25519KeyPair::from_pkcs8(der.as_ref()).map_err(|_| anyhow!("failed to load generated key"))?;
+        let public_key = pair.public_key().as_ref().to_vec();
+        let kid = format!("dev-{}", chrono::Utc::now().format("%Y%m%d%H%M%S"));
+
+        let mut verification_keys = BTreeMap::new();
+        verification_keys.insert(kid.clone(), VerificationKey {
+            decoding: DecodingKey::from_ed_der(&public_key),
+            public_key,
+        });
+
+        Ok(Self {
+            signing_kid: kid,
+            signing_key: EncodingKey::from_ed_der(der.as_ref()),
+            verification_keys,
+        })
+    }
+}
+
+/// `APP_ENV=development` relaxes startup checks; anything else is treated as production.
+pub fn dev_mode() -> bool {
+    matches!(std::env::var("APP_ENV").as_deref(), Ok("development") | Ok("dev"))
+}
+
+/// Loads the key store once at startup. Outside dev mode a missing key directory is fatal,
+/// so a misconfigured deployment cannot come up with keys nobody else can verify.
+pub fn init() -> Result<()> {
+    let store = match std::env::var("JWT_KEYS_DIR").ok().filter(|d| !d.is_empty()) {
+        Some(dir) => KeyStore::from_dir(Path::new(&dir), std::env::var("JWT_SIGNING_KID").ok().filter(|k| !k.is_empty()))?,
+        None if dev_mode() => {
+            tracing::warn!("JWT_KEYS_DIR not set; using an ephemeral signing key (development only)");
+            KeyStore::ephemeral()?
+        }
+        None => bail!("JWT_KEYS_DIR must point at the JWT signing keys (set APP_ENV=development to use a throwaway key)"),
+    };
+
+    tracing::info!("JWT signing key: {} ({} verification keys)", store.signing_kid(), store.verification_keys.len());
+    KEYS.set(store).map_err(|_| anyhow!("JWT keys already initialized"))
+}
+
+pub fn keys() -> &'static KeyStore {
+    KEYS.get().expect("jwt_keys::init must run before tokens are issued or verified")
+}
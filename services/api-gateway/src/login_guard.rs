@@ -0,0 +1,187 @@
+// Brute-force protection for password and second-factor login steps
+//
+// Failed attempts are counted in Redis per (account, client IP) pair, per client IP and
+// per account. Once a counter passes its threshold that pair, IP or account is locked, and
+// every further failure doubles the lockout, up to an hour. Keying the first counter by IP
+// too means someone guessing at an email only locks themselves out, not the account's
+// owner; the account-wide counter has a much higher threshold and only catches guessing
+// spread over many addresses.
+
+use redis::aio::Connection;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::database;
+use crate::sessions::ClientInfo;
+use crate::AppState;
+
+const ACCOUNT_FAILURE_THRESHOLD: i64 = 5;
+const IP_FAILURE_THRESHOLD: i64 = 20;
+const EMAIL_FAILURE_THRESHOLD: i64 = 50;
+const BASE_LOCKOUT_SECONDS: i64 = 30;
+const MAX_LOCKOUT_SECONDS: i64 = 3600;
+/// Failure counters are forgotten after an hour without new failures.
+const FAILURE_WINDOW_SECONDS: i64 = 3600;
+
+/// Clients without a known address share one bucket rather than escaping the IP limit.
+fn client_ip(client: &ClientInfo) -> &str {
+    client.ip_address.as_deref().unwrap_or("unknown")
+}
+
+fn account_key(kind: &str, email: &str, client: &ClientInfo) -> String {
+    format!("login:{}:account:{}:{}", kind, email.trim().to_lowercase(), client_ip(client))
+}
+
+fn email_key(kind: &str, email: &str) -> String {
+    format!("login:{}:email:{}", kind, email.trim().to_lowercase())
+}
+
+fn ip_key(kind: &str, client: &ClientInfo) -> String {
+    format!("login:{}:ip:{}", kind, client_ip(client))
+}
+
+fn lockout_seconds(failures: i64, threshold: i64) -> Option<i64> {
+    if failures < threshold {
+        return None;
+    }
+    let doublings = (failures - threshold).min(16) as u32;
+    Some((BASE_LOCKOUT_SECONDS * 2i64.pow(doublings)).min(MAX_LOCKOUT_SECONDS))
+}
+
+async fn connection(state: &AppState) -> Option<Connection> {
+    match state.redis.get_async_connection().await {
+        Ok(conn) => Some(conn),
+        Err(e) => {
+            // Failing open keeps logins working during a Redis outage
+            tracing::warn!("Redis unavailable for login throttling: {}", e);
+            None
+        }
+    }
+}
+
+/// Returns the number of seconds the caller has to wait, if the account (from this IP or
+/// from anywhere) or the IP is locked.
+pub async fn locked_for(state: &AppState, email: &str, client: &ClientInfo) -> Option<i64> {
+    let mut conn = connection(state).await?;
+
+    let mut remaining = 0;
+    for key in [account_key("lock", email, client), email_key("lock", email), ip_key("lock", client)] {
+        let ttl: i64 = redis::cmd("TTL").arg(&key).query_async(&mut conn).await.unwrap_or(-2);
+        remaining = remaining.max(ttl);
+    }
+    (remaining > 0).then_some(remaining)
+}
+
+async fn bump(conn: &mut Connection, counter: &str, lock: &str, threshold: i64) -> Option<(i64, i64)> {
+    let (failures,): (i64,) = redis::pipe()
+        .atomic()
+        .incr(counter, 1)
+        .expire(counter, FAILURE_WINDOW_SECONDS as usize)
+        .ignore()
+        .query_async(conn)
+        .await
+        .ok()?;
+
+    let seconds = lockout_seconds(failures, threshold)?;
+    redis::cmd("SET")
+        .arg(lock)
+        .arg(1)
+        .arg("EX")
+        .arg(seconds)
+        .query_async::<_, ()>(conn)
+        .await
+        .ok()?;
+    Some((failures, seconds))
+}
+
+/// Counts a failed attempt. `user_id` is set when the email belongs to a real account,
+/// so lockouts can be recorded in `user_activities` for admins to review.
+pub async fn record_failure(state: &AppState, email: &str, client: &ClientInfo, user_id: Option<Uuid>) {
+    let Some(mut conn) = connection(state).await else {
+        return;
+    };
+
+    let account_lock = bump(
+        &mut conn,
+        &account_key("fail", email, client),
+        &account_key("lock", email, client),
+        ACCOUNT_FAILURE_THRESHOLD,
+    )
+    .await;
+    let email_lock = bump(
+        &mut conn,
+        &email_key("fail", email),
+        &email_key("lock", email),
+        EMAIL_FAILURE_THRESHOLD,
+    )
+    .await;
+    let ip_lock = bump(&mut conn, &ip_key("fail", client), &ip_key("lock", client), IP_FAILURE_THRESHOLD).await;
+
+    for (scope, lock) in [("account", account_lock), ("email", email_lock), ("ip", ip_lock)] {
+        let Some((failures, seconds)) = lock else {
+            continue;
+        };
+        tracing::warn!(
+            "Login lockout ({}) for {} from {:?}: {} failures, locked for {}s",
+            scope, email, client.ip_address, failures, seconds
+        );
+        if let Some(user_id) = user_id {
+            let data = json!({
+                "scope": scope,
+                "email": email,
+                "failed_attempts": failures,
+                "locked_for_seconds": seconds,
+            });
+            if let Err(e) = database::record_user_activity(&state.db, user_id, "login_lockout", data, client).await {
+                tracing::warn!("Failed to record lockout activity: {}", e);
+            }
+        }
+    }
+}
+
+/// A successful login clears the account's failure counters; the IP counter is left alone
+/// so one valid account cannot be used to reset an attacker's budget.
+pub async fn record_success(state: &AppState, email: &str, client: &ClientInfo) {
+    if let Some(mut conn) = connection(state).await {
+        let _: Result<(), _> = redis::cmd("DEL")
+            .arg(account_key("fail", email, client))
+            .arg(email_key("fail", email))
+            .query_async(&mut conn)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_lockout_below_the_threshold() {
+        assert_eq!(lockout_seconds(0, ACCOUNT_FAILURE_THRESHOLD), None);
+        assert_eq!(lockout_seconds(ACCOUNT_FAILURE_THRESHOLD - 1, ACCOUNT_FAILURE_THRESHOLD), None);
+        assert_eq!(lockout_seconds(EMAIL_FAILURE_THRESHOLD - 1, EMAIL_FAILURE_THRESHOLD), None);
+        assert_eq!(lockout_seconds(ACCOUNT_FAILURE_THRESHOLD, ACCOUNT_FAILURE_THRESHOLD), Some(BASE_LOCKOUT_SECONDS));
+    }
+
+    #[test]
+    fn lockout_doubles_with_each_further_failure() {
+        assert_eq!(lockout_seconds(6, 5), Some(60));
+        assert_eq!(lockout_seconds(7, 5), Some(120));
+        assert_eq!(lockout_seconds(10, 5), Some(960));
+        assert_eq!(lockout_seconds(21, 20), Some(60));
+    }
+
+    #[test]
+    fn lockout_is_capped_at_an_hour() {
+        assert_eq!(lockout_seconds(12, 5), Some(MAX_LOCKOUT_SECONDS));
+        assert_eq!(lockout_seconds(1_000, 5), Some(MAX_LOCKOUT_SECONDS));
+        assert_eq!(lockout_seconds(i64::MAX, 5), Some(MAX_LOCKOUT_SECONDS));
+    }
+
+    #[test]
+    fn account_keys_ignore_email_case_and_whitespace() {
+        let client = ClientInfo { ip_address: Some("10.0.0.1".into()), ..Default::default() };
+        assert_eq!(account_key("fail", " Ann@Example.com ", &client), "login:fail:account:ann@example.com:10.0.0.1");
+        assert_eq!(email_key("lock", "Ann@Example.com"), "login:lock:email:ann@example.com");
+    }
+}
@@ -0,0 +1,131 @@
+// Outgoing mail: a transport-agnostic `Mailer` with SMTP and file/log implementations
+
+use anyhow::{Context, Result};
+use axum::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: &EmailMessage) -> Result<()>;
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(host: &str, port: u16, username: Option<String>, password: Option<String>, from: &str) -> Result<Self> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?.port(port);
+        if let (Some(username), Some(password)) = (username, password) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: from.parse().context("invalid MAIL_FROM address")?,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<()> {
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(message.to.parse()?)
+            .subject(&message.subject)
+            .body(message.body.clone())?;
+
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
+
+/// Writes each message to `<dir>/<timestamp>-<recipient>.eml`, or only logs it when no
+/// directory is configured. Meant for local development and tests.
+pub struct FileMailer {
+    dir: Option<PathBuf>,
+}
+
+impl FileMailer {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self { dir }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            tracing::info!("📧 Mail to {}: {}\n{}", message.to, message.subject, message.body);
+            return Ok(());
+        };
+
+        tokio::fs::create_dir_all(dir).await?;
+        let file_name = format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+            message.to.replace(|c: char| !c.is_ascii_alphanumeric() && c != '.', "_"),
+        );
+        let contents = format!("To: {}\nSubject: {}\n\n{}\n", message.to, message.subject, message.body);
+        tokio::fs::write(dir.join(file_name), contents).await?;
+        Ok(())
+    }
+}
+
+/// Picks the transport from `MAIL_TRANSPORT` (`smtp`, `file` or `log`; defaults to `log`).
+pub fn mailer_from_env() -> Result<Arc<dyn Mailer>> {
+    let transport = std::env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "log".to_string());
+
+    match transport.as_str() {
+        "smtp" => {
+            let host = std::env::var("SMTP_HOST").context("SMTP_HOST is required for MAIL_TRANSPORT=smtp")?;
+            let port = std::env::var("SMTP_PORT")
+                .ok()
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(587);
+            let from = std::env::var("MAIL_FROM").unwrap_or_else(|_| "Meal Prep Pro <no-reply@mealprep.com>".to_string());
+            Ok(Arc::new(SmtpMailer::new(
+                &host,
+                port,
+                std::env::var("SMTP_USERNAME").ok().filter(|v| !v.is_empty()),
+                std::env::var("SMTP_PASSWORD").ok().filter(|v| !v.is_empty()),
+                &from,
+            )?))
+        }
+        "file" => {
+            let dir = std::env::var("MAIL_OUTPUT_DIR").unwrap_or_else(|_| "./mail".to_string());
+            Ok(Arc::new(FileMailer::new(Some(PathBuf::from(dir)))))
+        }
+        _ => Ok(Arc::new(FileMailer::new(None))),
+    }
+}
+
+/// Keeps every message in memory so tests can read the links that were sent.
+#[cfg(test)]
+#[derive(Default)]
+pub struct CapturingMailer {
+    pub sent: std::sync::Mutex<Vec<EmailMessage>>,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl Mailer for CapturingMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<()> {
+        self.sent.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+}
@@ -4,19 +4,34 @@ use axum::{
     middleware::Next,
     response::Response,
 };
+use std::time::Instant;
 use crate::AppState;
-use crate::auth::{validate_jwt};
+use crate::api_keys::{self, KeyCheck};
+use crate::auth::{validate_jwt, AuthUser, Role};
+use crate::sessions::{self, ClientInfo};
+
+/// Auth routes that are reachable without an access token.
+const PUBLIC_AUTH_PATHS: &[&str] = &[
+    "/api/auth/register",
+    "/api/auth/login",
+    "/api/auth/refresh",
+    "/api/auth/verify-email",
+    "/api/auth/forgot-password",
+    "/api/auth/reset-password",
+    "/api/auth/2fa/verify",
+];
 
 pub async fn auth_middleware(
-    State(_state): State<AppState>,
-    request: Request,
+    State(state): State<AppState>,
+    mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
     let path = request.uri().path();
-    if path.starts_with("/api/auth")
+    if PUBLIC_AUTH_PATHS.contains(&path)
         || path == "/health"
         || path == "/metrics"
         || path == "/ws"
+        || path == "/.well-known/jwks.json"
     {
         return Ok(next.run(request).await);
     }
@@ -29,14 +44,85 @@ pub async fn auth_middleware(
         .or(auth_header);
 
     if let Some(token) = token {
-        if token == "demo-token" {
-            // Allow demo token
-            return Ok(next.run(request).await);
+        if token.starts_with(api_keys::KEY_PREFIX) {
+            let check = api_keys::authenticate(&state.db, token, request.method(), request.uri().path())
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            return match check {
+                KeyCheck::Allowed(user) => {
+                    request.extensions_mut().insert(user);
+                    Ok(next.run(request).await)
+                }
+                KeyCheck::Forbidden => Err(StatusCode::FORBIDDEN),
+                KeyCheck::Invalid => Err(StatusCode::UNAUTHORIZED),
+            };
         }
-        if validate_jwt(token).is_some() {
+
+        if let Some(data) = validate_jwt(token) {
+            let user = AuthUser::try_from(data.claims).map_err(|_| StatusCode::UNAUTHORIZED)?;
+            if let Some(sid) = user.session_id {
+                if sessions::is_session_revoked(&state, sid).await {
+                    return Err(StatusCode::UNAUTHORIZED);
+                }
+            }
+            request.extensions_mut().insert(user);
             return Ok(next.run(request).await);
         }
     }
 
     Err(StatusCode::UNAUTHORIZED)
 }
+
+/// Route guard used with `route_layer` in `create_router`; runs after `auth_middleware`.
+pub async fn require_role(
+    allowed: &'static [Role],
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let user = request.extensions().get::<AuthUser>().ok_or(StatusCode::UNAUTHORIZED)?;
+    if !allowed.contains(&user.role) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(next.run(request).await)
+}
+
+/// Records every request that made it past authentication into `api_requests`.
+pub async fn request_log_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let user_id = request.extensions().get::<AuthUser>().map(|u| u.id);
+    let client = ClientInfo::from_request(request.headers(), request.extensions());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let status_code = response.status().as_u16() as i32;
+    let response_time_ms = started.elapsed().as_millis() as i32;
+
+    // Logging must never slow down or fail the request itself
+    tokio::spawn(async move {
+        let result = sqlx::query(
+            "INSERT INTO api_requests (user_id, method, path, status_code, response_time_ms, ip_address, user_agent) \
+             VALUES ($1, $2, $3, $4, $5, $6::inet, $7)",
+        )
+        .bind(user_id)
+        .bind(&method)
+        .bind(&path)
+        .bind(status_code)
+        .bind(response_time_ms)
+        .bind(&client.ip_address)
+        .bind(&client.user_agent)
+        .execute(&state.db)
+        .await;
+
+        if let Err(e) = result {
+            tracing::debug!("Failed to record API request {} {}: {}", method, path, e);
+        }
+    });
+
+    response
+}
@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
-use validator::Validate;
+use chrono::{DateTime, NaiveDate, Utc};
+use validator::{Validate, ValidationError};
 use serde_json::Value as JsonValue;
+use crate::auth::Role;
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct User {
@@ -16,11 +17,18 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
 pub struct UserProfile {
+    #[validate(length(max = 255))]
     pub full_name: String,
+    /// Derived from `date_of_birth` when reading; ignored on writes.
+    #[serde(default)]
     pub age: Option<i32>,
+    #[serde(default)]
+    pub date_of_birth: Option<NaiveDate>,
+    #[validate(range(min = 50.0, max = 272.0))]
     pub height_cm: Option<f32>,
+    #[validate(range(min = 20.0, max = 650.0))]
     pub weight_kg: Option<f32>,
     pub activity_level: ActivityLevel,
     pub dietary_restrictions: Vec<String>,
@@ -28,11 +36,12 @@ pub struct UserProfile {
     pub health_goals: Vec<HealthGoal>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
-#[sqlx(type_name = "activity_level", rename_all = "snake_case")]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "activity_level_enum", rename_all = "snake_case")]
 pub enum ActivityLevel {
     Sedentary,
     LightlyActive,
+    #[default]
     ModeratelyActive,
     VeryActive,
     ExtraActive,
@@ -50,42 +59,101 @@ pub enum HealthGoal {
     ReducedInflammation,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
 pub struct UserPreferences {
     pub preferred_cuisines: Vec<String>,
     pub cooking_time_preference: CookingTimePreference,
     pub meal_prep_frequency: MealPrepFrequency,
+    #[validate]
     pub budget_range: BudgetRange,
     pub kitchen_equipment: Vec<String>,
     pub notification_settings: NotificationSettings,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
-#[sqlx(type_name = "cooking_time_preference", rename_all = "snake_case")]
+/// Stored as VARCHAR in `user_preferences`, hence the string conversions instead of `sqlx::Type`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub enum CookingTimePreference {
     Quick,      // < 30 min
+    #[default]
     Medium,     // 30-60 min
     Extended,   // > 60 min
     Any,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
-#[sqlx(type_name = "meal_prep_frequency", rename_all = "snake_case")]
+impl CookingTimePreference {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CookingTimePreference::Quick => "quick",
+            CookingTimePreference::Medium => "medium",
+            CookingTimePreference::Extended => "extended",
+            CookingTimePreference::Any => "any",
+        }
+    }
+}
+
+impl From<String> for CookingTimePreference {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "quick" => CookingTimePreference::Quick,
+            "extended" => CookingTimePreference::Extended,
+            "any" => CookingTimePreference::Any,
+            _ => CookingTimePreference::Medium,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub enum MealPrepFrequency {
     Daily,
     EveryOtherDay,
     TwiceWeekly,
+    #[default]
     Weekly,
     BiWeekly,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl MealPrepFrequency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MealPrepFrequency::Daily => "daily",
+            MealPrepFrequency::EveryOtherDay => "every_other_day",
+            MealPrepFrequency::TwiceWeekly => "twice_weekly",
+            MealPrepFrequency::Weekly => "weekly",
+            MealPrepFrequency::BiWeekly => "bi_weekly",
+        }
+    }
+}
+
+impl From<String> for MealPrepFrequency {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "daily" => MealPrepFrequency::Daily,
+            "every_other_day" => MealPrepFrequency::EveryOtherDay,
+            "twice_weekly" => MealPrepFrequency::TwiceWeekly,
+            "bi_weekly" => MealPrepFrequency::BiWeekly,
+            _ => MealPrepFrequency::Weekly,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "validate_budget_range"))]
 pub struct BudgetRange {
+    #[validate(range(min = 0.0, max = 10000.0))]
     pub min_per_meal: f32,
+    #[validate(range(min = 0.0, max = 10000.0))]
     pub max_per_meal: f32,
+    #[validate(range(min = 0.0, max = 100000.0))]
     pub weekly_limit: Option<f32>,
 }
 
+fn validate_budget_range(budget: &BudgetRange) -> Result<(), ValidationError> {
+    if budget.max_per_meal < budget.min_per_meal {
+        return Err(ValidationError::new("max_per_meal_below_min"));
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NotificationSettings {
     pub meal_reminders: bool,
@@ -95,6 +163,149 @@ pub struct NotificationSettings {
     pub weekly_summary: bool,
 }
 
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            meal_reminders: true,
+            prep_reminders: true,
+            shopping_reminders: true,
+            goal_updates: true,
+            weekly_summary: true,
+        }
+    }
+}
+
+/// Deserializes a PATCH field so that an absent key stays `None` (via `#[serde(default)]`)
+/// while an explicit `null` becomes `Some(None)`, i.e. "clear this value".
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// PATCH body for `/api/users/profile`: only the fields present are changed, and nullable
+/// fields sent as `null` are cleared.
+#[derive(Debug, Default, Deserialize)]
+pub struct UserProfileUpdate {
+    pub full_name: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub date_of_birth: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub height_cm: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub weight_kg: Option<Option<f32>>,
+    pub activity_level: Option<ActivityLevel>,
+    pub dietary_restrictions: Option<Vec<String>>,
+    pub allergies: Option<Vec<String>>,
+    pub health_goals: Option<Vec<HealthGoal>>,
+}
+
+impl UserProfileUpdate {
+    pub fn apply(self, profile: &mut UserProfile) {
+        if let Some(v) = self.full_name {
+            profile.full_name = v;
+        }
+        if let Some(v) = self.date_of_birth {
+            profile.date_of_birth = v;
+        }
+        if let Some(v) = self.height_cm {
+            profile.height_cm = v;
+        }
+        if let Some(v) = self.weight_kg {
+            profile.weight_kg = v;
+        }
+        if let Some(v) = self.activity_level {
+            profile.activity_level = v;
+        }
+        if let Some(v) = self.dietary_restrictions {
+            profile.dietary_restrictions = v;
+        }
+        if let Some(v) = self.allergies {
+            profile.allergies = v;
+        }
+        if let Some(v) = self.health_goals {
+            profile.health_goals = v;
+        }
+    }
+}
+
+/// PATCH body for `/api/users/preferences`; nested budget and notification fields merge individually.
+#[derive(Debug, Default, Deserialize)]
+pub struct UserPreferencesUpdate {
+    pub preferred_cuisines: Option<Vec<String>>,
+    pub cooking_time_preference: Option<CookingTimePreference>,
+    pub meal_prep_frequency: Option<MealPrepFrequency>,
+    pub budget_range: Option<BudgetRangeUpdate>,
+    pub kitchen_equipment: Option<Vec<String>>,
+    pub notification_settings: Option<NotificationSettingsUpdate>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BudgetRangeUpdate {
+    pub min_per_meal: Option<f32>,
+    pub max_per_meal: Option<f32>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub weekly_limit: Option<Option<f32>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NotificationSettingsUpdate {
+    pub meal_reminders: Option<bool>,
+    pub prep_reminders: Option<bool>,
+    pub shopping_reminders: Option<bool>,
+    pub goal_updates: Option<bool>,
+    pub weekly_summary: Option<bool>,
+}
+
+impl UserPreferencesUpdate {
+    pub fn apply(self, preferences: &mut UserPreferences) {
+        if let Some(v) = self.preferred_cuisines {
+            preferences.preferred_cuisines = v;
+        }
+        if let Some(v) = self.cooking_time_preference {
+            preferences.cooking_time_preference = v;
+        }
+        if let Some(v) = self.meal_prep_frequency {
+            preferences.meal_prep_frequency = v;
+        }
+        if let Some(v) = self.kitchen_equipment {
+            preferences.kitchen_equipment = v;
+        }
+        if let Some(budget) = self.budget_range {
+            let current = &mut preferences.budget_range;
+            if let Some(v) = budget.min_per_meal {
+                current.min_per_meal = v;
+            }
+            if let Some(v) = budget.max_per_meal {
+                current.max_per_meal = v;
+            }
+            if let Some(v) = budget.weekly_limit {
+                current.weekly_limit = v;
+            }
+        }
+        if let Some(n) = self.notification_settings {
+            let current = &mut preferences.notification_settings;
+            if let Some(v) = n.meal_reminders {
+                current.meal_reminders = v;
+            }
+            if let Some(v) = n.prep_reminders {
+                current.prep_reminders = v;
+            }
+            if let Some(v) = n.shopping_reminders {
+                current.shopping_reminders = v;
+            }
+            if let Some(v) = n.goal_updates {
+                current.goal_updates = v;
+            }
+            if let Some(v) = n.weekly_summary {
+                current.weekly_summary = v;
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Recipe {
     pub id: Uuid,
@@ -118,6 +329,7 @@ pub struct Recipe {
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "difficulty_level", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
 pub enum DifficultyLevel {
     Beginner,
     Intermediate,
@@ -127,21 +339,27 @@ pub enum DifficultyLevel {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecipeIngredient {
+    #[serde(default)]
     pub ingredient_id: Uuid,
     pub name: String,
     pub amount: f32,
     pub unit: String,
     pub preparation_note: Option<String>,
+    #[serde(default)]
     pub substitutions: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecipeStep {
+    /// Numbered in order when 0 or missing
+    #[serde(default)]
     pub step_number: i32,
     pub instruction: String,
     pub time_estimate_minutes: Option<i32>,
     pub temperature: Option<String>,
+    #[serde(default)]
     pub equipment_needed: Vec<String>,
+    #[serde(default)]
     pub tips: Vec<String>,
 }
 
@@ -156,6 +374,7 @@ pub struct NutritionInfo {
     pub sodium_mg: f32,
     pub vitamins: JsonValue, // Store as JSON
     pub minerals: JsonValue, // Store as JSON
+    /// 0-100 meal quality score from the nutrition service (Nutri-Score and NRF9.3)
     pub nutrition_score: Option<f32>,
 }
 
@@ -232,8 +451,13 @@ pub struct CreateUserRequest {
     pub email: String,
     #[validate(length(min = 8))]
     pub password: String,
-    pub profile: UserProfile,
-    pub preferences: UserPreferences,
+    /// Optional at sign-up; missing sections are stored with defaults.
+    #[serde(default)]
+    #[validate]
+    pub profile: Option<UserProfile>,
+    #[serde(default)]
+    #[validate]
+    pub preferences: Option<UserPreferences>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -242,14 +466,314 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    #[validate(length(min = 8))]
+    pub new_password: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
     pub access_token: String,
     pub refresh_token: String,
-    pub user: User,
+    pub user: UserSummary,
     pub expires_in: i64,
 }
 
+/// Login either completes, or stops at the second factor when TOTP is enabled.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(AuthResponse),
+    TwoFactorRequired(TwoFactorChallengeResponse),
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallengeResponse {
+    pub status: &'static str,
+    pub challenge_token: String,
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorLoginRequest {
+    pub challenge_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct UserSummary {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    #[sqlx(try_from = "String")]
+    pub role: Role,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct AdminUserView {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub is_active: Option<bool>,
+    pub email_verified: Option<bool>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserStatusRequest {
+    pub is_active: bool,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ApiRequestLog {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub method: String,
+    pub path: String,
+    pub status_code: i32,
+    pub response_time_ms: Option<i32>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl PageQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(50).clamp(1, 500)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrantCoachAccessRequest {
+    pub coach_email: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CoachClientLink {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub granted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct DailyNutritionRecord {
+    pub date: chrono::NaiveDate,
+    pub total_calories: Option<f64>,
+    pub total_protein_g: Option<f64>,
+    pub total_carbs_g: Option<f64>,
+    pub total_fat_g: Option<f64>,
+    pub total_fiber_g: Option<f64>,
+    pub total_sodium_mg: Option<f64>,
+    pub goal_adherence_score: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateApiKeyRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    /// `area:read` / `area:write`, e.g. `nutrition:write`.
+    #[validate(length(min = 1))]
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ApiKeyInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Ingredients may be plain-text lines ("2 cups (250 g) flour, sifted") or structured, and steps
+/// plain instructions or structured.
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateRecipeRequest {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    pub description: Option<String>,
+    #[validate(length(max = 50))]
+    pub cuisine_type: Option<String>,
+    pub difficulty_level: Option<DifficultyLevel>,
+    #[serde(default)]
+    #[validate(range(min = 0))]
+    pub prep_time_minutes: i32,
+    #[serde(default)]
+    #[validate(range(min = 0))]
+    pub cook_time_minutes: i32,
+    #[serde(default = "default_servings")]
+    #[validate(range(min = 1))]
+    pub servings: i32,
+    #[validate(length(min = 1))]
+    pub ingredients: Vec<crate::recipes::IngredientInput>,
+    #[serde(default)]
+    pub instructions: Vec<crate::recipes::StepInput>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub source_url: Option<String>,
+    pub image_url: Option<String>,
+}
+
+fn default_servings() -> i32 {
+    1
+}
+
+/// The stored recipe's id and its ingredients as they were read.
+#[derive(Debug, Serialize)]
+pub struct CreatedRecipe {
+    pub id: Uuid,
+    pub ingredients: Vec<RecipeIngredient>,
+}
+
+/// A recipe to import: a posted HTML page or JSON-LD document, parsed JSON-LD, or a URL to fetch.
+#[derive(Debug, Deserialize)]
+pub struct ImportRecipeRequest {
+    pub document: Option<String>,
+    pub json_ld: Option<serde_json::Value>,
+    /// Fetched when nothing else is posted; otherwise stored as where the document came from
+    pub url: Option<String>,
+}
+
+/// The stored recipe and what of the source could not be mapped onto it.
+#[derive(Debug, Serialize)]
+pub struct ImportedRecipeSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub ingredients: Vec<RecipeIngredient>,
+    pub steps: usize,
+    pub unmapped_fields: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecipeCollectionImportQuery {
+    /// Detected from the file when not given
+    pub format: Option<crate::recipe_collections::CollectionFormat>,
+    /// Only report what would be imported; pass `false` to store the recipes
+    #[serde(default = "default_dry_run")]
+    pub dry_run: bool,
+    /// Store recipes that duplicate existing ones or earlier ones in the file
+    #[serde(default)]
+    pub import_duplicates: bool,
+}
+
+fn default_dry_run() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecipeCollectionExportQuery {
+    pub format: crate::recipe_collections::CollectionFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecipeImportStatus {
+    /// Will be stored when the import is committed
+    New,
+    Imported,
+    /// Skipped as the same recipe as an existing one or one earlier in the file
+    Duplicate,
+    /// Could not be read; see `error`
+    Invalid,
+}
+
+/// What an import did, or in a dry run would do, with one recipe of the collection.
+#[derive(Debug, Serialize)]
+pub struct RecipeImportEntry {
+    pub source: String,
+    pub name: Option<String>,
+    pub status: RecipeImportStatus,
+    pub id: Option<Uuid>,
+    /// The user's recipe this one is the same as
+    pub duplicate_of: Option<Uuid>,
+    pub ingredients: usize,
+    pub steps: usize,
+    pub unmapped_fields: Vec<String>,
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecipeCollectionImportReport {
+    pub format: crate::recipe_collections::CollectionFormat,
+    pub dry_run: bool,
+    /// Recipes stored, or in a dry run that would be
+    pub imported: usize,
+    pub duplicates: usize,
+    pub invalid: usize,
+    pub recipes: Vec<RecipeImportEntry>,
+}
+
+/// Re-entering the password guards against deletion from a hijacked session.
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountDeletionStatus {
+    pub deletion_scheduled_for: DateTime<Utc>,
+}
+
+/// Returned once at creation; the plaintext `key` cannot be retrieved again.
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MealRecommendationRequest {
     pub meal_type: MealType,
@@ -319,3 +843,36 @@ pub struct Recommendation {
     pub priority: i32,
     pub estimated_benefit: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_update_distinguishes_null_from_missing() {
+        let mut profile = UserProfile {
+            height_cm: Some(180.0),
+            weight_kg: Some(80.0),
+            ..Default::default()
+        };
+
+        let update: UserProfileUpdate = serde_json::from_str(r#"{"height_cm": null}"#).unwrap();
+        update.apply(&mut profile);
+
+        assert_eq!(profile.height_cm, None);
+        assert_eq!(profile.weight_kg, Some(80.0));
+    }
+
+    #[test]
+    fn preferences_update_clears_weekly_limit() {
+        let mut preferences = UserPreferences::default();
+        preferences.budget_range.weekly_limit = Some(120.0);
+
+        let update: UserPreferencesUpdate =
+            serde_json::from_str(r#"{"budget_range": {"max_per_meal": 15.0, "weekly_limit": null}}"#).unwrap();
+        update.apply(&mut preferences);
+
+        assert_eq!(preferences.budget_range.weekly_limit, None);
+        assert_eq!(preferences.budget_range.max_per_meal, 15.0);
+    }
+}
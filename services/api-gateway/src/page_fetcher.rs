@@ -0,0 +1,175 @@
+// Fetching web pages for recipe import: a `PageFetcher` trait with an HTTP implementation
+//
+// Only public http(s) addresses are fetched: hosts resolving to loopback, private, link-local
+// or otherwise internal addresses are refused, also when reached through a redirect, so a
+// posted URL cannot be used to probe the internal network. Names are checked by the client's
+// own resolver, on the addresses it then connects to, so a name cannot resolve to a public
+// address for a check and an internal one for the connection.
+
+use anyhow::{bail, Context, Result};
+use axum::async_trait;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::Url;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Pages larger than this are not read.
+const MAX_PAGE_BYTES: usize = 5 * 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: usize = 5;
+
+#[async_trait]
+pub trait PageFetcher: Send + Sync {
+    /// The page's body as text.
+    async fn fetch(&self, url: &str) -> Result<String>;
+}
+
+pub struct HttpFetcher {
+    client: reqwest::Client,
+}
+
+impl HttpFetcher {
+    pub fn new() -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent("MealPrepPro-RecipeImport/1.0")
+            .dns_resolver(Arc::new(PublicResolver))
+            // Through a proxy the resolver would only ever see the proxy's name
+            .no_proxy()
+            .redirect(reqwest::redirect::Policy::custom(|attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                // Address literals are connected to without a lookup, so the resolver never sees them
+                } else if attempt.url().host_str().is_some_and(is_internal_host_name) {
+                    attempt.error("redirect to an internal address")
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()?;
+        Ok(Self { client })
+    }
+}
+
+fn is_internal_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64)
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || ip.to_ipv4_mapped().is_some_and(|v4| is_internal_address(IpAddr::V4(v4)))
+        }
+    }
+}
+
+/// Host names and address literals that are internal without a DNS lookup.
+fn is_internal_host_name(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(ip) => is_internal_address(ip),
+        Err(_) => host.eq_ignore_ascii_case("localhost") || host.to_ascii_lowercase().ends_with(".localhost"),
+    }
+}
+
+/// Resolves names like the system resolver, but fails for a name with any internal address.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if addresses.iter().any(|address| is_internal_address(address.ip())) {
+                return Err(format!("{} resolves to an internal address", host).into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[async_trait]
+impl PageFetcher for HttpFetcher {
+    async fn fetch(&self, url: &str) -> Result<String> {
+        let parsed = Url::parse(url).context("invalid URL")?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            bail!("only http and https URLs can be imported");
+        }
+        let host = parsed.host_str().context("URL has no host")?;
+        if is_internal_host_name(host) {
+            bail!("{} is an internal address", host);
+        }
+
+        let mut response = self.client.get(parsed).send().await?.error_for_status()?;
+        if response.content_length().is_some_and(|length| length as usize > MAX_PAGE_BYTES) {
+            bail!("page is larger than {} bytes", MAX_PAGE_BYTES);
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() > MAX_PAGE_BYTES {
+                bail!("page is larger than {} bytes", MAX_PAGE_BYTES);
+            }
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+/// Picks the fetcher from `RECIPE_FETCH` (`http` or `none`; defaults to `none`, which leaves
+/// recipe import to posted documents).
+pub fn fetcher_from_env() -> Result<Option<Arc<dyn PageFetcher>>> {
+    let fetcher = std::env::var("RECIPE_FETCH").unwrap_or_else(|_| "none".to_string());
+
+    match fetcher.as_str() {
+        "http" => Ok(Some(Arc::new(HttpFetcher::new()?))),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn recognizes_internal_hosts() {
+        let internal = [
+            "127.0.0.1",
+            "10.1.2.3",
+            "192.168.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "[::1]",
+            "[fd00::1]",
+            "[::ffff:10.0.0.1]",
+            "localhost",
+            "api.localhost",
+        ];
+        for host in internal {
+            assert!(is_internal_host_name(host), "{}", host);
+        }
+        for host in ["93.184.216.34", "[2606:2800:220:1::1]", "example.com"] {
+            assert!(!is_internal_host_name(host), "{}", host);
+        }
+    }
+
+    #[tokio::test]
+    async fn resolver_refuses_names_with_internal_addresses() {
+        let resolved = PublicResolver.resolve(Name::from_str("localhost").unwrap()).await;
+        assert!(resolved.is_err());
+    }
+}
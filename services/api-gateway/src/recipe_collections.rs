@@ -0,0 +1,1221 @@
+// Recipe collections: importing and exporting many recipes at once
+//
+// Formats are Paprika (`.paprikarecipes`, a zip of gzipped JSON recipes, or a single gzipped
+// `.paprikarecipe`), MealMaster text (several recipes per file, ingredients in columns) and a
+// JSON or YAML document of our own. An import is planned before anything is stored: every
+// recipe is read and compared with the user's recipes and the rest of the file, and the report
+// says which would be stored, which are duplicates (same source URL, or same name and
+// ingredients) and which could not be read. Only a committed import stores them, together.
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::{Cursor, Read, Write};
+use uuid::Uuid;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::models::{DifficultyLevel, RecipeImportEntry, RecipeImportStatus};
+use crate::recipe_import::{self, ImportedRecipe, MAX_CUISINE_CHARS, MAX_NAME_CHARS};
+use crate::recipes::{self, IngredientInput, NewRecipe, StepInput};
+
+/// Largest collection file accepted; Paprika archives embed their photos.
+pub const MAX_UPLOAD_BYTES: usize = 64 * 1024 * 1024;
+const MAX_RECIPES: usize = 10_000;
+/// A Paprika recipe larger than this once decompressed is not read.
+const MAX_ENTRY_BYTES: u64 = 16 * 1024 * 1024;
+/// A Paprika archive that decompresses to more than this in all (zip entries and the gzipped
+/// recipes in them) is not read.
+const MAX_DECOMPRESSED_BYTES: u64 = 4 * MAX_UPLOAD_BYTES as u64;
+
+/// Version of our own collection document.
+const COLLECTION_VERSION: u32 = 1;
+
+/// Fields of our own document's recipes.
+const COLLECTION_FIELDS: &[&str] = &[
+    "name",
+    "description",
+    "cuisine_type",
+    "difficulty_level",
+    "prep_time_minutes",
+    "cook_time_minutes",
+    "servings",
+    "tags",
+    "source_url",
+    "image_url",
+    "ingredients",
+    "instructions",
+];
+
+/// MealMaster unit abbreviations and the unit they are stored as; the first listed for a unit
+/// is the one written on export.
+const MEALMASTER_UNITS: &[(&str, &str)] = &[
+    ("x", ""),
+    ("ea", ""),
+    ("ts", "tsp"),
+    ("t", "tsp"),
+    ("tb", "tbsp"),
+    ("T", "tbsp"),
+    ("c", "cup"),
+    ("fl", "fl oz"),
+    ("pt", "pint"),
+    ("qt", "quart"),
+    ("ga", "gallon"),
+    ("oz", "oz"),
+    ("lb", "lb"),
+    ("ml", "ml"),
+    ("cb", "ml"),
+    ("cl", "cl"),
+    ("dl", "dl"),
+    ("l", "l"),
+    ("mg", "mg"),
+    ("g", "g"),
+    ("kg", "kg"),
+    ("sm", "small"),
+    ("md", "medium"),
+    ("lg", "large"),
+    ("cn", "can"),
+    ("pk", "package"),
+    ("pn", "pinch"),
+    ("dr", "drop"),
+    ("ds", "dash"),
+    ("ct", "carton"),
+    ("bn", "bunch"),
+    ("sl", "slice"),
+];
+
+const MEALMASTER_HEADER: &str = "MMMMM----- Recipe via Meal-Master (tm) v8.05";
+/// Ingredient text wraps onto "-" continuation lines after this many characters.
+const MEALMASTER_INGREDIENT_WIDTH: usize = 28;
+const MEALMASTER_LINE_WIDTH: usize = 76;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollectionFormat {
+    Paprika,
+    MealMaster,
+    Json,
+    Yaml,
+}
+
+impl CollectionFormat {
+    /// Guesses the format from the start of the file.
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(b"PK") || data.starts_with(&[0x1f, 0x8b]) {
+            return CollectionFormat::Paprika;
+        }
+        let start = String::from_utf8_lossy(&data[..data.len().min(4096)]).into_owned();
+        let start = start.trim_start_matches('\u{feff}').trim_start();
+        if start.starts_with('{') || start.starts_with('[') {
+            CollectionFormat::Json
+        } else if start.lines().any(is_mealmaster_header) {
+            CollectionFormat::MealMaster
+        } else {
+            CollectionFormat::Yaml
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            CollectionFormat::Paprika => "application/zip",
+            CollectionFormat::MealMaster => "text/plain; charset=utf-8",
+            CollectionFormat::Json => "application/json",
+            CollectionFormat::Yaml => "application/yaml",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CollectionFormat::Paprika => "paprikarecipes",
+            CollectionFormat::MealMaster => "mmf",
+            CollectionFormat::Json => "json",
+            CollectionFormat::Yaml => "yaml",
+        }
+    }
+}
+
+/// One recipe of a collection, or why it could not be read.
+#[derive(Debug)]
+pub struct CollectionEntry {
+    /// Where the recipe is in the file: its archive entry or its position
+    pub source: String,
+    pub recipe: Result<ImportedRecipe, String>,
+}
+
+/// Our own collection document; JSON and YAML carry the same fields.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecipeCollection {
+    pub version: u32,
+    pub recipes: Vec<CollectionRecipe>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollectionRecipe {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cuisine_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty_level: Option<DifficultyLevel>,
+    #[serde(default)]
+    pub prep_time_minutes: i32,
+    #[serde(default)]
+    pub cook_time_minutes: i32,
+    #[serde(default = "default_servings")]
+    pub servings: i32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+    /// Ingredient lines, or objects for those a line cannot express
+    pub ingredients: Vec<IngredientInput>,
+    #[serde(default)]
+    pub instructions: Vec<StepInput>,
+}
+
+fn default_servings() -> i32 {
+    1
+}
+
+/// A recipe as Paprika stores it; text fields may be null.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct PaprikaRecipe {
+    uid: Option<String>,
+    name: Option<String>,
+    ingredients: Option<String>,
+    directions: Option<String>,
+    description: Option<String>,
+    notes: Option<String>,
+    nutritional_info: Option<String>,
+    servings: Option<String>,
+    prep_time: Option<String>,
+    cook_time: Option<String>,
+    total_time: Option<String>,
+    difficulty: Option<String>,
+    rating: Option<i32>,
+    categories: Option<Vec<String>>,
+    source: Option<String>,
+    source_url: Option<String>,
+    image_url: Option<String>,
+    photo: Option<String>,
+    photo_data: Option<String>,
+    photo_hash: Option<String>,
+    created: Option<String>,
+    hash: Option<String>,
+}
+
+/// Reads every recipe in the file. A recipe that cannot be read is an entry with its error;
+/// only a file that cannot be read at all is an error.
+pub fn read_collection(format: CollectionFormat, data: &[u8]) -> Result<Vec<CollectionEntry>> {
+    let entries = match format {
+        CollectionFormat::Paprika => read_paprika(data)?,
+        CollectionFormat::MealMaster => read_mealmaster(&String::from_utf8_lossy(data)),
+        CollectionFormat::Json => read_document(serde_json::from_slice(data)?)?,
+        CollectionFormat::Yaml => read_document(serde_yaml::from_slice(data)?)?,
+    };
+    if entries.len() > MAX_RECIPES {
+        bail!("collection has more than {} recipes", MAX_RECIPES);
+    }
+    Ok(entries)
+}
+
+/// Writes the recipes in the format; ids are kept where the format has a place for them.
+pub fn write_collection(format: CollectionFormat, recipes: &[(Uuid, NewRecipe)]) -> Result<Vec<u8>> {
+    match format {
+        CollectionFormat::Paprika => write_paprika(recipes),
+        CollectionFormat::MealMaster => Ok(write_mealmaster(recipes).into_bytes()),
+        CollectionFormat::Json => Ok(serde_json::to_vec_pretty(&to_document(recipes))?),
+        CollectionFormat::Yaml => Ok(serde_yaml::to_string(&to_document(recipes))?.into_bytes()),
+    }
+}
+
+/// Recipes the user has, or ones earlier in the file, that a recipe is the same as.
+struct Fingerprint {
+    name: String,
+    ingredients: Vec<String>,
+    source_url: Option<String>,
+}
+
+impl Fingerprint {
+    fn of(recipe: &NewRecipe) -> Self {
+        let mut ingredients: Vec<String> = recipe.ingredients.iter().map(|i| normalize(&i.name)).collect();
+        ingredients.sort();
+        Fingerprint {
+            name: normalize(&recipe.name),
+            ingredients,
+            source_url: recipe.source_url.as_deref().map(normalize_url).filter(|url| !url.is_empty()),
+        }
+    }
+
+    fn same_recipe(&self, other: &Fingerprint) -> bool {
+        let same_source = self.source_url.is_some() && self.source_url == other.source_url;
+        same_source || (self.name == other.name && self.ingredients == other.ingredients)
+    }
+}
+
+/// Lowercase words without punctuation: "Grandma's  Chili!" -> "grandma s chili".
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn normalize_url(url: &str) -> String {
+    let url = url.trim().to_lowercase();
+    let url = url.trim_start_matches("https://").trim_start_matches("http://").trim_start_matches("www.");
+    url.trim_end_matches('/').to_string()
+}
+
+/// The report of what an import would do, and the recipes to store with their entry's index.
+/// Duplicates are left out unless `import_duplicates` is set.
+pub fn plan_import(
+    entries: Vec<CollectionEntry>,
+    existing: &[(Uuid, NewRecipe)],
+    import_duplicates: bool,
+) -> (Vec<RecipeImportEntry>, Vec<(usize, NewRecipe)>) {
+    let existing: Vec<(Uuid, Fingerprint)> = existing.iter().map(|(id, recipe)| (*id, Fingerprint::of(recipe))).collect();
+    let mut planned: Vec<(usize, Fingerprint)> = Vec::new();
+    let mut report = Vec::new();
+    let mut to_import = Vec::new();
+
+    for (index, entry) in entries.into_iter().enumerate() {
+        let imported = match entry.recipe {
+            Ok(imported) => imported,
+            Err(error) => {
+                report.push(RecipeImportEntry {
+                    source: entry.source,
+                    name: None,
+                    status: RecipeImportStatus::Invalid,
+                    id: None,
+                    duplicate_of: None,
+                    ingredients: 0,
+                    steps: 0,
+                    unmapped_fields: Vec::new(),
+                    warnings: Vec::new(),
+                    error: Some(error),
+                });
+                continue;
+            }
+        };
+
+        let fingerprint = Fingerprint::of(&imported.recipe);
+        let mut warnings = imported.warnings;
+        let duplicate_of = existing.iter().find(|(_, other)| fingerprint.same_recipe(other)).map(|(id, _)| *id);
+        let duplicate_in_file = planned.iter().find(|(_, other)| fingerprint.same_recipe(other)).map(|(i, _)| *i);
+        if let Some(earlier) = duplicate_in_file {
+            warnings.push(format!("same recipe as {} in this file", report[earlier].source));
+        }
+        if duplicate_of.is_none()
+            && duplicate_in_file.is_none()
+            && existing.iter().any(|(_, other)| other.name == fingerprint.name)
+        {
+            warnings.push("a different recipe with this name already exists".to_string());
+        }
+        let duplicate = duplicate_of.is_some() || duplicate_in_file.is_some();
+
+        report.push(RecipeImportEntry {
+            source: entry.source,
+            name: Some(imported.recipe.name.clone()),
+            status: if duplicate { RecipeImportStatus::Duplicate } else { RecipeImportStatus::New },
+            id: None,
+            duplicate_of,
+            ingredients: imported.recipe.ingredients.len(),
+            steps: imported.recipe.steps.len(),
+            unmapped_fields: imported.unmapped_fields,
+            warnings,
+            error: None,
+        });
+        if !duplicate || import_duplicates {
+            planned.push((index, fingerprint));
+            to_import.push((index, imported.recipe));
+        }
+    }
+    (report, to_import)
+}
+
+/// Fits the recipe to the table columns and turns what cannot be stored into an error.
+fn checked(mut recipe: NewRecipe, unmapped_fields: Vec<String>, mut warnings: Vec<String>) -> Result<ImportedRecipe, String> {
+    recipe.name = recipe.name.trim().to_string();
+    if recipe.name.is_empty() {
+        return Err("recipe has no name".to_string());
+    }
+    recipe.name = recipe_import::truncate(&recipe.name, MAX_NAME_CHARS);
+    recipe.cuisine_type = recipe
+        .cuisine_type
+        .map(|cuisine| recipe_import::truncate(cuisine.trim(), MAX_CUISINE_CHARS))
+        .filter(|cuisine| !cuisine.is_empty());
+    if recipe.servings < 1 {
+        warnings.push(format!("servings {} is not a number of servings; stored as 1", recipe.servings));
+        recipe.servings = 1;
+    }
+    recipe.prep_time_minutes = recipe.prep_time_minutes.max(0);
+    recipe.cook_time_minutes = recipe.cook_time_minutes.max(0);
+    for ingredient in &mut recipe.ingredients {
+        ingredient.name = recipe_import::truncate(&ingredient.name, recipes::MAX_INGREDIENT_NAME_CHARS);
+        ingredient.unit = recipe_import::truncate(&ingredient.unit, recipes::MAX_UNIT_CHARS);
+    }
+    if recipe.ingredients.is_empty() {
+        warnings.push("recipe has no ingredients".to_string());
+    }
+    Ok(ImportedRecipe {
+        recipe,
+        unmapped_fields,
+        warnings,
+    })
+}
+
+/// Ingredient lines, leaving out section headings ("For the sauce:") with a warning.
+fn ingredient_lines<'a>(lines: impl Iterator<Item = &'a str>, warnings: &mut Vec<String>) -> Vec<String> {
+    let mut ingredients = Vec::new();
+    for line in lines.map(str::trim).filter(|line| !line.is_empty()) {
+        if line.ends_with(':') && !line.chars().any(|c| c.is_ascii_digit()) {
+            warnings.push(format!("ingredient heading \"{}\" not kept", line.trim_end_matches(':')));
+        } else {
+            ingredients.push(line.to_string());
+        }
+    }
+    ingredients
+}
+
+/// "1. Preheat the oven" -> "Preheat the oven"
+fn strip_step_number(step: &str) -> &str {
+    let digits = step.find(|c: char| !c.is_ascii_digit()).unwrap_or(0);
+    match step[digits..].strip_prefix(['.', ')']) {
+        Some(rest) if digits > 0 => rest.trim_start(),
+        _ => step,
+    }
+}
+
+/// Minutes in "1 hr 30 mins", "45 minutes", "1 1/2 hours", "1:30", "PT1H30M" or a bare number
+/// of minutes.
+fn parse_minutes(text: &str) -> Option<i32> {
+    let text = text.trim().to_lowercase();
+    if text.starts_with('p') {
+        return recipe_import::parse_duration(&text);
+    }
+    if let Some((hours, minutes)) = text.split_once(':') {
+        if let (Ok(hours), Ok(minutes)) = (hours.trim().parse::<i32>(), minutes.trim().parse::<i32>()) {
+            return Some(hours * 60 + minutes);
+        }
+    }
+
+    let mut minutes = 0.0_f32;
+    let mut pending = 0.0_f32;
+    let mut read_any = false;
+    // The upper end of a range ("2-3 hours") is not added
+    let mut skip_number = false;
+    let mut rest = text.as_str();
+    while !rest.is_empty() {
+        let is_number = |c: char| c.is_ascii_digit() || c == '.' || c == '/';
+        let first = rest.chars().next()?;
+        let end = if is_number(first) {
+            rest.find(|c: char| !is_number(c)).unwrap_or(rest.len())
+        } else if first.is_alphabetic() {
+            rest.find(|c: char| !c.is_alphabetic()).unwrap_or(rest.len())
+        } else {
+            first.len_utf8()
+        };
+        let (token, remainder) = rest.split_at(end);
+        rest = remainder;
+
+        if is_number(first) && std::mem::take(&mut skip_number) {
+            continue;
+        } else if is_number(first) {
+            let value = match token.split_once('/') {
+                Some((numerator, denominator)) => {
+                    numerator.parse::<f32>().ok()? / denominator.parse::<f32>().ok().filter(|d| *d > 0.0)?
+                }
+                None => token.parse().ok()?,
+            };
+            pending += value;
+            read_any = true;
+        } else if first.is_alphabetic() && pending > 0.0 {
+            let factor = match first {
+                'd' => Some(24.0 * 60.0),
+                'h' => Some(60.0),
+                'm' => Some(1.0),
+                's' => Some(1.0 / 60.0),
+                _ => None,
+            };
+            if let Some(factor) = factor {
+                minutes += pending * factor;
+                pending = 0.0;
+            } else if token == "to" {
+                skip_number = true;
+            }
+        } else if first == '-' && pending > 0.0 {
+            skip_number = true;
+        }
+    }
+    read_any.then(|| (minutes + pending).round() as i32)
+}
+
+/// "1 hr 30 mins", or nothing for no time.
+fn format_minutes(minutes: i32) -> Option<String> {
+    match (minutes / 60, minutes % 60) {
+        (0, 0) => None,
+        (0, minutes) => Some(format!("{} mins", minutes)),
+        (hours, 0) => Some(format!("{} hr", hours)),
+        (hours, minutes) => Some(format!("{} hr {} mins", hours, minutes)),
+    }
+}
+
+fn read_paprika(data: &[u8]) -> Result<Vec<CollectionEntry>> {
+    // A single exported recipe is gzipped JSON rather than an archive
+    if data.starts_with(&[0x1f, 0x8b]) {
+        return Ok(vec![CollectionEntry {
+            source: "recipe 1".to_string(),
+            recipe: read_paprika_recipe(data, &mut 0),
+        }]);
+    }
+
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let mut entries = Vec::new();
+    let mut decompressed = 0;
+    for index in 0..archive.len() {
+        let file = archive.by_index(index)?;
+        let name = file.name().to_string();
+        if file.is_dir() || !name.to_lowercase().ends_with(".paprikarecipe") || name.starts_with("__MACOSX/") {
+            continue;
+        }
+        let mut compressed = Vec::new();
+        file.take(MAX_ENTRY_BYTES + 1).read_to_end(&mut compressed)?;
+        decompressed += compressed.len() as u64;
+        let recipe = if compressed.len() as u64 > MAX_ENTRY_BYTES {
+            Err(format!("recipe is larger than {} bytes", MAX_ENTRY_BYTES))
+        } else {
+            read_paprika_recipe(&compressed, &mut decompressed)
+        };
+        if decompressed > MAX_DECOMPRESSED_BYTES {
+            bail!("archive is larger than {} bytes decompressed", MAX_DECOMPRESSED_BYTES);
+        }
+        entries.push(CollectionEntry { source: name, recipe });
+        if entries.len() > MAX_RECIPES {
+            break;
+        }
+    }
+    Ok(entries)
+}
+
+/// Reads a recipe, gunzipping it if need be; what that decompresses is added to `decompressed`,
+/// also when the recipe turns out to be unreadable.
+fn read_paprika_recipe(data: &[u8], decompressed: &mut u64) -> Result<ImportedRecipe, String> {
+    let mut json = Vec::new();
+    if data.starts_with(&[0x1f, 0x8b]) {
+        let read = GzDecoder::new(data).take(MAX_ENTRY_BYTES + 1).read_to_end(&mut json);
+        *decompressed += json.len() as u64;
+        read.map_err(|e| format!("could not decompress recipe: {}", e))?;
+        if json.len() as u64 > MAX_ENTRY_BYTES {
+            return Err(format!("recipe is larger than {} bytes", MAX_ENTRY_BYTES));
+        }
+    } else {
+        json.extend_from_slice(data);
+    }
+    let paprika: PaprikaRecipe = serde_json::from_slice(&json).map_err(|e| format!("not a Paprika recipe: {}", e))?;
+
+    let present = |value: &Option<String>| value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+    let mut warnings = Vec::new();
+    let mut unmapped_fields = Vec::new();
+
+    let ingredients = ingredient_lines(paprika.ingredients.as_deref().unwrap_or("").lines(), &mut warnings);
+    let steps: Vec<StepInput> = paprika
+        .directions
+        .as_deref()
+        .unwrap_or("")
+        .lines()
+        .map(|line| strip_step_number(line.trim()).to_string())
+        .filter(|line| !line.is_empty())
+        .map(StepInput::Text)
+        .collect();
+
+    let mut minutes = |field: &str, value: &Option<String>| {
+        let text = present(value)?;
+        let minutes = parse_minutes(&text);
+        if minutes.is_none() {
+            warnings.push(format!("{}: could not read time \"{}\"", field, text));
+        }
+        minutes
+    };
+    let prep = minutes("prep_time", &paprika.prep_time);
+    let cook = minutes("cook_time", &paprika.cook_time);
+    let total = minutes("total_time", &paprika.total_time);
+    let (prep_time_minutes, cook_time_minutes) = recipe_import::split_times(prep, cook, total);
+
+    let servings = match present(&paprika.servings) {
+        Some(text) => recipe_import::leading_number(&text).unwrap_or_else(|| {
+            warnings.push(format!("servings: could not read servings from \"{}\"", text));
+            1
+        }),
+        None => 1,
+    };
+
+    let difficulty_level = present(&paprika.difficulty).and_then(|text| {
+        let level = match text.to_lowercase().as_str() {
+            "easy" | "beginner" | "simple" => Some(DifficultyLevel::Beginner),
+            "medium" | "moderate" | "intermediate" => Some(DifficultyLevel::Intermediate),
+            "hard" | "difficult" | "advanced" => Some(DifficultyLevel::Advanced),
+            "expert" | "very hard" => Some(DifficultyLevel::Expert),
+            _ => None,
+        };
+        if level.is_none() {
+            warnings.push(format!("difficulty: \"{}\" is not a difficulty level", text));
+        }
+        level
+    });
+
+    for (field, value) in [
+        ("notes", &paprika.notes),
+        ("nutritional_info", &paprika.nutritional_info),
+        ("source", &paprika.source),
+        ("photo_data", &paprika.photo_data),
+    ] {
+        if present(value).is_some() {
+            unmapped_fields.push(field.to_string());
+        }
+    }
+    if paprika.rating.is_some_and(|rating| rating > 0) {
+        unmapped_fields.push("rating".to_string());
+    }
+
+    let recipe = NewRecipe {
+        name: present(&paprika.name).unwrap_or_default(),
+        description: present(&paprika.description),
+        cuisine_type: None,
+        difficulty_level,
+        prep_time_minutes,
+        cook_time_minutes,
+        servings,
+        tags: paprika.categories.iter().flatten().map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect(),
+        source_url: present(&paprika.source_url),
+        image_url: present(&paprika.image_url),
+        ingredients: ingredients.iter().map(|line| recipes::parse_ingredient_line(line)).collect(),
+        steps: recipes::into_steps(steps),
+    };
+    checked(recipe, unmapped_fields, warnings)
+}
+
+fn write_paprika(recipes: &[(Uuid, NewRecipe)]) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let created = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut file_names = HashSet::new();
+
+    for (id, recipe) in recipes {
+        let mut paprika = PaprikaRecipe {
+            uid: Some(id.to_string().to_uppercase()),
+            name: Some(recipe.name.clone()),
+            ingredients: Some(recipe.ingredients.iter().map(recipes::format_ingredient).collect::<Vec<_>>().join("\n")),
+            directions: Some(recipe.steps.iter().map(|s| s.instruction.as_str()).collect::<Vec<_>>().join("\n")),
+            description: recipe.description.clone(),
+            servings: Some(recipe.servings.to_string()),
+            prep_time: format_minutes(recipe.prep_time_minutes),
+            cook_time: format_minutes(recipe.cook_time_minutes),
+            total_time: format_minutes(recipe.prep_time_minutes + recipe.cook_time_minutes),
+            difficulty: recipe.difficulty_level.as_ref().map(|level| {
+                match level {
+                    DifficultyLevel::Beginner => "Easy",
+                    DifficultyLevel::Intermediate => "Medium",
+                    DifficultyLevel::Advanced => "Hard",
+                    DifficultyLevel::Expert => "Expert",
+                }
+                .to_string()
+            }),
+            rating: Some(0),
+            categories: Some(recipe.tags.clone()),
+            source_url: recipe.source_url.clone(),
+            image_url: recipe.image_url.clone(),
+            created: Some(created.clone()),
+            ..PaprikaRecipe::default()
+        };
+        paprika.hash = Some(hex::encode(Sha256::digest(serde_json::to_vec(&paprika)?)));
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&serde_json::to_vec(&paprika)?)?;
+
+        // Names are file names in the archive, so unsafe characters go and repeats are numbered
+        let stem: String = recipe
+            .name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || " -_'&().,".contains(c) { c } else { '-' })
+            .take(100)
+            .collect();
+        let mut file_name = format!("{}.paprikarecipe", stem.trim());
+        let mut copy = 1;
+        while !file_names.insert(file_name.to_lowercase()) {
+            copy += 1;
+            file_name = format!("{} ({}).paprikarecipe", stem.trim(), copy);
+        }
+        zip.start_file(file_name.as_str(), options)?;
+        zip.write_all(&gzip.finish()?)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+fn is_mealmaster_header(line: &str) -> bool {
+    let line = line.trim();
+    (line.starts_with("MMMMM") || line.starts_with("-----")) && line.to_lowercase().contains("meal-master")
+}
+
+fn is_mealmaster_end(line: &str) -> bool {
+    matches!(line.trim(), "MMMMM" | "-----")
+}
+
+/// The heading of an ingredient section: "MMMMM--------SAUCE--------" -> "SAUCE".
+fn mealmaster_heading(line: &str) -> Option<String> {
+    let line = line.trim();
+    if !(line.starts_with("MMMMM") || line.starts_with("-----")) {
+        return None;
+    }
+    let heading = line.strip_prefix("MMMMM").unwrap_or(line).trim_matches('-').trim();
+    Some(heading.to_string())
+}
+
+/// Amount, unit abbreviation and text of a column-formatted ingredient: the amount in columns
+/// 1-7, the unit in 9-10 and the text from 12.
+fn mealmaster_columns(chars: &[char]) -> Option<(String, String, String)> {
+    if chars.len() < 12 || chars[7] != ' ' || chars[10] != ' ' {
+        return None;
+    }
+    let amount: String = chars[..7].iter().collect::<String>().trim().to_string();
+    let unit: String = chars[8..10].iter().collect::<String>().trim().to_string();
+    if !amount.chars().all(|c| c.is_ascii_digit() || " /.-".contains(c)) {
+        return None;
+    }
+    if !unit.is_empty() && !MEALMASTER_UNITS.iter().any(|(abbreviation, _)| *abbreviation == unit) {
+        return None;
+    }
+    let text = chars[11..].iter().collect::<String>().trim().to_string();
+    (!text.is_empty()).then_some((amount, unit, text))
+}
+
+/// The ingredients on a line, which holds two in the two-column layout.
+fn mealmaster_ingredients(line: &str) -> Option<Vec<(String, String, String)>> {
+    let chars: Vec<char> = line.trim_end().chars().collect();
+    if chars.len() > 41 && chars[39] == ' ' && chars[40] == ' ' {
+        if let (Some(left), Some(right)) = (mealmaster_columns(&chars[..39]), mealmaster_columns(&chars[41..])) {
+            return Some(vec![left, right]);
+        }
+    }
+    mealmaster_columns(&chars).map(|ingredient| vec![ingredient])
+}
+
+fn read_mealmaster(text: &str) -> Vec<CollectionEntry> {
+    let mut entries = Vec::new();
+    let mut lines = text.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if !is_mealmaster_header(line) {
+            continue;
+        }
+        // A recipe runs to its end line, or to the next header when that is missing
+        let mut body = Vec::new();
+        while let Some(line) = lines.next_if(|line| !is_mealmaster_header(line)) {
+            if is_mealmaster_end(line) {
+                break;
+            }
+            body.push(line);
+        }
+        entries.push(CollectionEntry {
+            source: format!("recipe {}", entries.len() + 1),
+            recipe: read_mealmaster_recipe(&body),
+        });
+        if entries.len() > MAX_RECIPES {
+            break;
+        }
+    }
+    entries
+}
+
+fn read_mealmaster_recipe(lines: &[&str]) -> Result<ImportedRecipe, String> {
+    let mut name = String::new();
+    let mut tags = Vec::new();
+    let mut servings = None;
+    let mut warnings = Vec::new();
+    let mut position = 0;
+
+    // Title, Categories and Servings or Yield, up to the first ingredient
+    while let Some(line) = lines.get(position).map(|line| line.trim()) {
+        let field = |label: &str| {
+            line.get(..label.len())
+                .filter(|start| start.eq_ignore_ascii_case(label))
+                .map(|_| line[label.len()..].trim())
+        };
+        if let Some(title) = field("Title:") {
+            name = title.to_string();
+        } else if let Some(categories) = field("Categories:") {
+            tags.extend(
+                categories
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty() && !c.eq_ignore_ascii_case("none"))
+                    .map(str::to_string),
+            );
+        } else if let Some(yield_text) = field("Servings:").or_else(|| field("Yield:")) {
+            servings = recipe_import::leading_number(yield_text);
+            if servings.is_none() {
+                warnings.push(format!("could not read servings from \"{}\"", yield_text));
+            }
+        } else if !line.is_empty() {
+            break;
+        }
+        position += 1;
+    }
+
+    let mut ingredients: Vec<(String, String, String)> = Vec::new();
+    while let Some(line) = lines.get(position) {
+        if line.trim().is_empty() {
+            position += 1;
+            continue;
+        }
+        if let Some(heading) = mealmaster_heading(line) {
+            if !heading.is_empty() {
+                warnings.push(format!("ingredient heading \"{}\" not kept", heading));
+            }
+            position += 1;
+            continue;
+        }
+        let Some(found) = mealmaster_ingredients(line) else { break };
+        for (amount, unit, text) in found {
+            // "-finely chopped" continues the ingredient above
+            match (text.strip_prefix('-'), ingredients.last_mut()) {
+                (Some(more), Some(last)) if amount.is_empty() && unit.is_empty() => {
+                    last.2 = format!("{} {}", last.2, more.trim());
+                }
+                _ => ingredients.push((amount, unit, text)),
+            }
+        }
+        position += 1;
+    }
+
+    // Directions are paragraphs separated by blank lines
+    let mut steps = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    for line in lines[position..].iter().map(|line| line.trim()).chain([""]) {
+        if line.is_empty() {
+            if !paragraph.is_empty() {
+                steps.push(StepInput::Text(strip_step_number(&paragraph.join(" ")).to_string()));
+                paragraph.clear();
+            }
+        } else {
+            paragraph.push(line);
+        }
+    }
+
+    let recipe = NewRecipe {
+        name,
+        description: None,
+        cuisine_type: None,
+        difficulty_level: None,
+        prep_time_minutes: 0,
+        cook_time_minutes: 0,
+        servings: servings.unwrap_or(1),
+        tags,
+        source_url: None,
+        image_url: None,
+        ingredients: ingredients
+            .iter()
+            .map(|(amount, unit, text)| {
+                let unit = MEALMASTER_UNITS
+                    .iter()
+                    .find(|(abbreviation, _)| abbreviation == unit)
+                    .map_or("", |(_, unit)| *unit);
+                // MealMaster puts preparation after a semicolon
+                let line = format!("{} {} {}", amount, unit, text.replacen(';', ",", 1));
+                recipes::parse_ingredient_line(&line)
+            })
+            .collect(),
+        steps: recipes::into_steps(steps),
+    };
+    checked(recipe, Vec::new(), warnings)
+}
+
+/// Greedy word wrap; words longer than the width get a line of their own.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// MealMaster carries the title, categories, yield, ingredients and directions.
+fn write_mealmaster(recipes: &[(Uuid, NewRecipe)]) -> String {
+    let mut out = String::new();
+    for (_, recipe) in recipes {
+        out.push_str(MEALMASTER_HEADER);
+        out.push_str("\n \n");
+        out.push_str(&format!("      Title: {}\n", recipe.name));
+        out.push_str(&format!(" Categories: {}\n", recipe.tags.join(", ")));
+        out.push_str(&format!("      Yield: {} Servings\n \n", recipe.servings));
+
+        for ingredient in &recipe.ingredients {
+            let amount = if ingredient.amount > 0.0 { recipes::format_quantity(ingredient.amount) } else { String::new() };
+            let mut text = ingredient.name.clone();
+            let abbreviation = match MEALMASTER_UNITS.iter().find(|(_, unit)| !unit.is_empty() && *unit == ingredient.unit) {
+                Some((abbreviation, _)) => *abbreviation,
+                None => {
+                    // Units MealMaster has no abbreviation for stay with the text
+                    if !ingredient.unit.is_empty() {
+                        text = format!("{} {}", ingredient.unit, text);
+                    }
+                    ""
+                }
+            };
+            if let Some(note) = &ingredient.preparation_note {
+                text = format!("{}; {}", text, note);
+            }
+            for (index, chunk) in wrap(&text, MEALMASTER_INGREDIENT_WIDTH).iter().enumerate() {
+                if index == 0 {
+                    out.push_str(&format!("{:>7} {:<2} {}\n", amount, abbreviation, chunk));
+                } else {
+                    out.push_str(&format!("{:>7} {:<2} -{}\n", "", "", chunk));
+                }
+            }
+        }
+        out.push_str(" \n");
+
+        for step in &recipe.steps {
+            for line in wrap(&step.instruction, MEALMASTER_LINE_WIDTH - 2) {
+                out.push_str(&format!("  {}\n", line));
+            }
+            out.push_str(" \n");
+        }
+        out.push_str("MMMMM\n\n");
+    }
+    out
+}
+
+fn read_document(document: Value) -> Result<Vec<CollectionEntry>> {
+    let recipes = match document {
+        Value::Array(recipes) => recipes,
+        Value::Object(mut collection) => {
+            let version = collection.get("version").and_then(Value::as_u64).unwrap_or(COLLECTION_VERSION as u64);
+            if version > COLLECTION_VERSION as u64 {
+                bail!("collection version {} is newer than this server reads", version);
+            }
+            match collection.remove("recipes") {
+                Some(Value::Array(recipes)) => recipes,
+                _ => bail!("collection has no list of recipes"),
+            }
+        }
+        _ => bail!("collection is neither a list of recipes nor a document with one"),
+    };
+
+    Ok(recipes
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let mut unmapped_fields: Vec<String> = value
+                .as_object()
+                .map(|fields| fields.keys().filter(|key| !COLLECTION_FIELDS.contains(&key.as_str())).cloned().collect())
+                .unwrap_or_default();
+            unmapped_fields.sort();
+
+            let recipe = serde_json::from_value::<CollectionRecipe>(value)
+                .map_err(|e| e.to_string())
+                .and_then(|recipe| {
+                    let recipe = NewRecipe {
+                        name: recipe.name,
+                        description: recipe.description,
+                        cuisine_type: recipe.cuisine_type,
+                        difficulty_level: recipe.difficulty_level,
+                        prep_time_minutes: recipe.prep_time_minutes,
+                        cook_time_minutes: recipe.cook_time_minutes,
+                        servings: recipe.servings,
+                        tags: recipe.tags,
+                        source_url: recipe.source_url,
+                        image_url: recipe.image_url,
+                        ingredients: recipe.ingredients.into_iter().map(IngredientInput::into_ingredient).collect(),
+                        steps: recipes::into_steps(recipe.instructions),
+                    };
+                    checked(recipe, unmapped_fields, Vec::new())
+                });
+            CollectionEntry {
+                source: format!("recipe {}", index + 1),
+                recipe,
+            }
+        })
+        .collect())
+}
+
+/// Ingredients are written as lines where the line reads back the same, and steps as their
+/// text where that is all they have, so the document stays easy to edit by hand.
+fn to_document(recipes: &[(Uuid, NewRecipe)]) -> RecipeCollection {
+    let recipes = recipes
+        .iter()
+        .map(|(_, recipe)| CollectionRecipe {
+            name: recipe.name.clone(),
+            description: recipe.description.clone(),
+            cuisine_type: recipe.cuisine_type.clone(),
+            difficulty_level: recipe.difficulty_level.clone(),
+            prep_time_minutes: recipe.prep_time_minutes,
+            cook_time_minutes: recipe.cook_time_minutes,
+            servings: recipe.servings,
+            tags: recipe.tags.clone(),
+            source_url: recipe.source_url.clone(),
+            image_url: recipe.image_url.clone(),
+            ingredients: recipe
+                .ingredients
+                .iter()
+                .map(|ingredient| {
+                    let line = recipes::format_ingredient(ingredient);
+                    let read_back = recipes::parse_ingredient_line(&line);
+                    let same = ingredient.substitutions.is_empty()
+                        && read_back.name == ingredient.name
+                        && read_back.unit == ingredient.unit
+                        && read_back.preparation_note == ingredient.preparation_note
+                        && (read_back.amount - ingredient.amount).abs() < 0.005;
+                    if same {
+                        IngredientInput::Line(line)
+                    } else {
+                        IngredientInput::Structured(ingredient.clone())
+                    }
+                })
+                .collect(),
+            instructions: recipe
+                .steps
+                .iter()
+                .map(|step| {
+                    let text_only = step.time_estimate_minutes.is_none()
+                        && step.temperature.is_none()
+                        && step.equipment_needed.is_empty()
+                        && step.tips.is_empty();
+                    if text_only {
+                        StepInput::Text(step.instruction.clone())
+                    } else {
+                        StepInput::Structured(step.clone())
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+
+    RecipeCollection {
+        version: COLLECTION_VERSION,
+        recipes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipe(name: &str, ingredients: &[&str], steps: &[&str]) -> NewRecipe {
+        NewRecipe {
+            name: name.to_string(),
+            description: None,
+            cuisine_type: None,
+            difficulty_level: None,
+            prep_time_minutes: 0,
+            cook_time_minutes: 0,
+            servings: 4,
+            tags: vec!["Bread".to_string()],
+            source_url: None,
+            image_url: None,
+            ingredients: ingredients.iter().map(|line| recipes::parse_ingredient_line(line)).collect(),
+            steps: recipes::into_steps(steps.iter().map(|s| StepInput::Text(s.to_string())).collect()),
+        }
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(data).unwrap();
+        gzip.finish().unwrap()
+    }
+
+    fn read_back(entries: Vec<CollectionEntry>) -> Vec<NewRecipe> {
+        entries.into_iter().map(|entry| entry.recipe.unwrap().recipe).collect()
+    }
+
+    fn assert_same_recipe(read: &NewRecipe, written: &NewRecipe) {
+        assert_eq!(read.name, written.name);
+        assert_eq!(read.servings, written.servings);
+        assert_eq!(read.tags, written.tags);
+        let ingredients = |recipe: &NewRecipe| {
+            recipe
+                .ingredients
+                .iter()
+                .map(|i| (i.amount, i.unit.clone(), i.name.clone(), i.preparation_note.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ingredients(read), ingredients(written));
+        let steps = |recipe: &NewRecipe| recipe.steps.iter().map(|s| s.instruction.clone()).collect::<Vec<_>>();
+        assert_eq!(steps(read), steps(written));
+    }
+
+    fn garlic_bread() -> NewRecipe {
+        recipe(
+            "Garlic Bread",
+            &[
+                "1 ½ cups (200 g) all-purpose flour, sifted",
+                "2 cloves garlic",
+                "1/4 cup butter, softened at room temperature for about an hour",
+                "salt, to taste",
+            ],
+            &["Mix the flour and butter.", "Bake until golden, about 20 minutes."],
+        )
+    }
+
+    #[test]
+    fn mealmaster_round_trip() {
+        let written = vec![
+            (Uuid::nil(), garlic_bread()),
+            (Uuid::nil(), recipe("Toast", &["1 slice bread"], &["Toast it."])),
+        ];
+        let text = write_mealmaster(&written);
+
+        // Amount right-aligned in columns 1-7, unit in 9-10, text from 12
+        assert!(text.contains("\n  1 1/2 c  all-purpose flour; 200 g;\n"), "{}", text);
+        assert!(text.contains("\n           -sifted\n"), "{}", text);
+        assert!(text.contains("\n      2    clove garlic\n"), "{}", text);
+        assert!(text.contains("\n    1/4 c  butter; softened at room\n           -temperature for about an\n           -hour\n"));
+
+        let read = read_back(read_mealmaster(&text));
+        assert_eq!(read.len(), 2);
+        for (read, (_, written)) in read.iter().zip(&written) {
+            assert_same_recipe(read, written);
+        }
+    }
+
+    #[test]
+    fn reads_two_column_mealmaster_ingredients() {
+        let text = format!(
+            "{}\n\n      Title: Pancakes\n\n{:<41}{}\n\n  Whisk.\nMMMMM\n",
+            MEALMASTER_HEADER, "      2 c  flour", "      1 tb sugar"
+        );
+        let read = read_back(read_mealmaster(&text));
+        let ingredients: Vec<_> = read[0].ingredients.iter().map(|i| (i.amount, i.unit.as_str(), i.name.as_str())).collect();
+        assert_eq!(ingredients, vec![(2.0, "cup", "flour"), (1.0, "tbsp", "sugar")]);
+    }
+
+    #[test]
+    fn paprika_round_trip() {
+        let mut written = garlic_bread();
+        written.prep_time_minutes = 15;
+        written.cook_time_minutes = 90;
+        written.difficulty_level = Some(DifficultyLevel::Beginner);
+        written.source_url = Some("https://example.com/garlic-bread".to_string());
+        let written = vec![(Uuid::new_v4(), written.clone()), (Uuid::new_v4(), written)];
+
+        let archive = write_paprika(&written).unwrap();
+        let entries = read_paprika(&archive).unwrap();
+        assert_eq!(
+            entries.iter().map(|e| e.source.as_str()).collect::<Vec<_>>(),
+            vec!["Garlic Bread.paprikarecipe", "Garlic Bread (2).paprikarecipe"]
+        );
+        for (read, (_, written)) in read_back(entries).iter().zip(&written) {
+            assert_same_recipe(read, written);
+            assert_eq!((read.prep_time_minutes, read.cook_time_minutes), (15, 90));
+            assert!(matches!(read.difficulty_level, Some(DifficultyLevel::Beginner)));
+            assert_eq!(read.source_url, written.source_url);
+        }
+    }
+
+    #[test]
+    fn parses_paprika_times() {
+        assert_eq!(parse_minutes("1 hr 30 mins"), Some(90));
+        assert_eq!(parse_minutes("45 minutes"), Some(45));
+        assert_eq!(parse_minutes("1 1/2 hours"), Some(90));
+        assert_eq!(parse_minutes("1:30"), Some(90));
+        assert_eq!(parse_minutes("PT1H30M"), Some(90));
+        assert_eq!(parse_minutes("20"), Some(20));
+        assert_eq!(parse_minutes("2-3 hours"), Some(120));
+        assert_eq!(parse_minutes("10 to 15 mins"), Some(10));
+        assert_eq!(parse_minutes("overnight"), None);
+        assert_eq!(format_minutes(90).as_deref(), Some("1 hr 30 mins"));
+        assert_eq!(format_minutes(0), None);
+    }
+
+    #[test]
+    fn plans_duplicates_against_existing_recipes_and_the_file() {
+        let entry = |source: &str, recipe: NewRecipe| CollectionEntry {
+            source: source.to_string(),
+            recipe: Ok(ImportedRecipe { recipe, unmapped_fields: Vec::new(), warnings: Vec::new() }),
+        };
+        let mut from_site = recipe("Pancakes", &["2 cups flour"], &[]);
+        from_site.source_url = Some("https://www.example.com/pancakes/".to_string());
+        let existing_id = Uuid::new_v4();
+        let existing = vec![(existing_id, from_site.clone())];
+
+        let mut same_source = recipe("Fluffy pancakes", &["3 eggs"], &[]);
+        same_source.source_url = Some("http://example.com/pancakes".to_string());
+        let entries = vec![
+            entry("recipe 1", same_source),
+            entry("recipe 2", recipe("Grandma's Chili!", &["1 lb beef", "1 can beans"], &[])),
+            entry("recipe 3", recipe("grandma s  chili", &["1 can beans", "2 lb beef"], &[])),
+            entry("recipe 4", recipe("Pancakes", &["1 cup oats"], &[])),
+            CollectionEntry { source: "recipe 5".to_string(), recipe: Err("recipe has no name".to_string()) },
+        ];
+
+        let (report, to_import) = plan_import(entries, &existing, false);
+        let statuses: Vec<_> = report.iter().map(|e| e.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                RecipeImportStatus::Duplicate,
+                RecipeImportStatus::New,
+                RecipeImportStatus::Duplicate,
+                RecipeImportStatus::New,
+                RecipeImportStatus::Invalid,
+            ]
+        );
+        assert_eq!(report[0].duplicate_of, Some(existing_id));
+        assert_eq!(report[2].duplicate_of, None);
+        assert_eq!(report[2].warnings, vec!["same recipe as recipe 2 in this file"]);
+        assert_eq!(report[3].warnings, vec!["a different recipe with this name already exists"]);
+        assert_eq!(to_import.iter().map(|(index, _)| *index).collect::<Vec<_>>(), vec![1, 3]);
+    }
+
+    #[test]
+    fn imports_duplicates_when_asked() {
+        let entries = (1..=2)
+            .map(|n| CollectionEntry {
+                source: format!("recipe {}", n),
+                recipe: Ok(ImportedRecipe {
+                    recipe: recipe("Toast", &["1 slice bread"], &[]),
+                    unmapped_fields: Vec::new(),
+                    warnings: Vec::new(),
+                }),
+            })
+            .collect();
+        let (report, to_import) = plan_import(entries, &[], true);
+        assert_eq!(report[1].status, RecipeImportStatus::Duplicate);
+        assert_eq!(to_import.len(), 2);
+    }
+
+    #[test]
+    fn rejects_archives_that_decompress_past_the_total_limit() {
+        let bomb = gzip(&vec![b' '; MAX_ENTRY_BYTES as usize]);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let count = MAX_DECOMPRESSED_BYTES / MAX_ENTRY_BYTES + 1;
+        for index in 0..count {
+            zip.start_file(format!("{}.paprikarecipe", index), FileOptions::default()).unwrap();
+            zip.write_all(&bomb).unwrap();
+        }
+        let archive = zip.finish().unwrap().into_inner();
+        assert!(archive.len() < MAX_UPLOAD_BYTES);
+
+        let error = read_paprika(&archive).unwrap_err();
+        assert!(error.to_string().contains("decompressed"), "{}", error);
+    }
+}
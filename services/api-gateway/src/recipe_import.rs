@@ -0,0 +1,546 @@
+// Importing recipes from schema.org `Recipe` JSON-LD, as embedded in most recipe web pages
+//
+// The document is either an HTML page, whose `<script type="application/ld+json">` blocks are
+// searched, or the JSON-LD itself. The Recipe node may be top-level, in an array or in an
+// `@graph`. Ingredient lines are read like typed ones; instructions may be text, HowToStep or
+// HowToSection (flattened in order); ISO 8601 durations ("PT1H30M") give the prep and cook
+// times, with a missing one taken from `totalTime`. Properties with no column to go to, and
+// values that could not be read, are reported rather than silently dropped.
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::models::ImportRecipeRequest;
+use crate::page_fetcher::PageFetcher;
+use crate::recipes::{self, NewRecipe, StepInput};
+
+/// Recipe properties that are read into the recipe.
+const MAPPED_PROPERTIES: &[&str] = &[
+    "@context",
+    "@type",
+    "@id",
+    "name",
+    "description",
+    "recipeIngredient",
+    "ingredients",
+    "recipeInstructions",
+    "prepTime",
+    "cookTime",
+    "totalTime",
+    "recipeYield",
+    "yield",
+    "recipeCuisine",
+    "recipeCategory",
+    "keywords",
+    "image",
+    "url",
+    "mainEntityOfPage",
+];
+
+pub const MAX_NAME_CHARS: usize = 255;
+pub const MAX_CUISINE_CHARS: usize = 50;
+
+/// A recipe read from JSON-LD, with what could not be mapped.
+#[derive(Debug)]
+pub struct ImportedRecipe {
+    pub recipe: NewRecipe,
+    /// Recipe properties that have no place in the recipe tables, e.g. "nutrition"
+    pub unmapped_fields: Vec<String>,
+    /// Values that were present but could not be read, e.g. an unparseable duration
+    pub warnings: Vec<String>,
+}
+
+/// The page at the posted URL could not be fetched; the other site's fault rather than the caller's.
+#[derive(Debug)]
+pub struct PageFetchFailed {
+    pub url: String,
+    pub reason: String,
+}
+
+impl fmt::Display for PageFetchFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fetching recipe page {} failed: {}", self.url, self.reason)
+    }
+}
+
+impl std::error::Error for PageFetchFailed {}
+
+/// Reads the recipe from the posted JSON-LD, else the posted document, else the page at the
+/// posted URL, which needs a `fetcher`.
+pub async fn import_request(request: &ImportRecipeRequest, fetcher: Option<&dyn PageFetcher>) -> Result<ImportedRecipe> {
+    let source_url = request.url.as_deref();
+    match (&request.json_ld, &request.document, source_url) {
+        (Some(json_ld), _, _) => import_json_ld(json_ld, source_url),
+        (None, Some(document), _) => import_document(document, source_url),
+        (None, None, Some(url)) => {
+            let fetcher = fetcher.context("fetching recipe pages is turned off")?;
+            let page = fetcher.fetch(url).await.map_err(|e| PageFetchFailed {
+                url: url.to_string(),
+                reason: e.to_string(),
+            })?;
+            import_document(&page, source_url)
+        }
+        (None, None, None) => bail!("no document, JSON-LD or URL to import"),
+    }
+}
+
+/// Reads the schema.org Recipe from an HTML page or a JSON-LD document. `source_url` is where
+/// the document came from and wins over the recipe's own `url`.
+pub fn import_document(document: &str, source_url: Option<&str>) -> Result<ImportedRecipe> {
+    let trimmed = document.trim_start_matches('\u{feff}').trim();
+    let blocks: Vec<Value> = if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        vec![serde_json::from_str(trimmed)?]
+    } else {
+        json_ld_blocks(trimmed)
+    };
+
+    match blocks.iter().find_map(find_recipe) {
+        Some(recipe) => Ok(import_recipe(recipe, source_url)),
+        None => bail!("no schema.org Recipe found in the document"),
+    }
+}
+
+/// Reads the schema.org Recipe from parsed JSON-LD.
+pub fn import_json_ld(json_ld: &Value, source_url: Option<&str>) -> Result<ImportedRecipe> {
+    match find_recipe(json_ld) {
+        Some(recipe) => Ok(import_recipe(recipe, source_url)),
+        None => bail!("no schema.org Recipe found in the JSON-LD"),
+    }
+}
+
+/// The parseable contents of the page's JSON-LD script blocks.
+fn json_ld_blocks(html: &str) -> Vec<Value> {
+    // ASCII lowercasing keeps byte offsets, so positions found in `lower` index `html`
+    let lower = html.to_ascii_lowercase();
+    let mut blocks = Vec::new();
+    let mut position = 0;
+
+    while let Some(start) = lower[position..].find("<script").map(|i| i + position) {
+        let Some(tag_end) = lower[start..].find('>').map(|i| i + start + 1) else { break };
+        let Some(end) = lower[tag_end..].find("</script").map(|i| i + tag_end) else { break };
+        position = end;
+
+        if !lower[start..tag_end].contains("application/ld+json") {
+            continue;
+        }
+        let content = html[tag_end..end]
+            .trim()
+            .trim_start_matches("<!--")
+            .trim_end_matches("-->")
+            .trim()
+            .trim_start_matches("//<![CDATA[")
+            .trim_end_matches("//]]>")
+            .trim();
+        if let Ok(value) = serde_json::from_str(content) {
+            blocks.push(value);
+        }
+    }
+    blocks
+}
+
+fn is_recipe_type(value: &Value) -> bool {
+    let is_recipe = |name: &str| name.rsplit(['/', ':']).next() == Some("Recipe");
+    match value {
+        Value::String(name) => is_recipe(name),
+        Value::Array(names) => names.iter().any(|n| n.as_str().is_some_and(is_recipe)),
+        _ => false,
+    }
+}
+
+/// The first Recipe node, searching arrays, `@graph` and nested objects.
+fn find_recipe(value: &Value) -> Option<&Map<String, Value>> {
+    match value {
+        Value::Object(object) if object.get("@type").is_some_and(is_recipe_type) => Some(object),
+        Value::Object(object) => object.values().find_map(find_recipe),
+        Value::Array(values) => values.iter().find_map(find_recipe),
+        _ => None,
+    }
+}
+
+fn import_recipe(node: &Map<String, Value>, source_url: Option<&str>) -> ImportedRecipe {
+    let mut warnings = Vec::new();
+
+    let name = node.get("name").and_then(text).map(|name| truncate(&name, MAX_NAME_CHARS));
+    if name.is_none() {
+        warnings.push("recipe has no name".to_string());
+    }
+
+    let ingredient_lines = node
+        .get("recipeIngredient")
+        .or_else(|| node.get("ingredients"))
+        .map(texts)
+        .unwrap_or_default();
+    if ingredient_lines.is_empty() {
+        warnings.push("recipe has no ingredients".to_string());
+    }
+
+    let mut instructions = Vec::new();
+    if let Some(value) = node.get("recipeInstructions") {
+        collect_instructions(value, &mut instructions);
+    }
+
+    let mut duration = |property: &str| {
+        let value = node.get(property).and_then(text)?;
+        let minutes = parse_duration(&value);
+        if minutes.is_none() {
+            warnings.push(format!("{}: could not read duration \"{}\"", property, value));
+        }
+        minutes
+    };
+    let prep = duration("prepTime");
+    let cook = duration("cookTime");
+    let total = duration("totalTime");
+    let (prep_time_minutes, cook_time_minutes) = split_times(prep, cook, total);
+
+    let servings = match node.get("recipeYield").or_else(|| node.get("yield")) {
+        Some(value) => {
+            let servings = texts(value).iter().find_map(|y| leading_number(y));
+            if servings.is_none() {
+                warnings.push(format!("recipeYield: could not read servings from {}", value));
+            }
+            servings.unwrap_or(1)
+        }
+        None => 1,
+    };
+
+    let mut tags: Vec<String> = Vec::new();
+    for value in [node.get("recipeCategory"), node.get("keywords")].into_iter().flatten() {
+        for tag in texts(value).iter().flat_map(|t| t.split(',')) {
+            let tag = tag.trim();
+            if !tag.is_empty() && !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+    }
+
+    let mut unmapped_fields: Vec<String> = node
+        .keys()
+        .filter(|key| !MAPPED_PROPERTIES.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    unmapped_fields.sort();
+
+    let recipe = NewRecipe {
+        name: name.unwrap_or_else(|| "Imported recipe".to_string()),
+        description: node.get("description").and_then(text),
+        cuisine_type: node
+            .get("recipeCuisine")
+            .and_then(|value| texts(value).into_iter().next())
+            .map(|cuisine| truncate(&cuisine, MAX_CUISINE_CHARS)),
+        difficulty_level: None,
+        prep_time_minutes,
+        cook_time_minutes,
+        servings,
+        tags,
+        source_url: source_url
+            .map(str::to_string)
+            .or_else(|| node.get("url").and_then(url))
+            .or_else(|| node.get("mainEntityOfPage").and_then(url)),
+        image_url: node.get("image").and_then(url),
+        ingredients: ingredient_lines.iter().map(|line| recipes::parse_ingredient_line(line)).collect(),
+        steps: recipes::into_steps(instructions.into_iter().map(StepInput::Text).collect()),
+    };
+
+    ImportedRecipe {
+        recipe,
+        unmapped_fields,
+        warnings,
+    }
+}
+
+/// Prep and cook minutes, a missing one being what the total leaves; a total alone is cooking.
+pub fn split_times(prep: Option<i32>, cook: Option<i32>, total: Option<i32>) -> (i32, i32) {
+    match (prep, cook, total) {
+        (Some(prep), Some(cook), _) => (prep, cook),
+        (Some(prep), None, Some(total)) => (prep, (total - prep).max(0)),
+        (None, Some(cook), Some(total)) => ((total - cook).max(0), cook),
+        (None, None, Some(total)) => (0, total),
+        (prep, cook, None) => (prep.unwrap_or(0), cook.unwrap_or(0)),
+    }
+}
+
+/// A single text value: a string, a number, or the first of an array.
+fn text(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => clean_text(s),
+        Value::Number(n) => n.to_string(),
+        Value::Array(values) => return values.iter().find_map(text),
+        Value::Object(object) => return ["@value", "text", "name"].iter().find_map(|key| object.get(*key).and_then(text)),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// Every text value: an array's elements, or a single value.
+fn texts(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(values) => values.iter().filter_map(text).collect(),
+        Value::String(s) if s.contains('\n') => s.lines().map(clean_text).filter(|line| !line.is_empty()).collect(),
+        value => text(value).into_iter().collect(),
+    }
+}
+
+/// A URL: a string, an ImageObject/WebPage `url` or `@id`, or the first of an array.
+fn url(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Array(values) => values.iter().find_map(url),
+        Value::Object(object) => object.get("url").or_else(|| object.get("@id")).and_then(url),
+        _ => None,
+    }
+}
+
+/// Instruction texts in order; sections are flattened and block-level HTML becomes separate steps.
+fn collect_instructions(value: &Value, instructions: &mut Vec<String>) {
+    match value {
+        Value::String(s) => {
+            let separated = s
+                .replace("<br>", "\n")
+                .replace("<br/>", "\n")
+                .replace("<br />", "\n")
+                .replace("</p>", "\n")
+                .replace("</li>", "\n");
+            instructions.extend(separated.lines().map(clean_text).filter(|line| !line.is_empty()));
+        }
+        Value::Array(values) => values.iter().for_each(|v| collect_instructions(v, instructions)),
+        Value::Object(object) => {
+            if let Some(items) = object.get("itemListElement") {
+                collect_instructions(items, instructions);
+            } else if let Some(step) = object.get("text").or_else(|| object.get("name")) {
+                collect_instructions(step, instructions);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Text with HTML tags removed, common entities decoded and whitespace collapsed.
+pub fn clean_text(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                stripped.push(' ');
+            }
+            c if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+    decode_entities(&stripped).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').filter(|end| *end <= 10).map(|end| &rest[1..end + 1]);
+        let replacement = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (entity, replacement) {
+            (Some(entity), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Minutes in an ISO 8601 duration such as "PT1H30M", "P0DT45M" or "PT0.5H". Durations in
+/// years or months are not times a recipe takes and are not read.
+pub fn parse_duration(duration: &str) -> Option<i32> {
+    let duration = duration.trim().to_ascii_uppercase();
+    let rest = duration.strip_prefix('P')?;
+    let (date, time) = match rest.split_once('T') {
+        Some((date, time)) => (date, time),
+        None => (rest, ""),
+    };
+
+    let mut minutes = 0.0_f64;
+    let mut read_any = false;
+    for (part, in_time) in [(date, false), (time, true)] {
+        let mut number = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() || c == '.' || c == ',' {
+                number.push(if c == ',' { '.' } else { c });
+                continue;
+            }
+            let value: f64 = number.parse().ok()?;
+            number.clear();
+            minutes += value
+                * match (c, in_time) {
+                    ('W', false) => 7.0 * 24.0 * 60.0,
+                    ('D', false) => 24.0 * 60.0,
+                    ('H', true) => 60.0,
+                    ('M', true) => 1.0,
+                    ('S', true) => 1.0 / 60.0,
+                    _ => return None,
+                };
+            read_any = true;
+        }
+        if !number.is_empty() {
+            return None;
+        }
+    }
+    (read_any && minutes <= i32::MAX as f64).then(|| minutes.round() as i32)
+}
+
+/// The first whole number in "4", "4 servings" or "Makes 12 muffins".
+pub fn leading_number(text: &str) -> Option<i32> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let digits: String = text[start..].chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok().filter(|n| *n > 0)
+}
+
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::async_trait;
+    use serde_json::json;
+
+    #[test]
+    fn parses_iso_durations() {
+        assert_eq!(parse_duration("PT1H30M"), Some(90));
+        assert_eq!(parse_duration("P0DT45M"), Some(45));
+        assert_eq!(parse_duration("PT0.5H"), Some(30));
+        assert_eq!(parse_duration("pt20m"), Some(20));
+        assert_eq!(parse_duration("P1D"), Some(1440));
+        assert_eq!(parse_duration("PT90S"), Some(2));
+        assert_eq!(parse_duration("P1M"), None);
+        assert_eq!(parse_duration("PT"), None);
+        assert_eq!(parse_duration("PT15"), None);
+        assert_eq!(parse_duration("30 minutes"), None);
+    }
+
+    #[test]
+    fn reads_json_ld_script_blocks() {
+        let html = r#"<html><head>
+            <script type="text/javascript">var recipe = {"@type": "Recipe"};</script>
+            <SCRIPT TYPE="application/ld+json">{"@type": "WebSite"}</SCRIPT>
+            <script type="application/ld+json"><!-- {"@type": "Recipe", "name": "Soup"} --></script>
+            <script type="application/ld+json">{not json}</script>
+        </head></html>"#;
+        let blocks = json_ld_blocks(html);
+        assert_eq!(blocks, vec![json!({"@type": "WebSite"}), json!({"@type": "Recipe", "name": "Soup"})]);
+    }
+
+    #[test]
+    fn finds_the_recipe_in_graphs_and_arrays() {
+        let graph = json!({
+            "@context": "https://schema.org",
+            "@graph": [{"@type": "WebPage"}, {"@type": ["Recipe", "NewsArticle"], "name": "Stew"}]
+        });
+        assert_eq!(find_recipe(&graph).and_then(|r| r.get("name")), Some(&json!("Stew")));
+
+        let array = json!([{"@type": "Person"}, {"@type": "http://schema.org/Recipe", "name": "Pie"}]);
+        assert_eq!(find_recipe(&array).and_then(|r| r.get("name")), Some(&json!("Pie")));
+
+        assert!(find_recipe(&json!({"@type": "RecipeCollection"})).is_none());
+    }
+
+    #[test]
+    fn flattens_instruction_sections() {
+        let value = json!([
+            {"@type": "HowToSection", "name": "Dough", "itemListElement": [
+                {"@type": "HowToStep", "text": "Mix the flour &amp; water."},
+                {"@type": "HowToStep", "text": "Knead."}
+            ]},
+            {"@type": "HowToStep", "name": "Bake"},
+            "<p>Cool.</p><p>Slice.</p>"
+        ]);
+        let mut instructions = Vec::new();
+        collect_instructions(&value, &mut instructions);
+        assert_eq!(instructions, vec!["Mix the flour & water.", "Knead.", "Bake", "Cool.", "Slice."]);
+    }
+
+    #[test]
+    fn decodes_named_and_numeric_entities() {
+        assert_eq!(decode_entities("Salt &amp; pepper"), "Salt & pepper");
+        assert_eq!(decode_entities("&quot;Best&quot; &#8211; &#x2019;s"), "\"Best\" \u{2013} \u{2019}s");
+        assert_eq!(decode_entities("AT&T & co &unknown; &#xZZ;"), "AT&T & co &unknown; &#xZZ;");
+        assert_eq!(clean_text("<b>1&nbsp;cup</b>\n  sugar"), "1 cup sugar");
+    }
+
+    struct FakeFetcher;
+
+    #[async_trait]
+    impl PageFetcher for FakeFetcher {
+        async fn fetch(&self, url: &str) -> Result<String> {
+            match url {
+                "https://example.com/pancakes" => Ok(r#"<script type="application/ld+json">
+                    {"@type": "Recipe", "name": "Pancakes", "recipeIngredient": ["2 cups flour"],
+                     "recipeInstructions": "Mix.", "prepTime": "PT10M", "totalTime": "PT25M",
+                     "recipeYield": "4 servings", "url": "https://example.com/amp/pancakes"}
+                    </script>"#
+                    .to_string()),
+                _ => bail!("404 Not Found"),
+            }
+        }
+    }
+
+    fn request(url: &str) -> ImportRecipeRequest {
+        ImportRecipeRequest {
+            document: None,
+            json_ld: None,
+            url: Some(url.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn imports_a_fetched_page() {
+        let imported = import_request(&request("https://example.com/pancakes"), Some(&FakeFetcher))
+            .await
+            .unwrap();
+        let recipe = imported.recipe;
+        assert_eq!(recipe.name, "Pancakes");
+        assert_eq!((recipe.prep_time_minutes, recipe.cook_time_minutes, recipe.servings), (10, 15, 4));
+        assert_eq!(recipe.ingredients[0].unit, "cup");
+        assert_eq!(recipe.source_url.as_deref(), Some("https://example.com/pancakes"));
+        assert!(imported.warnings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tells_fetch_failures_apart_from_unreadable_pages() {
+        let failed = import_request(&request("https://example.com/missing"), Some(&FakeFetcher)).await.unwrap_err();
+        assert!(failed.is::<PageFetchFailed>());
+
+        let turned_off = import_request(&request("https://example.com/pancakes"), None).await.unwrap_err();
+        assert!(!turned_off.is::<PageFetchFailed>());
+
+        // Posted documents are read without fetching
+        let posted = ImportRecipeRequest {
+            document: Some("<p>no recipe here</p>".to_string()),
+            ..request("https://example.com/pancakes")
+        };
+        let unreadable = import_request(&posted, Some(&FakeFetcher)).await.unwrap_err();
+        assert!(!unreadable.is::<PageFetchFailed>());
+    }
+}
@@ -0,0 +1,746 @@
+// Recipes: reading plain-text ingredient lines and storing recipes with their ingredients and steps
+//
+// Ingredient lines are read as quantity, unit, name and preparation note:
+// "1 ½ cups (200 g) all-purpose flour, sifted" -> 1.5 cup "all-purpose flour", note "200 g; sifted".
+// Quantities may be whole, decimal, fractions ("1/2", "½", "1 1/2") or ranges ("2-3", "2 to 3",
+// stored as the lower bound with the range in the note). Parenthesised measures, anything after
+// the first comma and phrases such as "to taste" or "for garnish" go to the note. A line without
+// a quantity ("salt to taste") has amount 0 and no unit.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::models::{DifficultyLevel, RecipeIngredient, RecipeStep};
+
+/// Unit spellings and the unit they are stored as.
+const UNITS: &[(&[&str], &str)] = &[
+    (&["cup", "cups", "c"], "cup"),
+    (&["tablespoon", "tablespoons", "tbsp", "tbsps", "tbs", "tbl", "tbls"], "tbsp"),
+    (&["teaspoon", "teaspoons", "tsp", "tsps"], "tsp"),
+    (&["gram", "grams", "gramme", "grammes", "g", "gr"], "g"),
+    (&["kilogram", "kilograms", "kg", "kgs"], "kg"),
+    (&["milligram", "milligrams", "mg"], "mg"),
+    (&["milliliter", "milliliters", "millilitre", "millilitres", "ml"], "ml"),
+    (&["centiliter", "centiliters", "centilitre", "centilitres", "cl"], "cl"),
+    (&["deciliter", "deciliters", "decilitre", "decilitres", "dl"], "dl"),
+    (&["liter", "liters", "litre", "litres", "l"], "l"),
+    (&["fl oz", "fluid ounce", "fluid ounces"], "fl oz"),
+    (&["ounce", "ounces", "oz"], "oz"),
+    (&["pound", "pounds", "lb", "lbs"], "lb"),
+    (&["pint", "pints", "pt"], "pint"),
+    (&["quart", "quarts", "qt"], "quart"),
+    (&["gallon", "gallons", "gal"], "gallon"),
+    (&["pinch", "pinches"], "pinch"),
+    (&["dash", "dashes"], "dash"),
+    (&["drop", "drops"], "drop"),
+    (&["clove", "cloves"], "clove"),
+    (&["can", "cans", "tin", "tins"], "can"),
+    (&["jar", "jars"], "jar"),
+    (&["package", "packages", "pkg", "packet", "packets"], "package"),
+    (&["carton", "cartons"], "carton"),
+    (&["slice", "slices"], "slice"),
+    (&["stick", "sticks"], "stick"),
+    (&["bunch", "bunches"], "bunch"),
+    (&["sprig", "sprigs"], "sprig"),
+    (&["stalk", "stalks"], "stalk"),
+    (&["head", "heads"], "head"),
+    (&["piece", "pieces"], "piece"),
+    (&["handful", "handfuls"], "handful"),
+    (&["large"], "large"),
+    (&["medium"], "medium"),
+    (&["small"], "small"),
+];
+
+const NUMBER_WORDS: &[(&str, f32)] = &[
+    ("a", 1.0),
+    ("an", 1.0),
+    ("one", 1.0),
+    ("two", 2.0),
+    ("three", 3.0),
+    ("four", 4.0),
+    ("five", 5.0),
+    ("six", 6.0),
+    ("seven", 7.0),
+    ("eight", 8.0),
+    ("nine", 9.0),
+    ("ten", 10.0),
+    ("eleven", 11.0),
+    ("twelve", 12.0),
+    ("half", 0.5),
+    ("dozen", 12.0),
+];
+
+const UNICODE_FRACTIONS: &[(char, &str)] = &[
+    ('½', "1/2"),
+    ('⅓', "1/3"),
+    ('⅔', "2/3"),
+    ('¼', "1/4"),
+    ('¾', "3/4"),
+    ('⅕', "1/5"),
+    ('⅖', "2/5"),
+    ('⅗', "3/5"),
+    ('⅘', "4/5"),
+    ('⅙', "1/6"),
+    ('⅚', "5/6"),
+    ('⅛', "1/8"),
+    ('⅜', "3/8"),
+    ('⅝', "5/8"),
+    ('⅞', "7/8"),
+];
+
+/// Phrases that end an ingredient line without a comma and belong in the note.
+const TRAILING_NOTES: &[&str] = &[
+    "to taste",
+    "as needed",
+    "as required",
+    "if needed",
+    "optional",
+    "for garnish",
+    "for serving",
+    "for frying",
+    "for greasing",
+    "divided",
+];
+
+/// An ingredient as sent by a client: a plain-text line or already split into fields.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IngredientInput {
+    Line(String),
+    Structured(RecipeIngredient),
+}
+
+impl IngredientInput {
+    pub fn into_ingredient(self) -> RecipeIngredient {
+        match self {
+            IngredientInput::Line(line) => parse_ingredient_line(&line),
+            IngredientInput::Structured(ingredient) => ingredient,
+        }
+    }
+}
+
+/// Longest ingredient name and unit the `recipe_ingredients` columns hold.
+pub const MAX_INGREDIENT_NAME_CHARS: usize = 255;
+pub const MAX_UNIT_CHARS: usize = 50;
+
+/// An ingredient sent by a client that cannot be stored as it is.
+#[derive(Debug)]
+pub struct InvalidIngredient(pub String);
+
+impl fmt::Display for InvalidIngredient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid ingredient: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidIngredient {}
+
+/// Reads a client's ingredients for storing. Blank lines and nameless ingredients are
+/// dropped; a name or unit longer than its column is an error rather than cut short.
+pub fn into_ingredients(inputs: Vec<IngredientInput>) -> Result<Vec<RecipeIngredient>, InvalidIngredient> {
+    let mut ingredients = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let mut ingredient = input.into_ingredient();
+        ingredient.name = ingredient.name.trim().to_string();
+        ingredient.unit = ingredient.unit.trim().to_string();
+        if ingredient.name.is_empty() {
+            continue;
+        }
+        if ingredient.name.chars().count() > MAX_INGREDIENT_NAME_CHARS {
+            return Err(InvalidIngredient(format!("name is longer than {} characters", MAX_INGREDIENT_NAME_CHARS)));
+        }
+        if ingredient.unit.chars().count() > MAX_UNIT_CHARS {
+            return Err(InvalidIngredient(format!("unit \"{}\" is longer than {} characters", ingredient.unit, MAX_UNIT_CHARS)));
+        }
+        ingredients.push(ingredient);
+    }
+    if ingredients.is_empty() {
+        return Err(InvalidIngredient("recipe has no ingredients".to_string()));
+    }
+    Ok(ingredients)
+}
+
+/// A step as sent by a client: its instruction text or all of its fields.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StepInput {
+    Text(String),
+    Structured(RecipeStep),
+}
+
+/// Numbers steps in order, keeping the number of structured steps that have one.
+pub fn into_steps(steps: Vec<StepInput>) -> Vec<RecipeStep> {
+    steps
+        .into_iter()
+        .zip(1..)
+        .map(|(step, number)| match step {
+            StepInput::Text(instruction) => RecipeStep {
+                step_number: number,
+                instruction: instruction.trim().to_string(),
+                time_estimate_minutes: None,
+                temperature: None,
+                equipment_needed: Vec::new(),
+                tips: Vec::new(),
+            },
+            StepInput::Structured(step) if step.step_number > 0 => step,
+            StepInput::Structured(step) => RecipeStep { step_number: number, ..step },
+        })
+        .filter(|step| !step.instruction.is_empty())
+        .collect()
+}
+
+/// Everything stored for a new recipe.
+#[derive(Debug, Clone)]
+pub struct NewRecipe {
+    pub name: String,
+    pub description: Option<String>,
+    pub cuisine_type: Option<String>,
+    pub difficulty_level: Option<DifficultyLevel>,
+    pub prep_time_minutes: i32,
+    pub cook_time_minutes: i32,
+    pub servings: i32,
+    pub tags: Vec<String>,
+    pub source_url: Option<String>,
+    pub image_url: Option<String>,
+    pub ingredients: Vec<RecipeIngredient>,
+    pub steps: Vec<RecipeStep>,
+}
+
+fn difficulty_name(level: &DifficultyLevel) -> &'static str {
+    match level {
+        DifficultyLevel::Beginner => "beginner",
+        DifficultyLevel::Intermediate => "intermediate",
+        DifficultyLevel::Advanced => "advanced",
+        DifficultyLevel::Expert => "expert",
+    }
+}
+
+fn difficulty_from_name(name: &str) -> Option<DifficultyLevel> {
+    match name {
+        "beginner" => Some(DifficultyLevel::Beginner),
+        "intermediate" => Some(DifficultyLevel::Intermediate),
+        "advanced" => Some(DifficultyLevel::Advanced),
+        "expert" => Some(DifficultyLevel::Expert),
+        _ => None,
+    }
+}
+
+/// Stores the recipe with its ingredients and steps in one transaction and returns its id.
+pub async fn insert_recipe(db: &PgPool, user_id: Uuid, recipe: &NewRecipe) -> Result<Uuid> {
+    let mut tx = db.begin().await?;
+    let recipe_id = insert_recipe_in(&mut tx, user_id, recipe).await?;
+    tx.commit().await?;
+    Ok(recipe_id)
+}
+
+/// Stores the recipe within the caller's transaction, so several can be committed together.
+pub async fn insert_recipe_in(tx: &mut Transaction<'_, Postgres>, user_id: Uuid, recipe: &NewRecipe) -> Result<Uuid> {
+    let (recipe_id,): (Uuid,) = sqlx::query_as(
+        "INSERT INTO recipes (user_id, name, description, cuisine_type, difficulty_level, prep_time_minutes, \
+                              cook_time_minutes, servings, tags, source_url, image_url) \
+         VALUES ($1, $2, $3, $4, COALESCE($5::difficulty_enum, 'intermediate'), $6, $7, $8, $9, $10, $11) \
+         RETURNING id",
+    )
+    .bind(user_id)
+    .bind(&recipe.name)
+    .bind(&recipe.description)
+    .bind(&recipe.cuisine_type)
+    .bind(recipe.difficulty_level.as_ref().map(difficulty_name))
+    .bind(recipe.prep_time_minutes)
+    .bind(recipe.cook_time_minutes)
+    .bind(recipe.servings)
+    .bind(&recipe.tags)
+    .bind(&recipe.source_url)
+    .bind(&recipe.image_url)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    for (index, ingredient) in recipe.ingredients.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO recipe_ingredients (recipe_id, ingredient_name, amount, unit, preparation_note, \
+                                             substitutions, order_index) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(recipe_id)
+        .bind(&ingredient.name)
+        .bind(ingredient.amount as f64)
+        .bind(&ingredient.unit)
+        .bind(&ingredient.preparation_note)
+        .bind(&ingredient.substitutions)
+        .bind(index as i32)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    for step in &recipe.steps {
+        sqlx::query(
+            "INSERT INTO recipe_steps (recipe_id, step_number, instruction, time_estimate_minutes, temperature, \
+                                       equipment_needed, tips) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(recipe_id)
+        .bind(step.step_number)
+        .bind(&step.instruction)
+        .bind(step.time_estimate_minutes)
+        .bind(&step.temperature)
+        .bind(&step.equipment_needed)
+        .bind(&step.tips)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(recipe_id)
+}
+
+/// All of the user's recipes with their ingredients and steps, oldest first.
+pub async fn load_recipes(db: &PgPool, user_id: Uuid) -> Result<Vec<(Uuid, NewRecipe)>> {
+    type RecipeRow = (
+        Uuid,
+        String,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+        i32,
+        i32,
+        i32,
+        Option<Vec<String>>,
+        Option<String>,
+        Option<String>,
+    );
+    let rows: Vec<RecipeRow> = sqlx::query_as(
+        "SELECT id, name, description, cuisine_type, difficulty_level::text, prep_time_minutes, cook_time_minutes, \
+                servings, tags, source_url, image_url \
+         FROM recipes WHERE user_id = $1 ORDER BY created_at, id",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    type IngredientRow = (Uuid, String, f64, String, Option<String>, Option<Vec<String>>);
+    let ingredient_rows: Vec<IngredientRow> = sqlx::query_as(
+        "SELECT ri.recipe_id, ri.ingredient_name, ri.amount::float8, ri.unit, ri.preparation_note, ri.substitutions \
+         FROM recipe_ingredients ri JOIN recipes r ON r.id = ri.recipe_id \
+         WHERE r.user_id = $1 ORDER BY ri.recipe_id, ri.order_index",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    let mut ingredients: HashMap<Uuid, Vec<RecipeIngredient>> = HashMap::new();
+    for (recipe_id, name, amount, unit, preparation_note, substitutions) in ingredient_rows {
+        ingredients.entry(recipe_id).or_default().push(RecipeIngredient {
+            ingredient_id: Uuid::nil(),
+            name,
+            amount: amount as f32,
+            unit,
+            preparation_note,
+            substitutions: substitutions.unwrap_or_default(),
+        });
+    }
+
+    type StepRow = (Uuid, i32, String, Option<i32>, Option<String>, Option<Vec<String>>, Option<Vec<String>>);
+    let step_rows: Vec<StepRow> = sqlx::query_as(
+        "SELECT rs.recipe_id, rs.step_number, rs.instruction, rs.time_estimate_minutes, rs.temperature, \
+                rs.equipment_needed, rs.tips \
+         FROM recipe_steps rs JOIN recipes r ON r.id = rs.recipe_id \
+         WHERE r.user_id = $1 ORDER BY rs.recipe_id, rs.step_number",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    let mut steps: HashMap<Uuid, Vec<RecipeStep>> = HashMap::new();
+    for (recipe_id, step_number, instruction, time_estimate_minutes, temperature, equipment_needed, tips) in step_rows {
+        steps.entry(recipe_id).or_default().push(RecipeStep {
+            step_number,
+            instruction,
+            time_estimate_minutes,
+            temperature,
+            equipment_needed: equipment_needed.unwrap_or_default(),
+            tips: tips.unwrap_or_default(),
+        });
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, name, description, cuisine_type, difficulty, prep, cook, servings, tags, source_url, image_url)| {
+            let recipe = NewRecipe {
+                name,
+                description,
+                cuisine_type,
+                difficulty_level: difficulty.as_deref().and_then(difficulty_from_name),
+                prep_time_minutes: prep,
+                cook_time_minutes: cook,
+                servings,
+                tags: tags.unwrap_or_default(),
+                source_url,
+                image_url,
+                ingredients: ingredients.remove(&id).unwrap_or_default(),
+                steps: steps.remove(&id).unwrap_or_default(),
+            };
+            (id, recipe)
+        })
+        .collect())
+}
+
+/// Splits "1 ½ cups (200 g) all-purpose flour, sifted" into amount, unit, name and note.
+pub fn parse_ingredient_line(line: &str) -> RecipeIngredient {
+    let line = line.trim().trim_start_matches(['-', '*', '•', '·']).trim();
+    let mut notes = Vec::new();
+
+    // Parenthesised text ("(200 g)", "(optional)") goes to the note
+    let mut text = String::with_capacity(line.len());
+    let mut depth = 0;
+    let mut parenthesised = String::new();
+    for c in line.chars() {
+        match c {
+            '(' | '[' => {
+                depth += 1;
+                if depth == 1 {
+                    parenthesised.clear();
+                    continue;
+                }
+            }
+            ')' | ']' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    push_note(&mut notes, &parenthesised);
+                    text.push(' ');
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        if depth > 0 {
+            parenthesised.push(c);
+        } else {
+            text.push(c);
+        }
+    }
+
+    // Anything after the first comma is preparation ("sifted", "finely chopped")
+    let (main, preparation) = match text.split_once(',') {
+        Some((main, rest)) => (main.to_string(), Some(rest.to_string())),
+        None => (text, None),
+    };
+
+    let mut tokens = tokens(&main);
+    let mut trailing = Vec::new();
+    'strip: loop {
+        for phrase in TRAILING_NOTES {
+            let words: Vec<&str> = phrase.split(' ').collect();
+            if tokens.len() > words.len()
+                && tokens[tokens.len() - words.len()..].iter().map(|t| t.to_lowercase()).eq(words.iter().map(|w| w.to_string()))
+            {
+                tokens.truncate(tokens.len() - words.len());
+                trailing.insert(0, phrase.to_string());
+                continue 'strip;
+            }
+        }
+        break;
+    }
+
+    let (amount, range_end, mut position) = quantity(&tokens).unwrap_or((0.0, None, 0));
+    let unit = match unit(&tokens[position..]) {
+        Some((unit, used)) => {
+            position += used;
+            unit
+        }
+        None => String::new(),
+    };
+    if tokens.get(position).is_some_and(|t| t.eq_ignore_ascii_case("of")) {
+        position += 1;
+    }
+    let name = tokens[position..].join(" ");
+
+    if let Some(end) = range_end {
+        notes.insert(0, format!("{}-{} {}", format_amount(amount), format_amount(end), unit).trim().to_string());
+    }
+    if let Some(preparation) = preparation {
+        push_note(&mut notes, &preparation);
+    }
+    for phrase in trailing {
+        push_note(&mut notes, &phrase);
+    }
+
+    RecipeIngredient {
+        ingredient_id: Uuid::nil(),
+        // A line that is all note ("salt, to taste" keeps "salt"; "(optional)" alone keeps the line)
+        name: if name.is_empty() { line.to_string() } else { name },
+        amount,
+        unit,
+        preparation_note: (!notes.is_empty()).then(|| notes.join("; ")),
+        substitutions: Vec::new(),
+    }
+}
+
+/// The ingredient as a line `parse_ingredient_line` reads back: "1 1/2 cup flour, sifted".
+pub fn format_ingredient(ingredient: &RecipeIngredient) -> String {
+    let mut parts = Vec::new();
+    if ingredient.amount > 0.0 {
+        parts.push(format_quantity(ingredient.amount));
+    }
+    if !ingredient.unit.is_empty() {
+        parts.push(ingredient.unit.clone());
+    }
+    parts.push(ingredient.name.clone());
+    let line = parts.join(" ");
+    match &ingredient.preparation_note {
+        Some(note) => format!("{}, {}", line, note),
+        None => line,
+    }
+}
+
+/// An amount as a cook writes it: "1 1/2", "3/4", else a decimal.
+pub fn format_quantity(amount: f32) -> String {
+    const FRACTIONS: &[(f32, &str)] = &[
+        (0.125, "1/8"),
+        (0.25, "1/4"),
+        (1.0 / 3.0, "1/3"),
+        (0.375, "3/8"),
+        (0.5, "1/2"),
+        (0.625, "5/8"),
+        (2.0 / 3.0, "2/3"),
+        (0.75, "3/4"),
+        (0.875, "7/8"),
+    ];
+    let whole = amount.trunc();
+    let fraction = amount - whole;
+    match FRACTIONS.iter().find(|(value, _)| (fraction - value).abs() < 0.005) {
+        Some((_, text)) if whole == 0.0 => text.to_string(),
+        Some((_, text)) => format!("{} {}", whole, text),
+        None => format_amount(amount),
+    }
+}
+
+fn push_note(notes: &mut Vec<String>, note: &str) {
+    let note = note.split_whitespace().collect::<Vec<_>>().join(" ");
+    let note = note.trim_matches(|c: char| c == ',' || c == ';' || c.is_whitespace());
+    if !note.is_empty() {
+        notes.push(note.to_string());
+    }
+}
+
+/// Words of the line, with unicode fractions spelled out ("1½" -> "1 1/2"), numbers split from
+/// units ("200g" -> "200 g") and spaced range dashes joined ("2 - 3" -> "2-3").
+fn tokens(text: &str) -> Vec<String> {
+    let mut spaced = String::with_capacity(text.len());
+    for c in text.chars() {
+        match UNICODE_FRACTIONS.iter().find(|(f, _)| *f == c) {
+            Some((_, ascii)) => {
+                spaced.push(' ');
+                spaced.push_str(ascii);
+                spaced.push(' ');
+            }
+            None if c == '–' || c == '—' => spaced.push('-'),
+            None if c == '⁄' => spaced.push('/'),
+            None => spaced.push(c),
+        }
+    }
+    let spaced = spaced.replace(" - ", "-").replace("/ ", "/").replace(" /", "/");
+
+    let mut tokens = Vec::new();
+    for token in spaced.split_whitespace() {
+        let digits = token.find(|c: char| c.is_alphabetic()).unwrap_or(token.len());
+        if digits > 0 && digits < token.len() && number(&token[..digits]).is_some() {
+            tokens.push(token[..digits].to_string());
+            tokens.push(token[digits..].to_string());
+        } else {
+            tokens.push(token.to_string());
+        }
+    }
+    tokens
+}
+
+fn number(token: &str) -> Option<f32> {
+    if let Ok(value) = token.parse::<f32>() {
+        return (value.is_finite() && value >= 0.0).then_some(value);
+    }
+    let (numerator, denominator) = token.split_once('/')?;
+    let denominator: f32 = denominator.parse().ok()?;
+    (denominator > 0.0).then_some(numerator.parse::<f32>().ok()? / denominator)
+}
+
+/// A number with an optional fraction after it: "1 1/2".
+fn mixed_number(tokens: &[String]) -> Option<(f32, usize)> {
+    let whole = number(tokens.first()?)?;
+    match tokens.get(1).filter(|t| t.contains('/')).and_then(|t| number(t)) {
+        Some(fraction) if !tokens[0].contains('/') => Some((whole + fraction, 2)),
+        _ => Some((whole, 1)),
+    }
+}
+
+/// The leading quantity: its value, the end of a range, and how many tokens it took.
+fn quantity(tokens: &[String]) -> Option<(f32, Option<f32>, usize)> {
+    let first = tokens.first()?;
+    let word = |index: usize| tokens.get(index).map(|t| t.to_lowercase());
+
+    // "2-3"
+    if let Some((low, high)) = first.split_once('-') {
+        if let (Some(low), Some(high)) = (number(low), number(high)) {
+            return Some((low, Some(high), 1));
+        }
+    }
+
+    let (mut amount, mut used) = match mixed_number(tokens) {
+        Some(found) => found,
+        None => match (first.to_lowercase().as_str(), word(1).as_deref()) {
+            ("a" | "an", Some("half")) | ("half", Some("a" | "an")) => (0.5, 2),
+            (first, _) => (NUMBER_WORDS.iter().find(|(w, _)| *w == first)?.1, 1),
+        },
+    };
+    // "a dozen", "2 dozen"
+    if word(used).as_deref() == Some("dozen") {
+        amount *= 12.0;
+        used += 1;
+    }
+
+    // "2 to 3", "2 or 3"
+    if matches!(word(used).as_deref(), Some("to" | "or")) {
+        if let Some((high, high_used)) = mixed_number(&tokens[used + 1..]) {
+            return Some((amount, Some(high), used + 1 + high_used));
+        }
+    }
+    Some((amount, None, used))
+}
+
+/// The unit at the start of `tokens` and how many tokens it took.
+fn unit(tokens: &[String]) -> Option<(String, usize)> {
+    let lookup = |text: &str| {
+        let text = text.trim_end_matches('.');
+        UNITS
+            .iter()
+            .find(|(spellings, _)| spellings.contains(&text))
+            .map(|(_, unit)| unit.to_string())
+    };
+
+    if let [first, second, ..] = tokens {
+        if let Some(unit) = lookup(&format!("{} {}", first, second).to_lowercase()) {
+            return Some((unit, 2));
+        }
+    }
+    let first = tokens.first()?;
+    // "T" is a tablespoon and "t" a teaspoon in older recipes
+    match first.trim_end_matches('.') {
+        "T" | "Tbsp" | "TB" => Some(("tbsp".to_string(), 1)),
+        "t" => Some(("tsp".to_string(), 1)),
+        _ => lookup(&first.to_lowercase()).map(|unit| (unit, 1)),
+    }
+}
+
+fn format_amount(amount: f32) -> String {
+    let rounded = (amount * 1000.0).round() / 1000.0;
+    if rounded.fract() == 0.0 {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{}", rounded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(line: &str) -> (f32, String, String, Option<String>) {
+        let ingredient = parse_ingredient_line(line);
+        (ingredient.amount, ingredient.unit, ingredient.name, ingredient.preparation_note)
+    }
+
+    #[test]
+    fn parses_quantity_unit_name_and_notes() {
+        assert_eq!(
+            parsed("1 ½ cups (200 g) all-purpose flour, sifted"),
+            (1.5, "cup".into(), "all-purpose flour".into(), Some("200 g; sifted".into()))
+        );
+        assert_eq!(parsed("2 cloves garlic"), (2.0, "clove".into(), "garlic".into(), None));
+        assert_eq!(parsed("- 200g butter"), (200.0, "g".into(), "butter".into(), None));
+        assert_eq!(parsed("1 T. olive oil"), (1.0, "tbsp".into(), "olive oil".into(), None));
+        assert_eq!(parsed("a pinch of salt"), (1.0, "pinch".into(), "salt".into(), None));
+        assert_eq!(parsed("2 fl oz cream"), (2.0, "fl oz".into(), "cream".into(), None));
+    }
+
+    #[test]
+    fn keeps_ranges_and_trailing_phrases_in_the_note() {
+        assert_eq!(
+            parsed("2-3 tbsp sugar, divided"),
+            (2.0, "tbsp".into(), "sugar".into(), Some("2-3 tbsp; divided".into()))
+        );
+        assert_eq!(
+            parsed("2 to 3 large eggs"),
+            (2.0, "large".into(), "eggs".into(), Some("2-3 large".into()))
+        );
+        assert_eq!(parsed("salt to taste"), (0.0, String::new(), "salt".into(), Some("to taste".into())));
+        assert_eq!(parsed("half a lemon"), (0.5, String::new(), "lemon".into(), None));
+        assert_eq!(parsed("a dozen eggs"), (12.0, String::new(), "eggs".into(), None));
+    }
+
+    #[test]
+    fn formats_quantities_as_fractions() {
+        assert_eq!(format_quantity(1.5), "1 1/2");
+        assert_eq!(format_quantity(0.75), "3/4");
+        assert_eq!(format_quantity(2.0 / 3.0), "2/3");
+        assert_eq!(format_quantity(3.0), "3");
+        assert_eq!(format_quantity(0.3), "0.3");
+        assert_eq!(format_quantity(2.125), "2 1/8");
+    }
+
+    #[test]
+    fn formatted_quantities_read_back() {
+        for amount in [0.125, 0.25, 1.0 / 3.0, 0.5, 0.75, 1.0, 1.5, 2.0 / 3.0, 2.375, 4.0, 0.3, 12.25] {
+            let line = format!("{} cup milk", format_quantity(amount));
+            let ingredient = parse_ingredient_line(&line);
+            assert!((ingredient.amount - amount).abs() < 0.001, "{} read back as {}", line, ingredient.amount);
+        }
+    }
+
+    #[test]
+    fn formatted_ingredients_read_back() {
+        for line in ["1 ½ cups (200 g) all-purpose flour, sifted", "2 cloves garlic", "salt, to taste", "3/4 tsp baking soda"] {
+            let ingredient = parse_ingredient_line(line);
+            let again = parse_ingredient_line(&format_ingredient(&ingredient));
+            assert_eq!(
+                (again.amount, &again.unit, &again.name, &again.preparation_note),
+                (ingredient.amount, &ingredient.unit, &ingredient.name, &ingredient.preparation_note),
+                "{}",
+                line
+            );
+        }
+    }
+
+    fn structured(name: &str, unit: &str) -> IngredientInput {
+        IngredientInput::Structured(RecipeIngredient {
+            ingredient_id: Uuid::nil(),
+            name: name.to_string(),
+            amount: 1.0,
+            unit: unit.to_string(),
+            preparation_note: None,
+            substitutions: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn drops_blank_ingredients() {
+        let ingredients = into_ingredients(vec![
+            IngredientInput::Line("2 cloves garlic".into()),
+            IngredientInput::Line("   ".into()),
+            structured("  ", "cup"),
+            structured(" butter ", " g "),
+        ])
+        .unwrap();
+        let names: Vec<_> = ingredients.iter().map(|i| (i.name.as_str(), i.unit.as_str())).collect();
+        assert_eq!(names, [("garlic", "clove"), ("butter", "g")]);
+
+        assert!(into_ingredients(vec![IngredientInput::Line(String::new())]).is_err());
+    }
+
+    #[test]
+    fn refuses_names_and_units_too_long_to_store() {
+        let name = "é".repeat(MAX_INGREDIENT_NAME_CHARS);
+        assert!(into_ingredients(vec![structured(&name, "g")]).is_ok());
+        assert!(into_ingredients(vec![structured(&format!("{}e", name), "g")]).is_err());
+        assert!(into_ingredients(vec![structured("flour", &"x".repeat(MAX_UNIT_CHARS + 1))]).is_err());
+        assert!(into_ingredients(vec![IngredientInput::Line(format!("1 cup {}", "a".repeat(300)))]).is_err());
+    }
+}
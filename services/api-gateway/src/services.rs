@@ -1,11 +1,42 @@
+// Calls to the other services behind the gateway
 use anyhow::Result;
+use std::time::Duration;
+use uuid::Uuid;
+
+const NUTRITION_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct ServiceOrchestrator {
-    // TODO: Implement service orchestration
+    client: reqwest::Client,
+    nutrition_service_url: String,
 }
 
 impl ServiceOrchestrator {
     pub async fn new() -> Result<Self> {
-        Ok(Self {})
+        let nutrition_service_url = std::env::var("NUTRITION_SERVICE_URL")
+            .unwrap_or_else(|_| "http://nutrition-service:8081".to_string());
+        let client = reqwest::Client::builder().timeout(NUTRITION_TIMEOUT).build()?;
+        Ok(Self {
+            client,
+            nutrition_service_url: nutrition_service_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Has the nutrition service compute and store the per-serving nutrition and
+    /// `nutrition_score` of freshly stored recipes. Runs in the background: a recipe is
+    /// saved even when it cannot be analyzed (yet), and is simply left without a score.
+    pub fn analyze_recipes(&self, recipe_ids: Vec<Uuid>) {
+        if recipe_ids.is_empty() {
+            return;
+        }
+        let client = self.client.clone();
+        let base_url = self.nutrition_service_url.clone();
+        tokio::spawn(async move {
+            for recipe_id in recipe_ids {
+                let url = format!("{}/analyze/recipe/{}", base_url, recipe_id);
+                if let Err(e) = client.post(&url).send().await.and_then(|r| r.error_for_status()) {
+                    tracing::warn!("Nutrition analysis of recipe {} failed: {}", recipe_id, e);
+                }
+            }
+        });
     }
 }
@@ -0,0 +1,238 @@
+// Login sessions: one `user_sessions` row per login, shared id with its refresh-token family
+
+use anyhow::Result;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, Extensions, HeaderMap},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::auth::{self, ACCESS_TOKEN_TTL_MINUTES, REFRESH_TOKEN_TTL_DAYS};
+use crate::AppState;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SessionInfo {
+    pub id: Uuid,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub last_accessed: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Where a login came from, as far as the gateway can tell.
+#[derive(Debug, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Number of reverse proxies in front of the gateway (`TRUSTED_PROXY_HOPS`, default 0).
+/// Each one appends its peer to `X-Forwarded-For`, so only that many entries from the
+/// right can be trusted; anything further left is whatever the client chose to send.
+fn trusted_proxy_hops() -> usize {
+    static HOPS: OnceLock<usize> = OnceLock::new();
+    *HOPS.get_or_init(|| {
+        std::env::var("TRUSTED_PROXY_HOPS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(0)
+    })
+}
+
+/// Picks the client address: the entry our outermost trusted proxy appended, or the TCP
+/// peer when there are no trusted proxies or the header is shorter than the proxy chain.
+fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trusted_hops: usize) -> Option<IpAddr> {
+    if trusted_hops == 0 {
+        return peer;
+    }
+
+    let forwarded: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .collect();
+
+    forwarded
+        .len()
+        .checked_sub(trusted_hops)
+        .and_then(|i| forwarded[i].parse().ok())
+        .or(peer)
+}
+
+impl ClientInfo {
+    pub fn from_parts(headers: &HeaderMap, peer: Option<SocketAddr>) -> Self {
+        let ip_address = client_ip(headers, peer.map(|p| p.ip()), trusted_proxy_hops()).map(|ip| ip.to_string());
+
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.chars().take(512).collect());
+
+        Self { ip_address, user_agent }
+    }
+
+    /// Reads the peer address that `into_make_service_with_connect_info` stores on each request.
+    pub fn from_request(headers: &HeaderMap, extensions: &Extensions) -> Self {
+        let peer = extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| *addr);
+        Self::from_parts(headers, peer)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_request(&parts.headers, &parts.extensions))
+    }
+}
+
+fn revoked_key(session_id: Uuid) -> String {
+    format!("session:revoked:{}", session_id)
+}
+
+pub async fn create_session(
+    db: &PgPool,
+    session_id: Uuid,
+    user_id: Uuid,
+    refresh_token: &str,
+    client: &ClientInfo,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO user_sessions (id, user_id, session_token, ip_address, user_agent, expires_at) VALUES ($1, $2, $3, $4::inet, $5, $6)",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .bind(auth::hash_token(refresh_token))
+    .bind(&client.ip_address)
+    .bind(&client.user_agent)
+    .bind(Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS))
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn list_active_sessions(db: &PgPool, user_id: Uuid) -> Result<Vec<SessionInfo>> {
+    let sessions = sqlx::query_as::<_, SessionInfo>(
+        "SELECT id, host(ip_address) AS ip_address, user_agent, created_at, last_accessed, expires_at \
+         FROM user_sessions \
+         WHERE user_id = $1 AND is_active IS NOT FALSE AND expires_at > NOW() \
+         ORDER BY last_accessed DESC NULLS LAST",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    Ok(sessions)
+}
+
+/// Revokes a session and its refresh tokens, and flags it in Redis so access tokens
+/// already handed out for it stop working before they expire.
+///
+/// Returns `false` when the session does not belong to `user_id`.
+pub async fn revoke_session(state: &AppState, user_id: Uuid, session_id: Uuid) -> Result<bool> {
+    let mut tx = state.db.begin().await?;
+
+    let updated = sqlx::query("UPDATE user_sessions SET is_active = FALSE WHERE id = $1 AND user_id = $2")
+        .bind(session_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if updated.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query("UPDATE refresh_tokens SET is_revoked = TRUE, revoked_at = COALESCE(revoked_at, NOW()) WHERE family_id = $1")
+        .bind(session_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Flag the session in Redis before committing: if Redis is down the revocation rolls
+    // back and the caller sees the error, instead of a DB-revoked session whose access
+    // tokens keep passing the marker check. Access tokens live at most
+    // ACCESS_TOKEN_TTL_MINUTES, so the marker can expire with them.
+    let mut conn = state.redis.get_async_connection().await?;
+    redis::cmd("SET")
+        .arg(revoked_key(session_id))
+        .arg(1)
+        .arg("EX")
+        .arg(ACCESS_TOKEN_TTL_MINUTES * 60)
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Ends every active session of a user, e.g. when an admin deactivates the account.
+pub async fn revoke_all_sessions(state: &AppState, user_id: Uuid) -> Result<()> {
+    let session_ids: Vec<(Uuid,)> = sqlx::query_as("SELECT id FROM user_sessions WHERE user_id = $1 AND is_active IS NOT FALSE")
+        .bind(user_id)
+        .fetch_all(&state.db)
+        .await?;
+
+    for (session_id,) in session_ids {
+        revoke_session(state, user_id, session_id).await?;
+    }
+    Ok(())
+}
+
+/// Checked on every authenticated request. Redis is the fast path; if it is unavailable
+/// we fall back to the session row rather than letting revoked sessions through.
+pub async fn is_session_revoked(state: &AppState, session_id: Uuid) -> bool {
+    let cached = async {
+        let mut conn = state.redis.get_async_connection().await?;
+        redis::cmd("EXISTS")
+            .arg(revoked_key(session_id))
+            .query_async::<_, bool>(&mut conn)
+            .await
+    }
+    .await;
+
+    match cached {
+        Ok(revoked) => revoked,
+        Err(e) => {
+            tracing::warn!("Redis unavailable for session check, falling back to database: {}", e);
+            let active: Option<(Option<bool>,)> = sqlx::query_as("SELECT is_active FROM user_sessions WHERE id = $1")
+                .bind(session_id)
+                .fetch_optional(&state.db)
+                .await
+                .unwrap_or(None);
+            !matches!(active, Some((Some(true),)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn client_ip_ignores_entries_left_of_the_trusted_proxies() {
+        let peer = Some("10.0.0.2".parse().unwrap());
+        let headers = forwarded("6.6.6.6, 203.0.113.7");
+
+        assert_eq!(client_ip(&headers, peer, 0), peer);
+        assert_eq!(client_ip(&headers, peer, 1), Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(client_ip(&headers, peer, 2), Some("6.6.6.6".parse().unwrap()));
+        assert_eq!(client_ip(&headers, peer, 3), peer);
+        assert_eq!(client_ip(&HeaderMap::new(), peer, 1), peer);
+    }
+}
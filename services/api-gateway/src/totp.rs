@@ -0,0 +1,156 @@
+// Time-based one-time passwords (RFC 6238) and recovery codes for two-factor login
+
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore};
+use sha1::Sha1;
+
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Codes from one step before or after the current one are accepted to absorb clock drift.
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+const ISSUER: &str = "Meal Prep Pro";
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// 160-bit secret, base32-encoded as authenticator apps expect.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// `otpauth://` URI for rendering as a QR code during enrollment.
+pub fn otpauth_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        issuer = urlencode(ISSUER),
+        account = urlencode(account),
+    )
+}
+
+fn urlencode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation, RFC 4226 section 5.3
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]]) & 0x7fff_ffff;
+    binary % 10u32.pow(DIGITS)
+}
+
+/// Verifies `code` against `secret` at `unix_time` and returns the matched time step.
+///
+/// Callers store the step and reject codes at or before it, so a code cannot be replayed.
+pub fn verify_code(secret: &str, code: &str, unix_time: i64, last_used_step: Option<i64>) -> Option<i64> {
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let current = unix_time / STEP_SECONDS;
+
+    (current - ALLOWED_DRIFT_STEPS..=current + ALLOWED_DRIFT_STEPS)
+        .filter(|step| *step >= 0 && last_used_step.is_none_or(|last| *step > last))
+        .find(|step| hotp(&key, *step as u64) == code)
+}
+
+/// Recovery codes look like `k7dq-m2xp`; only their SHA-256 is stored.
+pub fn generate_recovery_codes() -> Vec<String> {
+    const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..8).map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char).collect();
+            format!("{}-{}", &chars[..4], &chars[4..])
+        })
+        .collect()
+}
+
+pub fn normalize_recovery_code(code: &str) -> String {
+    let compact: String = code.trim().to_lowercase().chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+    if compact.len() == 8 {
+        format!("{}-{}", &compact[..4], &compact[4..])
+    } else {
+        compact
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA1 seed from RFC 6238 Appendix B, base32-encoded.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    /// RFC 6238 Appendix B SHA1 vectors: unix time and the 8-digit TOTP. We issue 6 digits,
+    /// which are the low 6 digits of the same truncated value.
+    const RFC_VECTORS: [(i64, &str); 6] = [
+        (59, "94287082"),
+        (1_111_111_109, "07081804"),
+        (1_111_111_111, "14050471"),
+        (1_234_567_890, "89005924"),
+        (2_000_000_000, "69279037"),
+        (20_000_000_000, "65353130"),
+    ];
+
+    #[test]
+    fn hotp_matches_the_rfc_vectors() {
+        let key = BASE32_NOPAD.decode(RFC_SECRET.as_bytes()).unwrap();
+        assert_eq!(key, b"12345678901234567890");
+        for (time, expected) in RFC_VECTORS {
+            let code = format!("{:06}", hotp(&key, (time / STEP_SECONDS) as u64));
+            assert_eq!(code, expected[2..], "T = {}", time);
+        }
+    }
+
+    #[test]
+    fn verify_code_accepts_the_rfc_vectors() {
+        for (time, expected) in RFC_VECTORS {
+            assert_eq!(verify_code(RFC_SECRET, &expected[2..], time, None), Some(time / STEP_SECONDS));
+        }
+        assert_eq!(verify_code(RFC_SECRET, "287 082", 59, None), Some(1));
+        assert_eq!(verify_code(RFC_SECRET, "94287082", 59, None), None);
+        assert_eq!(verify_code(RFC_SECRET, "28708a", 59, None), None);
+    }
+
+    #[test]
+    fn verify_code_allows_one_step_of_drift() {
+        // "279037" belongs to step 66666666 (T = 2000000000)
+        let step = 2_000_000_000 / STEP_SECONDS;
+        assert_eq!(verify_code(RFC_SECRET, "279037", (step - 1) * STEP_SECONDS, None), Some(step));
+        assert_eq!(verify_code(RFC_SECRET, "279037", (step + 1) * STEP_SECONDS + 29, None), Some(step));
+        assert_eq!(verify_code(RFC_SECRET, "279037", (step - 2) * STEP_SECONDS, None), None);
+        assert_eq!(verify_code(RFC_SECRET, "279037", (step + 2) * STEP_SECONDS, None), None);
+    }
+
+    #[test]
+    fn verify_code_rejects_used_steps() {
+        let step = 2_000_000_000 / STEP_SECONDS;
+        let now = step * STEP_SECONDS;
+        assert_eq!(verify_code(RFC_SECRET, "279037", now, Some(step - 1)), Some(step));
+        assert_eq!(verify_code(RFC_SECRET, "279037", now, Some(step)), None);
+        assert_eq!(verify_code(RFC_SECRET, "279037", now + STEP_SECONDS, Some(step + 1)), None);
+    }
+
+    #[test]
+    fn recovery_codes_normalize_to_the_issued_form() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            assert_eq!(normalize_recovery_code(&code.to_uppercase().replace('-', " ")), *code);
+        }
+    }
+}
@@ -2,6 +2,11 @@ use anyhow::Result;
 use uuid::Uuid;
 use crate::models::{NutritionAnalysisInternal, DailyNutritionAnalysisInternal, HealthInsights, DailyInsights};
 
+/// Nutri-Score points (of 10) at which a negative component is called out.
+const HIGH_PENALTY_POINTS: i32 = 7;
+/// FDA "good source": at least 10% of the Daily Value per serving, taken here per 100 kcal.
+const GOOD_SOURCE_PERCENT: f32 = 10.0;
+
 pub struct NutritionAI {
     // AI engine implementation
 }
@@ -11,14 +16,47 @@ impl NutritionAI {
         Ok(Self {})
     }
 
+    /// Scores the meal by its `quality` and explains the components that pull it down or up.
     pub async fn generate_health_insights(
         &self,
-        _analysis: &NutritionAnalysisInternal,
+        analysis: &NutritionAnalysisInternal,
         _user_id: Uuid,
     ) -> Result<HealthInsights> {
+        let quality = &analysis.quality;
+        let mut insights = vec![format!(
+            "Nutri-Score {} ({} points)",
+            quality.nutri_score.grade, quality.nutri_score.score
+        )];
+
+        for component in &quality.nutri_score.negative {
+            if component.points >= HIGH_PENALTY_POINTS {
+                insights.push(format!(
+                    "High in {}: {:.1} {} per 100 g ({} of {} penalty points)",
+                    component.name.replace('_', " "),
+                    component.value_per_100g,
+                    component.unit,
+                    component.points,
+                    component.max_points
+                ));
+            }
+        }
+        for component in &quality.nutri_score.positive {
+            if component.points == 0 {
+                insights.push(format!("Little {}", component.name.replace('_', " ")));
+            }
+        }
+        if let Some(nrf) = &quality.nutrient_rich_foods {
+            for nutrient in nrf.encouraged.iter().filter(|n| n.percent_of_reference >= GOOD_SOURCE_PERCENT) {
+                insights.push(format!(
+                    "Good source of {} ({:.0}% of the Daily Value per 100 kcal)",
+                    nutrient.nutrient, nutrient.percent_of_reference
+                ));
+            }
+        }
+
         Ok(HealthInsights {
-            overall_score: 85.0,
-            insights: vec!["Good protein intake".to_string()],
+            overall_score: quality.nutrition_score,
+            insights,
         })
     }
 
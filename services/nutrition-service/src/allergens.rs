@@ -0,0 +1,462 @@
+// Allergen detection from ingredient names and food descriptions, and checks against a
+// user's allergy list
+//
+// Categories are the major food allergens (US top 9 plus the other EU-declarable ones).
+// Detection is word based and knows the derived ingredients that carry an allergen under
+// another name ("whey" -> milk, "soy lecithin" -> soy, "tahini" -> sesame).
+
+use crate::models::{AllergenSafety, AllergyConflict};
+use crate::MealIngredient;
+
+struct Allergen {
+    name: &'static str,
+    /// Words and phrases that mean the allergen is present; plurals match too.
+    terms: &'static [&'static str],
+    /// Phrases that contain a term without the allergen ("peanut butter" is not milk).
+    exclusions: &'static [&'static str],
+    /// Phrases anywhere in the text that rule the allergen out ("gluten free bread").
+    free_of: &'static [&'static str],
+}
+
+const ALLERGENS: &[Allergen] = &[
+    Allergen {
+        name: "milk",
+        terms: &[
+            "milk", "cream", "butter", "buttermilk", "cheese", "yogurt", "yoghurt", "whey", "casein", "caseinate",
+            "lactose", "lactalbumin", "ghee", "kefir", "curd", "ricotta", "mozzarella", "parmesan", "cheddar",
+            "brie", "feta", "paneer", "custard", "mascarpone", "half and half",
+        ],
+        exclusions: &[
+            "coconut milk", "almond milk", "soy milk", "oat milk", "rice milk", "cashew milk", "peanut butter",
+            "almond butter", "cashew butter", "nut butter", "cocoa butter", "shea butter", "apple butter",
+            "butter bean", "cream of tartar", "coconut cream", "coconut yogurt", "soy yogurt", "bean curd",
+        ],
+        free_of: &["dairy free", "milk free", "vegan"],
+    },
+    Allergen {
+        name: "egg",
+        terms: &["egg", "albumin", "albumen", "ovalbumin", "lysozyme", "mayonnaise", "mayo", "meringue", "eggnog"],
+        exclusions: &[],
+        free_of: &["egg free", "eggless", "vegan"],
+    },
+    Allergen {
+        name: "fish",
+        terms: &[
+            "fish", "finfish", "salmon", "tuna", "cod", "anchovy", "sardine", "trout", "haddock", "halibut",
+            "mackerel", "tilapia", "pollock", "bass", "snapper", "catfish", "herring", "swordfish", "flounder",
+            "perch", "pike", "carp", "eel", "caviar", "roe", "worcestershire",
+        ],
+        exclusions: &[],
+        free_of: &["vegan"],
+    },
+    Allergen {
+        name: "crustacean_shellfish",
+        terms: &[
+            "crustacean", "shrimp", "prawn", "crab", "lobster", "crayfish", "crawfish", "langoustine", "krill",
+            "scampi",
+        ],
+        exclusions: &["crab apple"],
+        free_of: &["vegan"],
+    },
+    Allergen {
+        name: "mollusc",
+        terms: &[
+            "mollusk", "mollusc", "clam", "mussel", "oyster", "scallop", "squid", "calamari", "octopus", "snail",
+            "escargot", "abalone", "cuttlefish", "whelk",
+        ],
+        exclusions: &["oyster mushroom"],
+        free_of: &["vegan"],
+    },
+    Allergen {
+        name: "tree_nuts",
+        terms: &[
+            "nut", "almond", "walnut", "pecan", "cashew", "pistachio", "hazelnut", "filbert", "macadamia",
+            "brazil nut", "pine nut", "chestnut", "praline", "marzipan", "gianduja", "nutella", "pesto",
+        ],
+        exclusions: &["water chestnut", "tiger nut"],
+        free_of: &["nut free", "tree nut free"],
+    },
+    Allergen {
+        name: "peanut",
+        terms: &["peanut", "groundnut", "arachis", "monkey nut"],
+        exclusions: &[],
+        free_of: &["peanut free"],
+    },
+    Allergen {
+        name: "wheat",
+        terms: &[
+            "wheat", "flour", "semolina", "durum", "spelt", "farro", "couscous", "bulgur", "seitan", "bread",
+            "breadcrumb", "panko", "pasta", "noodle", "spaghetti", "macaroni", "udon", "ramen", "cracker",
+            "biscuit", "cookie", "cake", "pastry", "croissant", "bagel", "pita", "naan", "tortilla", "soy sauce",
+        ],
+        exclusions: &[
+            "buckwheat", "rice flour", "almond flour", "coconut flour", "corn flour", "chickpea flour",
+            "buckwheat flour", "oat flour", "potato flour", "soy flour", "tapioca flour", "rice noodle", "rice pasta",
+            "corn tortilla", "rice cake", "rice cracker",
+        ],
+        free_of: &["gluten free", "wheat free"],
+    },
+    Allergen {
+        name: "gluten",
+        terms: &["gluten", "barley", "rye", "malt", "triticale", "beer", "ale", "lager"],
+        exclusions: &[],
+        free_of: &["gluten free"],
+    },
+    Allergen {
+        name: "soy",
+        terms: &[
+            "soy", "soya", "soybean", "tofu", "tempeh", "edamame", "miso", "lecithin", "tamari", "natto", "shoyu",
+            "tvp", "textured vegetable protein",
+        ],
+        exclusions: &["sunflower lecithin"],
+        free_of: &["soy free"],
+    },
+    Allergen {
+        name: "sesame",
+        terms: &["sesame", "tahini", "benne", "gomasio", "halva", "halvah", "hummus", "zaatar"],
+        exclusions: &[],
+        free_of: &["sesame free"],
+    },
+    Allergen {
+        name: "mustard",
+        terms: &["mustard"],
+        exclusions: &[],
+        free_of: &[],
+    },
+    Allergen {
+        name: "celery",
+        terms: &["celery", "celeriac"],
+        exclusions: &[],
+        free_of: &[],
+    },
+    Allergen {
+        name: "lupin",
+        terms: &["lupin", "lupine", "lupini"],
+        exclusions: &[],
+        free_of: &[],
+    },
+    Allergen {
+        name: "sulphites",
+        terms: &["sulfite", "sulphite", "metabisulfite", "sulfur dioxide", "sulphur dioxide", "wine"],
+        exclusions: &[],
+        free_of: &[],
+    },
+];
+
+/// What people write in their allergy list, mapped to the categories it rules out.
+const ALLERGY_ALIASES: &[(&str, &[&str])] = &[
+    ("milk", &["milk"]),
+    ("dairy", &["milk"]),
+    ("lactose", &["milk"]),
+    ("casein", &["milk"]),
+    ("whey", &["milk"]),
+    ("egg", &["egg"]),
+    ("fish", &["fish"]),
+    ("finfish", &["fish"]),
+    ("shellfish", &["crustacean_shellfish", "mollusc"]),
+    ("crustacean", &["crustacean_shellfish"]),
+    ("crustacean shellfish", &["crustacean_shellfish"]),
+    ("mollusc", &["mollusc"]),
+    ("mollusk", &["mollusc"]),
+    ("seafood", &["fish", "crustacean_shellfish", "mollusc"]),
+    ("nut", &["tree_nuts", "peanut"]),
+    ("tree nut", &["tree_nuts"]),
+    ("peanut", &["peanut"]),
+    ("groundnut", &["peanut"]),
+    ("wheat", &["wheat"]),
+    ("gluten", &["gluten", "wheat"]),
+    ("celiac", &["gluten", "wheat"]),
+    ("coeliac", &["gluten", "wheat"]),
+    ("soy", &["soy"]),
+    ("soya", &["soy"]),
+    ("soybean", &["soy"]),
+    ("sesame", &["sesame"]),
+    ("mustard", &["mustard"]),
+    ("celery", &["celery"]),
+    ("lupin", &["lupin"]),
+    ("sulphite", &["sulphites"]),
+    ("sulfite", &["sulphites"]),
+];
+
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// `word` is `term` or one of its plurals.
+fn same_word(word: &str, term: &str) -> bool {
+    word == term
+        || word.strip_suffix('s') == Some(term)
+        || word.strip_suffix("es") == Some(term)
+        || (term.ends_with('y') && word.strip_suffix("ies") == term.strip_suffix('y'))
+}
+
+/// Start positions where `phrase` occurs in `text`.
+fn phrase_positions(text: &[String], phrase: &str) -> Vec<usize> {
+    let phrase: Vec<&str> = phrase.split_whitespace().collect();
+    if phrase.is_empty() || phrase.len() > text.len() {
+        return Vec::new();
+    }
+    (0..=text.len() - phrase.len())
+        .filter(|&start| phrase.iter().enumerate().all(|(i, term)| same_word(&text[start + i], term)))
+        .collect()
+}
+
+fn contains_phrase(text: &[String], phrase: &str) -> bool {
+    !phrase_positions(text, phrase).is_empty()
+}
+
+fn present(allergen: &Allergen, text: &[String]) -> bool {
+    if allergen.free_of.iter().any(|phrase| contains_phrase(text, phrase)) {
+        return false;
+    }
+
+    let mut excluded = vec![false; text.len()];
+    for exclusion in allergen.exclusions {
+        let length = exclusion.split_whitespace().count();
+        for start in phrase_positions(text, exclusion) {
+            excluded[start..start + length].iter_mut().for_each(|e| *e = true);
+        }
+    }
+
+    allergen.terms.iter().any(|term| {
+        let length = term.split_whitespace().count();
+        phrase_positions(text, term)
+            .into_iter()
+            .any(|start| !excluded[start..start + length].iter().any(|e| *e))
+    })
+}
+
+/// Whether `text` contains `phrase` as whole words, plurals included.
+pub fn mentions(text: &str, phrase: &str) -> bool {
+    contains_phrase(&words(text), &phrase.to_lowercase())
+}
+
+pub fn is_category(name: &str) -> bool {
+    ALLERGENS.iter().any(|a| a.name == name)
+}
+
+fn ruled_out(allergen: &str, text: &[String]) -> bool {
+    ALLERGENS
+        .iter()
+        .filter(|a| a.name == allergen)
+        .any(|a| a.free_of.iter().any(|phrase| contains_phrase(text, phrase)))
+}
+
+/// Allergen categories found in `text` (an ingredient name and/or an FDC description).
+pub fn detect(text: &str) -> Vec<&'static str> {
+    let text = words(text);
+    let mut found: Vec<&'static str> = ALLERGENS.iter().filter(|a| present(a, &text)).map(|a| a.name).collect();
+    // Wheat always carries gluten
+    if found.contains(&"wheat") && !found.contains(&"gluten") {
+        found.push("gluten");
+    }
+    found
+}
+
+/// Categories an entry of a user's allergy list rules out, from every alias it mentions
+/// ("sesame seeds", "cow's milk", "dairy products"); empty when it names no major allergen
+/// ("strawberries"), which is then matched literally. A longer alias claims its words, so
+/// "tree nuts" is not also "nut" (which would add peanut).
+pub fn categories_for_allergy(allergy: &str) -> Vec<&'static str> {
+    let text: Vec<String> = words(allergy)
+        .into_iter()
+        .filter(|w| !matches!(w.as_str(), "allergy" | "allergies" | "intolerance" | "intolerant" | "free"))
+        .collect();
+
+    let mut aliases: Vec<&(&str, &[&str])> = ALLERGY_ALIASES.iter().collect();
+    aliases.sort_by_key(|(alias, _)| std::cmp::Reverse(alias.split_whitespace().count()));
+
+    let mut claimed = vec![false; text.len()];
+    let mut categories = Vec::new();
+    for (alias, alias_categories) in aliases {
+        let length = alias.split_whitespace().count();
+        for start in phrase_positions(&text, alias) {
+            if claimed[start..start + length].iter().any(|c| *c) {
+                continue;
+            }
+            claimed[start..start + length].iter_mut().for_each(|c| *c = true);
+            for category in alias_categories.iter() {
+                if !categories.contains(category) {
+                    categories.push(*category);
+                }
+            }
+        }
+    }
+    categories
+}
+
+/// Singular form of each word, so a literal allergy ("strawberries") finds "strawberry".
+fn singular_words(text: &str) -> String {
+    words(text)
+        .into_iter()
+        .map(|w| match w.strip_suffix("ies") {
+            Some(stem) => format!("{}y", stem),
+            None if w.len() > 3 && w.ends_with('s') && !w.ends_with("ss") => w[..w.len() - 1].to_string(),
+            None => w,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// What was detected for one ingredient of a meal.
+pub struct IngredientAllergens {
+    pub ingredient: String,
+    /// Ingredient name plus the matched food's description, for literal allergy matches
+    text: String,
+    pub allergens: Vec<&'static str>,
+}
+
+impl IngredientAllergens {
+    /// Looks at both the name as written and the FDC description it matched, which names
+    /// the base food when the ingredient is a brand or a dish.
+    pub fn new(ingredient: &str, description: Option<&str>) -> Self {
+        let text = match description {
+            Some(description) => format!("{} {}", ingredient, description),
+            None => ingredient.to_string(),
+        };
+        let name = words(ingredient);
+        let mut allergens = detect(ingredient);
+        // The name can rule out an allergen its closest FDC food has ("vegan cheese")
+        for allergen in description.map(detect).unwrap_or_default() {
+            if !allergens.contains(&allergen) && !ruled_out(allergen, &name) {
+                allergens.push(allergen);
+            }
+        }
+        Self {
+            ingredient: ingredient.to_string(),
+            text,
+            allergens,
+        }
+    }
+}
+
+/// Checks a meal against a user's allergy list. Any conflict makes the meal unsafe.
+pub fn check(allergies: &[String], ingredients: &[IngredientAllergens]) -> AllergenSafety {
+    let mut conflicts = Vec::new();
+
+    for allergy in allergies.iter().filter(|a| !a.trim().is_empty()) {
+        let categories = categories_for_allergy(allergy);
+        for ingredient in ingredients {
+            if categories.is_empty() {
+                let literal = singular_words(allergy);
+                if contains_phrase(&words(&ingredient.text), &literal) {
+                    conflicts.push(AllergyConflict {
+                        allergy: allergy.clone(),
+                        ingredient: ingredient.ingredient.clone(),
+                        allergen: None,
+                    });
+                }
+                continue;
+            }
+            if let Some(allergen) = ingredient.allergens.iter().find(|a| categories.contains(a)) {
+                conflicts.push(AllergyConflict {
+                    allergy: allergy.clone(),
+                    ingredient: ingredient.ingredient.clone(),
+                    allergen: Some(allergen.to_string()),
+                });
+            }
+        }
+    }
+
+    AllergenSafety {
+        safe: conflicts.is_empty(),
+        checked_allergies: allergies.to_vec(),
+        conflicts,
+    }
+}
+
+/// Checks ingredients by name alone, for meals that have not been analysed.
+pub fn check_ingredients(allergies: &[String], ingredients: &[MealIngredient]) -> AllergenSafety {
+    let detected: Vec<IngredientAllergens> = ingredients.iter().map(|i| IngredientAllergens::new(&i.name, None)).collect();
+    check(allergies, &detected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allergies(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn detects_derived_ingredients() {
+        assert_eq!(detect("whey protein isolate"), vec!["milk"]);
+        assert_eq!(detect("ghee"), vec!["milk"]);
+        assert_eq!(detect("soy lecithin"), vec!["soy"]);
+        assert_eq!(detect("tahini"), vec!["sesame"]);
+        assert_eq!(detect("whole wheat flour"), vec!["wheat", "gluten"]);
+    }
+
+    #[test]
+    fn exclusions_are_not_the_allergen() {
+        assert_eq!(detect("peanut butter"), vec!["peanut"]);
+        assert!(detect("coconut milk").is_empty());
+        assert!(detect("sunflower lecithin").is_empty());
+        assert_eq!(detect("coconut milk and butter"), vec!["milk"]);
+    }
+
+    #[test]
+    fn free_of_rules_an_allergen_out() {
+        assert!(detect("vegan cheese").is_empty());
+        assert!(detect("gluten free bread").is_empty());
+
+        // Also when the matched food has it
+        let cheese = IngredientAllergens::new("vegan cheese", Some("Cheese, cheddar"));
+        assert!(cheese.allergens.is_empty());
+        let cheese = IngredientAllergens::new("cheese", Some("Cheese, cheddar"));
+        assert_eq!(cheese.allergens, vec!["milk"]);
+    }
+
+    #[test]
+    fn reads_allergies_that_mention_an_alias() {
+        assert_eq!(categories_for_allergy("Dairy"), vec!["milk"]);
+        assert_eq!(categories_for_allergy("sesame seeds"), vec!["sesame"]);
+        assert_eq!(categories_for_allergy("cow's milk"), vec!["milk"]);
+        assert_eq!(categories_for_allergy("dairy products"), vec!["milk"]);
+        assert_eq!(categories_for_allergy("milk protein"), vec!["milk"]);
+        assert_eq!(categories_for_allergy("peanuts"), vec!["peanut"]);
+        assert_eq!(categories_for_allergy("tree nuts"), vec!["tree_nuts"]);
+        assert_eq!(categories_for_allergy("nuts"), vec!["tree_nuts", "peanut"]);
+        assert_eq!(categories_for_allergy("gluten intolerance"), vec!["gluten", "wheat"]);
+        assert!(categories_for_allergy("strawberries").is_empty());
+    }
+
+    #[test]
+    fn checks_aliased_plural_and_literal_allergies() {
+        let meal = [
+            IngredientAllergens::new("tahini", None),
+            IngredientAllergens::new("whey", None),
+            IngredientAllergens::new("roasted peanuts", None),
+            IngredientAllergens::new("strawberry jam", None),
+            IngredientAllergens::new("coconut milk", None),
+        ];
+
+        let result = check(&allergies(&["sesame seeds", "dairy products"]), &meal);
+        assert!(!result.safe);
+        let conflicts: Vec<_> = result
+            .conflicts
+            .iter()
+            .map(|c| (c.allergy.as_str(), c.ingredient.as_str(), c.allergen.as_deref()))
+            .collect();
+        assert_eq!(
+            conflicts,
+            vec![("sesame seeds", "tahini", Some("sesame")), ("dairy products", "whey", Some("milk"))]
+        );
+
+        let result = check(&allergies(&["Peanuts"]), &meal);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].ingredient, "roasted peanuts");
+
+        let result = check(&allergies(&["strawberries"]), &meal);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].ingredient, "strawberry jam");
+        assert_eq!(result.conflicts[0].allergen, None);
+
+        assert!(check(&allergies(&["kiwi", " "]), &meal).safe);
+    }
+}
@@ -0,0 +1,248 @@
+// Cooking adjustments: nutrient retention factors and raw-to-cooked weight yields per food group
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+const BUNDLED_FACTORS: &str = include_str!("../data/cooking_factors.json");
+
+/// Words in an FDC description meaning its values are already for the cooked food.
+const COOKED_WORDS: &[&str] = &[
+    "cooked", "baked", "boiled", "braised", "broiled", "fried", "grilled", "microwaved", "poached", "roasted",
+    "simmered", "steamed", "stewed", "toasted",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CookingMethod {
+    Raw,
+    Baked,
+    Boiled,
+    Fried,
+    Grilled,
+    Microwaved,
+    PressureCooked,
+    Roasted,
+    Sauteed,
+    Steamed,
+}
+
+impl CookingMethod {
+    pub fn parse(value: &str) -> Option<Self> {
+        let normalized = value.trim().to_lowercase().replace(['-', '_'], " ").replace('é', "e");
+        let method = match normalized.as_str() {
+            "" | "raw" | "none" | "uncooked" => CookingMethod::Raw,
+            "baked" | "bake" => CookingMethod::Baked,
+            "boiled" | "boil" | "simmered" | "poached" | "blanched" => CookingMethod::Boiled,
+            "fried" | "fry" | "deep fried" | "stir fried" | "pan fried" => CookingMethod::Fried,
+            "grilled" | "grill" | "broiled" | "barbecued" | "bbq" => CookingMethod::Grilled,
+            "microwaved" | "microwave" => CookingMethod::Microwaved,
+            "pressure cooked" | "pressure cooker" | "instant pot" => CookingMethod::PressureCooked,
+            "roasted" | "roast" => CookingMethod::Roasted,
+            "sauteed" | "saute" => CookingMethod::Sauteed,
+            "steamed" | "steam" => CookingMethod::Steamed,
+            _ => return None,
+        };
+        Some(method)
+    }
+
+    /// Key of the method in `data/cooking_factors.json`.
+    fn key(&self) -> &'static str {
+        match self {
+            CookingMethod::Raw => "raw",
+            CookingMethod::Baked => "baked",
+            CookingMethod::Boiled => "boiled",
+            CookingMethod::Fried => "fried",
+            CookingMethod::Grilled => "grilled",
+            CookingMethod::Microwaved => "microwaved",
+            CookingMethod::PressureCooked => "pressure_cooked",
+            CookingMethod::Roasted => "roasted",
+            CookingMethod::Sauteed => "sauteed",
+            CookingMethod::Steamed => "steamed",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownCookingMethod(pub String);
+
+impl fmt::Display for UnknownCookingMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown cooking method {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownCookingMethod {}
+
+/// A cooked weight was given for an ingredient whose cooking method is unknown, so it
+/// cannot be converted back to the raw weight FDC values are given for.
+#[derive(Debug)]
+pub struct CookedWeightWithoutMethod(pub String);
+
+impl fmt::Display for CookedWeightWithoutMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is given as a cooked weight but no cooking method is known", self.0)
+    }
+}
+
+impl std::error::Error for CookedWeightWithoutMethod {}
+
+/// Effect of one cooking method on one food group.
+#[derive(Debug, Deserialize)]
+pub struct MethodFactors {
+    /// Cooked weight / raw weight
+    #[serde(rename = "yield")]
+    pub weight_yield: f32,
+    /// Share of each nutrient left after cooking, by nutrient name or macronutrient
+    /// (`protein`, `fat`, `carbohydrates`, `sugar`, `fiber`); `vitamins` and `minerals`
+    /// cover micronutrients without their own entry. Anything else is fully retained.
+    retention: HashMap<String, f32>,
+}
+
+impl MethodFactors {
+    /// Raw weight of food that weighs `cooked` after cooking, in the same unit.
+    pub fn raw_weight(&self, cooked: f32) -> f32 {
+        cooked / self.weight_yield
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CookingTable {
+    fallback_group: String,
+    /// FDC food category -> food group
+    groups: HashMap<String, String>,
+    minerals: Vec<String>,
+    /// Food group -> cooking method -> factors
+    factors: HashMap<String, HashMap<String, MethodFactors>>,
+}
+
+impl CookingTable {
+    /// The table shipped in `data/cooking_factors.json`.
+    pub fn bundled() -> Result<Self> {
+        let table: Self = serde_json::from_str(BUNDLED_FACTORS)?;
+        if !table.factors.contains_key(&table.fallback_group) {
+            bail!("cooking factors have no entry for the fallback group {}", table.fallback_group);
+        }
+        Ok(table)
+    }
+
+    /// Factors for `method` applied to a food of `category`; `None` for raw food. Groups
+    /// without their own entry for a method use the fallback group's.
+    pub fn factors(&self, category: Option<&str>, method: CookingMethod) -> Option<&MethodFactors> {
+        if method == CookingMethod::Raw {
+            return None;
+        }
+        let group = category.and_then(|c| self.groups.get(c)).unwrap_or(&self.fallback_group);
+        self.factors
+            .get(group)
+            .and_then(|methods| methods.get(method.key()))
+            .or_else(|| self.factors.get(&self.fallback_group)?.get(method.key()))
+    }
+
+    /// Retention of a macronutrient or micronutrient under `factors`.
+    pub fn retention(&self, factors: &MethodFactors, nutrient: &str) -> f32 {
+        if let Some(retention) = factors.retention.get(nutrient) {
+            return *retention;
+        }
+        if matches!(nutrient, "protein" | "fat" | "carbohydrates" | "sugar" | "fiber") {
+            return 1.0;
+        }
+        let class = if self.minerals.iter().any(|m| m == nutrient) { "minerals" } else { "vitamins" };
+        factors.retention.get(class).copied().unwrap_or(1.0)
+    }
+}
+
+/// Whether an FDC description is for food that has already been cooked ("Rice, white, cooked").
+pub fn is_cooked_description(description: &str) -> bool {
+    description
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| COOKED_WORDS.contains(&word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> CookingTable {
+        CookingTable::bundled().unwrap()
+    }
+
+    #[test]
+    fn parses_method_names() {
+        assert_eq!(CookingMethod::parse("Stir-Fried"), Some(CookingMethod::Fried));
+        assert_eq!(CookingMethod::parse("sautéed"), Some(CookingMethod::Sauteed));
+        assert_eq!(CookingMethod::parse(" pressure_cooked "), Some(CookingMethod::PressureCooked));
+        assert_eq!(CookingMethod::parse("poached"), Some(CookingMethod::Boiled));
+        assert_eq!(CookingMethod::parse("BBQ"), Some(CookingMethod::Grilled));
+        assert_eq!(CookingMethod::parse(""), Some(CookingMethod::Raw));
+        assert_eq!(CookingMethod::parse("sous vide"), None);
+        assert_eq!(CookingMethod::parse("diced"), None);
+    }
+
+    #[test]
+    fn every_method_has_factors_for_unknown_foods() {
+        let table = table();
+        for method in [
+            CookingMethod::Baked,
+            CookingMethod::Boiled,
+            CookingMethod::Fried,
+            CookingMethod::Grilled,
+            CookingMethod::Microwaved,
+            CookingMethod::PressureCooked,
+            CookingMethod::Roasted,
+            CookingMethod::Sauteed,
+            CookingMethod::Steamed,
+        ] {
+            assert!(table.factors(None, method).is_some(), "{:?}", method);
+            assert!(table.factors(Some("Spices and Herbs"), method).is_some(), "{:?}", method);
+        }
+        assert!(table.factors(None, CookingMethod::Raw).is_none());
+        assert!(table.factors(Some("Cereal Grains and Pasta"), CookingMethod::Raw).is_none());
+    }
+
+    #[test]
+    fn groups_without_a_method_use_the_fallback_group() {
+        let table = table();
+        let boiled_grains = table.factors(Some("Cereal Grains and Pasta"), CookingMethod::Boiled).unwrap();
+        assert_eq!(boiled_grains.weight_yield, 2.6);
+
+        // Nuts have no "boiled" entry of their own
+        let boiled_nuts = table.factors(Some("Nut and Seed Products"), CookingMethod::Boiled).unwrap();
+        let boiled_other = table.factors(None, CookingMethod::Boiled).unwrap();
+        assert!(std::ptr::eq(boiled_nuts, boiled_other));
+    }
+
+    #[test]
+    fn retention_falls_back_from_nutrient_to_class() {
+        let table = table();
+        let boiled_vegetables = table.factors(Some("Vegetables and Vegetable Products"), CookingMethod::Boiled).unwrap();
+        assert_eq!(table.retention(boiled_vegetables, "Vitamin C"), 0.55);
+        assert_eq!(table.retention(boiled_vegetables, "Potassium"), 0.75);
+        assert_eq!(table.retention(boiled_vegetables, "carbohydrates"), 0.95);
+        // Macronutrients without an entry are kept; other nutrients use their class
+        assert_eq!(table.retention(boiled_vegetables, "protein"), 1.0);
+        assert_eq!(table.retention(boiled_vegetables, "Zinc"), 0.9);
+        assert_eq!(table.retention(boiled_vegetables, "Vitamin K"), 0.75);
+    }
+
+    #[test]
+    fn converts_cooked_weight_to_raw() {
+        let table = table();
+        // 260 g of boiled rice started as 100 g of dry rice
+        let boiled_grains = table.factors(Some("Cereal Grains and Pasta"), CookingMethod::Boiled).unwrap();
+        assert!((boiled_grains.raw_weight(260.0) - 100.0).abs() < 0.001);
+
+        // Roasting meat loses weight, so the raw weight is larger
+        let roasted_meat = table.factors(Some("Beef Products"), CookingMethod::Roasted).unwrap();
+        assert!(roasted_meat.weight_yield < 1.0);
+        assert!(roasted_meat.raw_weight(100.0) > 100.0);
+    }
+
+    #[test]
+    fn recognizes_cooked_descriptions() {
+        assert!(is_cooked_description("Rice, white, long-grain, regular, enriched, cooked"));
+        assert!(is_cooked_description("Chicken, broilers or fryers, breast, meat only, roasted"));
+        assert!(!is_cooked_description("Rice, white, long-grain, regular, raw, enriched"));
+        assert!(!is_cooked_description("Cookies, chocolate chip"));
+    }
+}
@@ -1,8 +1,161 @@
 // Database operations for nutrition service
 use sqlx::PgPool;
 use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use uuid::Uuid;
+
+use crate::energy_calculator::SODIUM_TARGET_MG;
+use crate::models::{ConsumerProfile, NutritionGoalCalculation};
+use crate::{BasicNutrition, MealIngredient};
 
 pub async fn initialize_database(_pool: &PgPool) -> Result<()> {
     // Database initialization logic
     Ok(())
 }
+
+/// Stores a calculated goal as the user's active `nutrition_goals` row, closing any active one.
+pub async fn insert_ai_nutrition_goal(pool: &PgPool, user_id: Uuid, calculation: &NutritionGoalCalculation) -> Result<Uuid> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "UPDATE nutrition_goals SET is_active = FALSE, end_date = CURRENT_DATE, updated_at = NOW() \
+         WHERE user_id = $1 AND is_active = TRUE",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let goals = &calculation.goals;
+    let (goal_id,): (Uuid,) = sqlx::query_as(
+        "INSERT INTO nutrition_goals (user_id, goal_type, target_calories, target_protein_g, target_carbs_g, \
+                                      target_fat_g, target_fiber_g, target_sodium_mg, start_date, is_active, created_by_ai) \
+         VALUES ($1, $2::goal_type_enum, $3, $4, $5, $6, $7, $8, CURRENT_DATE, TRUE, TRUE) \
+         RETURNING id",
+    )
+    .bind(user_id)
+    .bind(&calculation.goal_type)
+    .bind(goals.daily_calories)
+    .bind(goals.daily_protein)
+    .bind(goals.daily_carbs)
+    .bind(goals.daily_fat)
+    .bind(goals.daily_fiber)
+    .bind(SODIUM_TARGET_MG)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(goal_id)
+}
+
+/// What the user's profile says about them as a consumer: allergies and age (from
+/// `date_of_birth`). The profile has no sex column, so that is left to the request.
+pub async fn load_profile_consumer(pool: &PgPool, user_id: Uuid) -> Result<ConsumerProfile> {
+    let row: Option<(Option<Vec<String>>, Option<NaiveDate>)> =
+        sqlx::query_as("SELECT allergies, date_of_birth FROM user_profiles WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+    let (allergies, date_of_birth) = row.unwrap_or((None, None));
+
+    let age_years = date_of_birth
+        .map(|dob| (Utc::now().date_naive() - dob).num_days() as f32 / 365.25)
+        .filter(|age| *age >= 0.0);
+
+    Ok(ConsumerProfile {
+        age_years,
+        allergies: Some(allergies.unwrap_or_default()),
+        ..Default::default()
+    })
+}
+
+/// The allergies on the user's profile; empty when they have no profile or listed none.
+pub async fn load_user_allergies(pool: &PgPool, user_id: Uuid) -> Result<Vec<String>> {
+    let row: Option<(Option<Vec<String>>,)> = sqlx::query_as("SELECT allergies FROM user_profiles WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.and_then(|(allergies,)| allergies).unwrap_or_default())
+}
+
+/// A stored recipe's servings and ingredients, or `None` if there is no such recipe.
+pub async fn load_recipe_ingredients(pool: &PgPool, recipe_id: Uuid) -> Result<Option<(i32, Vec<MealIngredient>)>> {
+    let servings: Option<(i32,)> = sqlx::query_as("SELECT servings FROM recipes WHERE id = $1")
+        .bind(recipe_id)
+        .fetch_optional(pool)
+        .await?;
+    let Some((servings,)) = servings else {
+        return Ok(None);
+    };
+
+    let rows: Vec<(Uuid, String, f64, String, Option<String>)> = sqlx::query_as(
+        "SELECT id, ingredient_name, amount::float8, unit, preparation_note \
+         FROM recipe_ingredients WHERE recipe_id = $1 ORDER BY order_index",
+    )
+    .bind(recipe_id)
+    .fetch_all(pool)
+    .await?;
+
+    let ingredients = rows
+        .into_iter()
+        .map(|(ingredient_id, name, amount, unit, preparation)| MealIngredient {
+            ingredient_id,
+            name,
+            amount: amount as f32,
+            unit,
+            preparation,
+            cooked_weight: false,
+        })
+        .collect();
+    Ok(Some((servings.max(1), ingredients)))
+}
+
+/// Stores per-serving nutrition and the 0-100 `nutrition_score` for a recipe.
+pub async fn save_recipe_nutrition(pool: &PgPool, recipe_id: Uuid, per_serving: &BasicNutrition, nutrition_score: f32) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO recipe_nutrition (recipe_id, calories_per_serving, protein_g, carbohydrates_g, fat_g, fiber_g, \
+                                       sugar_g, sodium_mg, nutrition_score) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+         ON CONFLICT (recipe_id) DO UPDATE SET \
+             calories_per_serving = EXCLUDED.calories_per_serving, protein_g = EXCLUDED.protein_g, \
+             carbohydrates_g = EXCLUDED.carbohydrates_g, fat_g = EXCLUDED.fat_g, fiber_g = EXCLUDED.fiber_g, \
+             sugar_g = EXCLUDED.sugar_g, sodium_mg = EXCLUDED.sodium_mg, nutrition_score = EXCLUDED.nutrition_score",
+    )
+    .bind(recipe_id)
+    .bind(per_serving.calories)
+    .bind(per_serving.protein)
+    .bind(per_serving.carbohydrates)
+    .bind(per_serving.fat)
+    .bind(per_serving.fiber)
+    .bind(per_serving.sugar)
+    .bind(per_serving.sodium)
+    .bind(nutrition_score.clamp(0.0, 100.0))
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE recipes SET calories_per_serving = $2 WHERE id = $1")
+        .bind(recipe_id)
+        .bind(per_serving.calories)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Serving-weighted mean `nutrition_score` of the recipes a user logged in the last `days`
+/// days, with the number of logged meals that had a score.
+pub async fn recent_meal_quality(pool: &PgPool, user_id: Uuid, days: i32) -> Result<(Option<f32>, i64)> {
+    let (score, meals): (Option<f64>, i64) = sqlx::query_as(
+        "SELECT SUM(rn.nutrition_score * ml.servings)::float8 / NULLIF(SUM(ml.servings), 0)::float8, COUNT(*) \
+         FROM meal_logs ml JOIN recipe_nutrition rn ON rn.recipe_id = ml.recipe_id \
+         WHERE ml.user_id = $1 AND rn.nutrition_score IS NOT NULL \
+           AND ml.logged_at >= NOW() - make_interval(days => $2)",
+    )
+    .bind(user_id)
+    .bind(days)
+    .fetch_one(pool)
+    .await?;
+    Ok((score.map(|s| s as f32), meals))
+}
@@ -0,0 +1,478 @@
+// Diet rules: declarative per-diet rules over ingredients and nutrients, loaded from JSON files
+//
+// Each file in `data/diets/` (and in `DIET_RULES_DIR`, which adds diets or replaces bundled ones
+// of the same name) describes one diet as a list of rules:
+//
+// - `exclude`: no ingredient may match the filter (words, FDC categories or allergen categories)
+// - `not_combined`: ingredients matching `first` and `second` may not share a meal
+// - `limit`: a nutrient's `min`/`max` for the meal, as a share of its energy or per 1000 kcal
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::allergens;
+use crate::{DietCompliance, DietViolation};
+
+const BUNDLED_DIETS: &[(&str, &str)] = &[
+    ("dairy_free.json", include_str!("../data/diets/dairy_free.json")),
+    ("dash.json", include_str!("../data/diets/dash.json")),
+    ("gluten_free.json", include_str!("../data/diets/gluten_free.json")),
+    ("halal.json", include_str!("../data/diets/halal.json")),
+    ("keto.json", include_str!("../data/diets/keto.json")),
+    ("kosher.json", include_str!("../data/diets/kosher.json")),
+    ("low_fodmap.json", include_str!("../data/diets/low_fodmap.json")),
+    ("low_sodium.json", include_str!("../data/diets/low_sodium.json")),
+    ("paleo.json", include_str!("../data/diets/paleo.json")),
+    ("pescatarian.json", include_str!("../data/diets/pescatarian.json")),
+    ("renal.json", include_str!("../data/diets/renal.json")),
+    ("vegan.json", include_str!("../data/diets/vegan.json")),
+    ("vegetarian.json", include_str!("../data/diets/vegetarian.json")),
+];
+
+/// Nutrients with a fixed energy content, which `energy_share` limits can use (kcal/g).
+const ENERGY_PER_GRAM: &[(&str, f32)] = &[
+    ("protein", 4.0),
+    ("carbohydrates", 4.0),
+    ("net_carbohydrates", 4.0),
+    ("sugar", 4.0),
+    ("fat", 9.0),
+    ("saturated_fat", 9.0),
+];
+
+/// Ingredients contributing at least this share of a nutrient that is over its limit are named
+/// in the violation.
+const CONTRIBUTOR_SHARE: f32 = 0.25;
+
+#[derive(Debug, Deserialize)]
+pub struct Diet {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+enum Rule {
+    Exclude {
+        reason: String,
+        #[serde(flatten)]
+        filter: IngredientFilter,
+    },
+    NotCombined {
+        reason: String,
+        first: IngredientFilter,
+        second: IngredientFilter,
+    },
+    Limit {
+        reason: String,
+        /// `calories`, `protein`, `carbohydrates`, `net_carbohydrates`, `fat`, `saturated_fat`,
+        /// `fiber`, `sugar`, `sodium` or a micronutrient name, in the unit the analysis reports it in
+        nutrient: String,
+        #[serde(default)]
+        basis: Basis,
+        min: Option<f32>,
+        max: Option<f32>,
+    },
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Basis {
+    /// Amount in the meal
+    #[default]
+    Meal,
+    /// Fraction (0-1) of the meal's energy
+    EnergyShare,
+    /// Amount per 1000 kcal of the meal
+    #[serde(rename = "per_1000_kcal")]
+    Per1000Kcal,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct IngredientFilter {
+    /// Words or phrases in the ingredient name or its FDC description
+    #[serde(default)]
+    terms: Vec<String>,
+    /// FDC food categories
+    #[serde(default)]
+    categories: Vec<String>,
+    /// Allergen categories, with their derived ingredients
+    #[serde(default)]
+    allergens: Vec<String>,
+    /// Phrases that exempt an otherwise matching ingredient
+    #[serde(default)]
+    except: Vec<String>,
+}
+
+/// What the rules know about one ingredient of a meal.
+pub struct IngredientFacts {
+    pub ingredient: String,
+    /// FDC description and category; `None` for unmatched ingredients, checked by name only
+    pub description: Option<String>,
+    pub category: Option<String>,
+    pub allergens: Vec<&'static str>,
+    /// What the ingredient adds to the meal, by nutrient name
+    pub nutrients: HashMap<String, f32>,
+}
+
+impl IngredientFilter {
+    fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.categories.is_empty() && self.allergens.is_empty()
+    }
+
+    fn matches(&self, ingredient: &IngredientFacts) -> bool {
+        let text = match &ingredient.description {
+            Some(description) => format!("{} {}", ingredient.ingredient, description),
+            None => ingredient.ingredient.clone(),
+        };
+        if self.except.iter().any(|phrase| allergens::mentions(&text, phrase)) {
+            return false;
+        }
+        self.terms.iter().any(|term| allergens::mentions(&text, term))
+            || ingredient.category.as_ref().is_some_and(|c| self.categories.contains(c))
+            || ingredient.allergens.iter().any(|a| self.allergens.iter().any(|f| f == a))
+    }
+
+    fn matching(&self, ingredients: &[IngredientFacts]) -> Vec<String> {
+        ingredients.iter().filter(|i| self.matches(i)).map(|i| i.ingredient.clone()).collect()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.is_empty() {
+            bail!("a filter needs terms, categories or allergens");
+        }
+        if let Some(unknown) = self.allergens.iter().find(|a| !allergens::is_category(a)) {
+            bail!("unknown allergen category {}", unknown);
+        }
+        Ok(())
+    }
+}
+
+impl Rule {
+    fn validate(&self) -> Result<()> {
+        match self {
+            Rule::Exclude { filter, .. } => filter.validate(),
+            Rule::NotCombined { first, second, .. } => first.validate().and(second.validate()),
+            Rule::Limit { nutrient, basis, min, max, .. } => {
+                if min.is_none() && max.is_none() {
+                    bail!("limit on {} needs a min or a max", nutrient);
+                }
+                if matches!(basis, Basis::EnergyShare) && energy_per_gram(nutrient).is_none() {
+                    bail!("{} has no energy content for an energy_share limit", nutrient);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn evaluate(&self, ingredients: &[IngredientFacts], totals: &HashMap<String, (String, f32)>) -> Option<DietViolation> {
+        match self {
+            Rule::Exclude { reason, filter } => {
+                let offending = filter.matching(ingredients);
+                (!offending.is_empty()).then(|| DietViolation {
+                    reason: reason.clone(),
+                    ingredients: offending,
+                    detail: None,
+                })
+            }
+            Rule::NotCombined { reason, first, second } => {
+                let first = first.matching(ingredients);
+                let second = second.matching(ingredients);
+                (!first.is_empty() && !second.is_empty()).then(|| DietViolation {
+                    reason: reason.clone(),
+                    ingredients: first.into_iter().chain(second).collect(),
+                    detail: None,
+                })
+            }
+            Rule::Limit { reason, nutrient, basis, min, max } => {
+                let (unit, amount) = totals.get(nutrient).cloned().unwrap_or_default();
+                let calories = totals.get("calories").map_or(0.0, |(_, kcal)| *kcal);
+                let (value, unit) = match basis {
+                    Basis::Meal => (amount, unit),
+                    // Relative limits say nothing about a meal without energy
+                    _ if calories <= 0.0 => return None,
+                    Basis::EnergyShare => (amount * energy_per_gram(nutrient)? / calories, String::new()),
+                    Basis::Per1000Kcal => (amount / calories * 1000.0, format!("{}/1000 kcal", unit)),
+                };
+                let describe = |v: f32| match basis {
+                    Basis::EnergyShare => format!("{:.0}% of energy from {}", v * 100.0, nutrient),
+                    _ => format!("{:.1} {} {}", v, unit, nutrient),
+                };
+                let bound = |v: f32| match basis {
+                    Basis::EnergyShare => format!("{:.0}%", v * 100.0),
+                    _ => format!("{} {}", v, unit),
+                };
+
+                if let Some(max) = max.filter(|max| value > *max) {
+                    return Some(DietViolation {
+                        reason: reason.clone(),
+                        ingredients: main_contributors(ingredients, nutrient, amount),
+                        detail: Some(format!("{} (at most {})", describe(value), bound(max))),
+                    });
+                }
+                if let Some(min) = min.filter(|min| value < *min) {
+                    return Some(DietViolation {
+                        reason: reason.clone(),
+                        ingredients: Vec::new(),
+                        detail: Some(format!("{} (at least {})", describe(value), bound(min))),
+                    });
+                }
+                None
+            }
+        }
+    }
+}
+
+fn energy_per_gram(nutrient: &str) -> Option<f32> {
+    ENERGY_PER_GRAM.iter().find(|(name, _)| *name == nutrient).map(|(_, kcal)| *kcal)
+}
+
+/// Ingredients providing a large share of a nutrient, largest first.
+fn main_contributors(ingredients: &[IngredientFacts], nutrient: &str, total: f32) -> Vec<String> {
+    if total <= 0.0 {
+        return Vec::new();
+    }
+    let mut contributors: Vec<(&IngredientFacts, f32)> = ingredients
+        .iter()
+        .filter_map(|i| i.nutrients.get(nutrient).map(|amount| (i, *amount)))
+        .filter(|(_, amount)| amount / total >= CONTRIBUTOR_SHARE)
+        .collect();
+    contributors.sort_by(|a, b| b.1.total_cmp(&a.1));
+    contributors.into_iter().map(|(i, _)| i.ingredient.clone()).collect()
+}
+
+impl Diet {
+    fn parse(file: &str, contents: &str) -> Result<Self> {
+        let diet: Diet = serde_json::from_str(contents).with_context(|| format!("invalid diet rules in {}", file))?;
+        for rule in &diet.rules {
+            rule.validate().with_context(|| format!("invalid rule in {}", file))?;
+        }
+        Ok(diet)
+    }
+
+    pub fn evaluate(&self, ingredients: &[IngredientFacts], totals: &HashMap<String, (String, f32)>) -> DietCompliance {
+        let violations: Vec<DietViolation> = self.rules.iter().filter_map(|rule| rule.evaluate(ingredients, totals)).collect();
+        DietCompliance {
+            diet: self.name.clone(),
+            compliant: violations.is_empty(),
+            violations,
+        }
+    }
+}
+
+pub struct DietRules {
+    diets: Vec<Diet>,
+}
+
+impl DietRules {
+    /// The diets shipped in `data/diets/`.
+    pub fn bundled() -> Result<Self> {
+        let diets = BUNDLED_DIETS
+            .iter()
+            .map(|(file, contents)| Diet::parse(file, contents))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { diets })
+    }
+
+    /// Adds every `*.json` diet in `dir`, replacing a loaded diet with the same name.
+    pub fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .with_context(|| format!("cannot read diet rules from {}", dir.display()))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+        paths.sort();
+
+        for path in paths {
+            let diet = Diet::parse(&path.display().to_string(), &std::fs::read_to_string(&path)?)?;
+            self.diets.retain(|d| d.name != diet.name);
+            self.diets.push(diet);
+        }
+        Ok(())
+    }
+
+    pub fn evaluate(&self, ingredients: &[IngredientFacts], totals: &HashMap<String, (String, f32)>) -> Vec<DietCompliance> {
+        self.diets.iter().map(|diet| diet.evaluate(ingredients, totals)).collect()
+    }
+
+    pub fn diets(&self) -> &[Diet] {
+        &self.diets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diet(name: &str) -> Diet {
+        let (file, contents) = BUNDLED_DIETS.iter().find(|(file, _)| file.trim_end_matches(".json") == name).unwrap();
+        Diet::parse(file, contents).unwrap()
+    }
+
+    fn facts(ingredient: &str) -> IngredientFacts {
+        IngredientFacts {
+            ingredient: ingredient.to_string(),
+            description: None,
+            category: None,
+            allergens: allergens::detect(ingredient),
+            nutrients: HashMap::new(),
+        }
+    }
+
+    fn with_nutrient(ingredient: &str, nutrient: &str, amount: f32) -> IngredientFacts {
+        IngredientFacts {
+            nutrients: HashMap::from([(nutrient.to_string(), amount)]),
+            ..facts(ingredient)
+        }
+    }
+
+    fn totals(values: &[(&str, &str, f32)]) -> Totals {
+        values
+            .iter()
+            .map(|(nutrient, unit, amount)| (nutrient.to_string(), (unit.to_string(), *amount)))
+            .collect()
+    }
+
+    type Totals = HashMap<String, (String, f32)>;
+
+    fn violations(diet: &Diet, ingredients: &[IngredientFacts], totals: &Totals) -> Vec<(String, Vec<String>, Option<String>)> {
+        diet.evaluate(ingredients, totals)
+            .violations
+            .into_iter()
+            .map(|v| (v.reason, v.ingredients, v.detail))
+            .collect()
+    }
+
+    fn flagged(diet: &Diet, ingredients: &[&str]) -> Vec<String> {
+        let ingredients: Vec<_> = ingredients.iter().map(|i| facts(i)).collect();
+        diet.evaluate(&ingredients, &HashMap::new())
+            .violations
+            .into_iter()
+            .flat_map(|v| v.ingredients)
+            .collect()
+    }
+
+    #[test]
+    fn every_bundled_diet_parses() {
+        let rules = DietRules::bundled().unwrap();
+        assert_eq!(rules.diets().len(), BUNDLED_DIETS.len());
+
+        // Every file in data/diets is bundled, and loading the directory replaces rather than adds
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/diets");
+        let mut files: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".json"))
+            .collect();
+        files.sort();
+        let bundled: Vec<&str> = BUNDLED_DIETS.iter().map(|(file, _)| *file).collect();
+        assert_eq!(files, bundled);
+
+        let mut reloaded = DietRules::bundled().unwrap();
+        reloaded.load_dir(&dir).unwrap();
+        assert_eq!(reloaded.diets().len(), BUNDLED_DIETS.len());
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let parse = |rule: &str| Diet::parse("test.json", &format!(r#"{{"name": "test", "rules": [{}]}}"#, rule));
+        assert!(parse(r#"{"rule": "exclude", "reason": "r", "except": ["x"]}"#).is_err());
+        assert!(parse(r#"{"rule": "exclude", "reason": "r", "allergens": ["chocolate"]}"#).is_err());
+        assert!(parse(r#"{"rule": "limit", "reason": "r", "nutrient": "sodium"}"#).is_err());
+        assert!(parse(r#"{"rule": "limit", "reason": "r", "nutrient": "sodium", "basis": "energy_share", "max": 0.1}"#).is_err());
+        assert!(parse(r#"{"rule": "limit", "reason": "r", "nutrient": "sodium", "basis": "per_1000_kcal", "max": 1000}"#).is_ok());
+    }
+
+    #[test]
+    fn exclude_spares_excepted_ingredients() {
+        let keto = diet("keto");
+        assert_eq!(flagged(&keto, &["white rice", "corn", "brown sugar"]), ["white rice", "corn", "brown sugar"]);
+        assert!(flagged(&keto, &["cauliflower rice", "corn oil", "rice vinegar", "almond flour", "sugar snap peas"]).is_empty());
+
+        let vegan = diet("vegan");
+        assert_eq!(flagged(&vegan, &["beef tomato", "ground beef", "butter", "vegan cheese"]), ["ground beef", "butter"]);
+
+        // Categories and descriptions of matched foods count too
+        let yogurt = IngredientFacts {
+            description: Some("Yogurt, Greek, plain, nonfat".to_string()),
+            category: Some("Dairy and Egg Products".to_string()),
+            ..facts("skyr")
+        };
+        assert_eq!(violations(&vegan, &[yogurt], &HashMap::new()).len(), 1);
+    }
+
+    #[test]
+    fn not_combined_needs_both_sides() {
+        let kosher = diet("kosher");
+        assert_eq!(flagged(&kosher, &["chicken breast", "butter"]), ["chicken breast", "butter"]);
+        assert!(flagged(&kosher, &["chicken breast", "olive oil"]).is_empty());
+        assert!(flagged(&kosher, &["parmesan", "butter"]).is_empty());
+        assert!(flagged(&kosher, &["beef tomato", "mozzarella"]).is_empty());
+    }
+
+    #[test]
+    fn limits_apply_per_basis() {
+        let keto = diet("keto");
+        let meal = totals(&[("calories", "kcal", 500.0), ("net_carbohydrates", "g", 25.0), ("fat", "g", 20.0)]);
+        assert_eq!(
+            violations(&keto, &[], &meal),
+            [
+                (
+                    "Too many net carbohydrates for ketosis".to_string(),
+                    Vec::new(),
+                    Some("25.0 g net_carbohydrates (at most 20 g)".to_string())
+                ),
+                (
+                    "Not enough of the energy comes from fat".to_string(),
+                    Vec::new(),
+                    Some("36% of energy from fat (at least 60%)".to_string())
+                ),
+            ]
+        );
+        let fatty = totals(&[("calories", "kcal", 500.0), ("net_carbohydrates", "g", 5.0), ("fat", "g", 40.0)]);
+        assert!(violations(&keto, &[], &fatty).is_empty());
+
+        let dash = diet("dash");
+        let salty = totals(&[("calories", "kcal", 400.0), ("sodium", "mg", 600.0), ("fiber", "g", 6.0)]);
+        assert_eq!(
+            violations(&dash, &[], &salty),
+            [(
+                "Too much sodium for the energy it provides".to_string(),
+                Vec::new(),
+                Some("1500.0 mg/1000 kcal sodium (at most 1150 mg/1000 kcal)".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn relative_limits_skip_meals_without_energy() {
+        let dash = diet("dash");
+        let broth = totals(&[("calories", "kcal", 0.0), ("sodium", "mg", 900.0), ("fat", "g", 5.0)]);
+        assert!(violations(&dash, &[], &broth).is_empty());
+
+        // A limit on the meal itself still applies
+        let keto = diet("keto");
+        let syrup = totals(&[("net_carbohydrates", "g", 30.0)]);
+        assert_eq!(violations(&keto, &[], &syrup).len(), 1);
+    }
+
+    #[test]
+    fn names_the_main_contributors_to_an_exceeded_limit() {
+        let dash = diet("dash");
+        let ingredients = [
+            with_nutrient("bread", "sodium", 100.0),
+            with_nutrient("soy sauce", "sodium", 900.0),
+            with_nutrient("feta", "sodium", 400.0),
+        ];
+        let meal = totals(&[("calories", "kcal", 500.0), ("sodium", "mg", 1400.0), ("fiber", "g", 10.0)]);
+        let found = violations(&dash, &ingredients, &meal);
+        assert_eq!(found.len(), 1);
+        // bread adds 7% of the sodium, under the 25% share
+        assert_eq!(found[0].1, ["soy sauce", "feta"]);
+
+        assert!(main_contributors(&ingredients, "sodium", 0.0).is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+// Dietary reference intakes (RDA/AI and tolerable upper intake levels) by life stage
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::energy_calculator::Sex;
+use crate::models::ConsumerProfile;
+
+const BUNDLED_DRI: &str = include_str!("../data/dri.json");
+
+/// Used when the request does not say who is eating.
+const DEFAULT_AGE_YEARS: f32 = 30.0;
+
+#[derive(Debug, Deserialize)]
+struct DriFile {
+    groups: Vec<String>,
+    nutrients: Vec<NutrientDri>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NutrientDri {
+    name: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    unit: String,
+    basis: String,
+    intake: Vec<f32>,
+    #[serde(default)]
+    ul: Option<Vec<Option<f32>>>,
+}
+
+/// Reference values resolved for one nutrient and one consumer.
+#[derive(Debug, Clone)]
+pub struct ReferenceIntake {
+    pub unit: String,
+    /// "RDA" or "AI" (infants always have AI values).
+    pub basis: String,
+    pub target: f32,
+    pub upper_limit: Option<f32>,
+}
+
+pub struct DriTable {
+    groups: Vec<String>,
+    nutrients: Vec<NutrientDri>,
+}
+
+impl DriTable {
+    /// The table shipped in `data/dri.json`.
+    pub fn bundled() -> Result<Self> {
+        Self::from_json(BUNDLED_DRI)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let file: DriFile = serde_json::from_str(json)?;
+        for nutrient in &file.nutrients {
+            let ul_len = nutrient.ul.as_ref().map_or(file.groups.len(), Vec::len);
+            if nutrient.intake.len() != file.groups.len() || ul_len != file.groups.len() {
+                bail!("DRI entry for {} does not have one value per group", nutrient.name);
+            }
+        }
+        Ok(Self {
+            groups: file.groups,
+            nutrients: file.nutrients,
+        })
+    }
+
+    fn nutrient(&self, name: &str) -> Option<&NutrientDri> {
+        let name = name.trim().to_lowercase();
+        self.nutrients
+            .iter()
+            .find(|n| n.name.to_lowercase() == name || n.aliases.contains(&name))
+    }
+
+    fn group_index(&self, group: &str) -> Option<usize> {
+        self.groups.iter().position(|g| g == group)
+    }
+
+    /// Looks up the reference intake of `nutrient` for `consumer` (an adult when `None`).
+    ///
+    /// When the sex is unknown the higher of the male and female targets and the lower of
+    /// the upper limits is used, so percentages are never overstated.
+    pub fn lookup(&self, nutrient: &str, consumer: Option<&ConsumerProfile>) -> Option<ReferenceIntake> {
+        let entry = self.nutrient(nutrient)?;
+        let age = consumer.and_then(|c| c.age_years).unwrap_or(DEFAULT_AGE_YEARS);
+        let sex = consumer.and_then(|c| c.sex.as_deref()).and_then(Sex::parse);
+
+        let candidates: Vec<usize> = life_stage_groups(age, sex, consumer)
+            .iter()
+            .filter_map(|group| self.group_index(group))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let target = candidates.iter().map(|&i| entry.intake[i]).fold(f32::MIN, f32::max);
+        let upper_limit = entry.ul.as_ref().and_then(|ul| {
+            candidates
+                .iter()
+                .filter_map(|&i| ul[i])
+                .reduce(f32::min)
+        });
+        let basis = if age < 1.0 { "AI" } else { entry.basis.as_str() };
+
+        Some(ReferenceIntake {
+            unit: entry.unit.clone(),
+            basis: basis.to_string(),
+            target,
+            upper_limit,
+        })
+    }
+}
+
+fn age_band(age: f32) -> &'static str {
+    match age {
+        a if a < 14.0 => "9_13",
+        a if a < 19.0 => "14_18",
+        a if a < 31.0 => "19_30",
+        a if a < 51.0 => "31_50",
+        a if a < 71.0 => "51_70",
+        _ => "71_plus",
+    }
+}
+
+/// DRI group names that apply; two when the sex is unknown.
+fn life_stage_groups(age: f32, sex: Option<Sex>, consumer: Option<&ConsumerProfile>) -> Vec<String> {
+    if age < 0.5 {
+        return vec!["infants_0_6m".to_string()];
+    }
+    if age < 1.0 {
+        return vec!["infants_7_12m".to_string()];
+    }
+    if age < 4.0 {
+        return vec!["children_1_3".to_string()];
+    }
+    if age < 9.0 {
+        return vec!["children_4_8".to_string()];
+    }
+
+    let pregnant = consumer.is_some_and(|c| c.pregnant);
+    let lactating = consumer.is_some_and(|c| c.lactating);
+    if (pregnant || lactating) && sex != Some(Sex::Male) {
+        let stage = if pregnant { "pregnancy" } else { "lactation" };
+        let band = match age {
+            a if a < 19.0 => "14_18",
+            a if a < 31.0 => "19_30",
+            _ => "31_50",
+        };
+        return vec![format!("{}_{}", stage, band)];
+    }
+
+    let band = age_band(age);
+    match sex {
+        Some(Sex::Male) => vec![format!("males_{}", band)],
+        Some(Sex::Female) => vec![format!("females_{}", band)],
+        None => vec![format!("males_{}", band), format!("females_{}", band)],
+    }
+}
+
+/// Converts between the mass units used for micronutrients; `None` for unknown units.
+pub fn convert_mass(amount: f32, from: &str, to: &str) -> Option<f32> {
+    fn micrograms(unit: &str) -> Option<f32> {
+        match unit.trim().to_lowercase().as_str() {
+            "mcg" | "µg" | "ug" => Some(1.0),
+            "mg" => Some(1_000.0),
+            "g" => Some(1_000_000.0),
+            _ => None,
+        }
+    }
+    Some(amount * micrograms(from)? / micrograms(to)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn consumer(age: f32, sex: Option<&str>) -> ConsumerProfile {
+        ConsumerProfile {
+            age_years: Some(age),
+            sex: sex.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn lookup(nutrient: &str, consumer: &ConsumerProfile) -> ReferenceIntake {
+        DriTable::bundled().unwrap().lookup(nutrient, Some(consumer)).unwrap()
+    }
+
+    #[test]
+    fn picks_the_life_stage_group() {
+        let infant = lookup("Vitamin C", &consumer(0.3, None));
+        assert_eq!((infant.target, infant.basis.as_str(), infant.upper_limit), (40.0, "AI", None));
+        assert_eq!(lookup("Vitamin C", &consumer(2.0, None)).target, 15.0);
+        assert_eq!(lookup("Vitamin C", &consumer(16.0, Some("female"))).target, 65.0);
+        assert_eq!(lookup("Vitamin D", &consumer(75.0, Some("male"))).target, 20.0);
+
+        let iron = lookup("Iron", &consumer(35.0, Some("F")));
+        assert_eq!((iron.target, iron.unit.as_str(), iron.basis.as_str()), (18.0, "mg", "RDA"));
+        assert_eq!(lookup("iron", &consumer(35.0, Some("male"))).target, 8.0);
+    }
+
+    #[test]
+    fn unknown_sex_takes_the_higher_target_and_lower_limit() {
+        assert_eq!(lookup("Iron", &consumer(35.0, None)).target, 18.0);
+        assert_eq!(lookup("Zinc", &consumer(16.0, None)).target, 11.0);
+
+        // The bundled ULs do not differ by sex, so a made-up table shows the lower one is used
+        let table = DriTable::from_json(
+            r#"{"groups": ["males_19_30", "females_19_30"], "nutrients": [
+                {"name": "Test", "unit": "mg", "basis": "RDA", "intake": [10, 12], "ul": [50, 40]}]}"#,
+        )
+        .unwrap();
+        let test = table.lookup("test", Some(&consumer(25.0, None))).unwrap();
+        assert_eq!((test.target, test.upper_limit), (12.0, Some(40.0)));
+        let male = table.lookup("test", Some(&consumer(25.0, Some("m")))).unwrap();
+        assert_eq!((male.target, male.upper_limit), (10.0, Some(50.0)));
+
+        let adult = DriTable::bundled().unwrap().lookup("Vitamin C", None).unwrap();
+        assert_eq!((adult.target, adult.upper_limit), (90.0, Some(2000.0)));
+    }
+
+    #[test]
+    fn pregnancy_and_lactation_replace_the_adult_band() {
+        let mut pregnant = consumer(25.0, None);
+        pregnant.pregnant = true;
+        assert_eq!(lookup("Iron", &pregnant).target, 27.0);
+        assert_eq!(lookup("Folate", &pregnant).target, 600.0);
+
+        let mut lactating = consumer(40.0, Some("female"));
+        lactating.lactating = true;
+        assert_eq!(lookup("Vitamin C", &lactating).target, 120.0);
+
+        // Not for a man
+        let mut male = consumer(25.0, Some("male"));
+        male.pregnant = true;
+        assert_eq!(lookup("Iron", &male).target, 8.0);
+    }
+
+    #[test]
+    fn vitamin_a_has_no_upper_limit_on_rae() {
+        assert_eq!(lookup("Vitamin A", &consumer(2.0, None)).upper_limit, None);
+        assert_eq!(lookup("vitamin a rae", &consumer(30.0, Some("male"))).target, 900.0);
+        assert!(DriTable::bundled().unwrap().lookup("Unobtainium", None).is_none());
+    }
+
+    #[test]
+    fn converts_mass_units() {
+        assert_eq!(convert_mass(1.5, "mg", "mcg"), Some(1500.0));
+        assert_eq!(convert_mass(250.0, "µg", "mg"), Some(0.25));
+        assert_eq!(convert_mass(0.5, "g", "MG"), Some(500.0));
+        assert_eq!(convert_mass(1.0, "IU", "mcg"), None);
+    }
+}
@@ -0,0 +1,238 @@
+// Energy expenditure and macronutrient targets from body measurements, activity and goals
+use std::fmt;
+
+use crate::models::{NutritionGoalCalculation, NutritionGoals, NutritionGoalsRequest};
+
+/// Sodium target used for every goal (mg/day, the adult chronic disease risk reduction intake).
+pub const SODIUM_TARGET_MG: f32 = 2300.0;
+
+/// Body metrics outside what the formulas were fitted for; the caller's mistake (422).
+#[derive(Debug)]
+pub struct InvalidBodyMetrics(pub &'static str);
+
+impl fmt::Display for InvalidBodyMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid body metrics: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidBodyMetrics {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sex {
+    Male,
+    Female,
+}
+
+impl Sex {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "male" | "m" | "man" => Some(Sex::Male),
+            "female" | "f" | "woman" => Some(Sex::Female),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GoalDirection {
+    WeightLoss,
+    WeightGain,
+    MuscleGain,
+    Maintenance,
+}
+
+impl GoalDirection {
+    /// The first goal that changes energy balance wins; other goals (energy, digestion, ...) maintain.
+    pub fn from_goals(goals: &[String]) -> Self {
+        goals
+            .iter()
+            .find_map(|goal| match normalize(goal).as_str() {
+                "weight_loss" | "lose_weight" | "fat_loss" => Some(GoalDirection::WeightLoss),
+                "weight_gain" | "gain_weight" => Some(GoalDirection::WeightGain),
+                "muscle_gain" | "build_muscle" => Some(GoalDirection::MuscleGain),
+                _ => None,
+            })
+            .unwrap_or(GoalDirection::Maintenance)
+    }
+
+    /// Value of `goal_type_enum` for the `nutrition_goals` row.
+    pub fn as_goal_type(&self) -> &'static str {
+        match self {
+            GoalDirection::WeightLoss => "weight_loss",
+            GoalDirection::WeightGain => "weight_gain",
+            GoalDirection::MuscleGain => "muscle_gain",
+            GoalDirection::Maintenance => "maintenance",
+        }
+    }
+
+    /// Fraction of TDEE added (or removed) to reach the goal.
+    fn calorie_adjustment(&self) -> f32 {
+        match self {
+            GoalDirection::WeightLoss => -0.20,
+            GoalDirection::WeightGain => 0.15,
+            GoalDirection::MuscleGain => 0.10,
+            GoalDirection::Maintenance => 0.0,
+        }
+    }
+
+    /// Protein in grams per kg of body weight.
+    fn protein_per_kg(&self) -> f32 {
+        match self {
+            GoalDirection::WeightLoss | GoalDirection::MuscleGain => 2.0,
+            GoalDirection::WeightGain => 1.8,
+            GoalDirection::Maintenance => 1.4,
+        }
+    }
+
+    fn fat_share(&self) -> f32 {
+        match self {
+            GoalDirection::MuscleGain => 0.25,
+            _ => 0.30,
+        }
+    }
+}
+
+fn normalize(value: &str) -> String {
+    value.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// Physical activity level multipliers applied to BMR.
+pub fn activity_multiplier(activity_level: &str) -> f32 {
+    match normalize(activity_level).as_str() {
+        "sedentary" => 1.2,
+        "lightly_active" | "lightlyactive" | "light" => 1.375,
+        "very_active" | "veryactive" => 1.725,
+        "extra_active" | "extraactive" | "extremely_active" => 1.9,
+        _ => 1.55, // moderately_active
+    }
+}
+
+/// Mifflin-St Jeor. Without a known sex the midpoint of the male and female constants is used.
+pub fn mifflin_st_jeor(weight_kg: f32, height_cm: f32, age: i32, sex: Option<Sex>) -> f32 {
+    let sex_constant = match sex {
+        Some(Sex::Male) => 5.0,
+        Some(Sex::Female) => -161.0,
+        None => -78.0,
+    };
+    10.0 * weight_kg + 6.25 * height_cm - 5.0 * age as f32 + sex_constant
+}
+
+/// Katch-McArdle, based on lean body mass; more accurate when body fat is known.
+pub fn katch_mcardle(weight_kg: f32, body_fat_percentage: f32) -> f32 {
+    let lean_mass_kg = weight_kg * (1.0 - body_fat_percentage / 100.0);
+    370.0 + 21.6 * lean_mass_kg
+}
+
+/// Lowest intake recommended without medical supervision.
+fn calorie_floor(sex: Option<Sex>) -> f32 {
+    match sex {
+        Some(Sex::Male) => 1500.0,
+        _ => 1200.0,
+    }
+}
+
+/// Fat never drops below this share of energy when protein and fat have to be squeezed
+/// into a low calorie target.
+const MIN_FAT_SHARE: f32 = 0.20;
+
+fn validate(request: &NutritionGoalsRequest) -> Result<(), InvalidBodyMetrics> {
+    if !(1..=120).contains(&request.age) {
+        return Err(InvalidBodyMetrics("age must be between 1 and 120 years"));
+    }
+    if !(20.0..=650.0).contains(&request.weight_kg) {
+        return Err(InvalidBodyMetrics("weight_kg must be between 20 and 650"));
+    }
+    if !(50.0..=272.0).contains(&request.height_cm) {
+        return Err(InvalidBodyMetrics("height_cm must be between 50 and 272"));
+    }
+    Ok(())
+}
+
+pub fn calculate(request: &NutritionGoalsRequest) -> Result<NutritionGoalCalculation, InvalidBodyMetrics> {
+    validate(request)?;
+
+    let sex = request.sex.as_deref().and_then(Sex::parse);
+    let body_fat = request.body_fat_percentage.filter(|bf| *bf > 2.0 && *bf < 70.0);
+
+    let (bmr, bmr_formula) = match body_fat {
+        Some(bf) => (katch_mcardle(request.weight_kg, bf), "katch_mcardle"),
+        None => (mifflin_st_jeor(request.weight_kg, request.height_cm, request.age, sex), "mifflin_st_jeor"),
+    };
+
+    let multiplier = activity_multiplier(&request.activity_level);
+    let tdee = bmr * multiplier;
+
+    let direction = GoalDirection::from_goals(&request.goals);
+    let adjustment = direction.calorie_adjustment();
+    let target_calories = (tdee * (1.0 + adjustment)).max(calorie_floor(sex)).round();
+
+    // Protein is set per kg, fat as a share of energy, carbohydrates fill the rest
+    let mut protein_g = request.weight_kg * direction.protein_per_kg();
+    let mut fat_g = target_calories * direction.fat_share() / 9.0;
+    if protein_g * 4.0 + fat_g * 9.0 > target_calories {
+        // Heavy users on a floored target: give up fat first, then protein, so the
+        // macros still add up to the calorie target
+        fat_g = ((target_calories - protein_g * 4.0) / 9.0).max(target_calories * MIN_FAT_SHARE / 9.0);
+        protein_g = protein_g.min((target_calories - fat_g * 9.0) / 4.0);
+    }
+    let carbs_g = ((target_calories - protein_g * 4.0 - fat_g * 9.0) / 4.0).max(0.0);
+    // 14 g of fiber per 1000 kcal
+    let fiber_g = target_calories / 1000.0 * 14.0;
+
+    Ok(NutritionGoalCalculation {
+        bmr: bmr.round(),
+        bmr_formula: bmr_formula.to_string(),
+        activity_multiplier: multiplier,
+        tdee: tdee.round(),
+        calorie_adjustment: adjustment,
+        goal_type: direction.as_goal_type().to_string(),
+        goals: NutritionGoals {
+            daily_calories: target_calories,
+            daily_protein: protein_g.round(),
+            daily_carbs: carbs_g.round(),
+            daily_fat: fat_g.round(),
+            daily_fiber: fiber_g.round(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn request(age: i32, weight_kg: f32, height_cm: f32, goals: &[&str]) -> NutritionGoalsRequest {
+        NutritionGoalsRequest {
+            user_id: Uuid::nil(),
+            age,
+            weight_kg,
+            height_cm,
+            activity_level: "sedentary".to_string(),
+            goals: goals.iter().map(|g| g.to_string()).collect(),
+            sex: Some("female".to_string()),
+            body_fat_percentage: None,
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_metrics() {
+        assert!(calculate(&request(30, 0.0, 170.0, &[])).is_err());
+        assert!(calculate(&request(-5, 70.0, 170.0, &[])).is_err());
+        assert!(calculate(&request(30, 70.0, 0.0, &[])).is_err());
+        assert!(calculate(&request(30, f32::NAN, 170.0, &[])).is_err());
+    }
+
+    #[test]
+    fn macros_are_scaled_down_to_fit_the_target() {
+        // Little lean mass on a deficit: 2 g/kg of body weight plus 30 % fat exceeds the target
+        let mut heavy = request(40, 130.0, 160.0, &["weight_loss"]);
+        heavy.body_fat_percentage = Some(65.0);
+        let calculation = calculate(&heavy).unwrap();
+        let goals = &calculation.goals;
+        let macro_calories = goals.daily_protein * 4.0 + goals.daily_fat * 9.0 + goals.daily_carbs * 4.0;
+
+        assert!((macro_calories - goals.daily_calories).abs() <= 10.0, "{:?}", goals);
+        assert!(goals.daily_fat * 9.0 >= goals.daily_calories * MIN_FAT_SHARE - 5.0);
+    }
+}
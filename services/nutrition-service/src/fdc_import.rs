@@ -0,0 +1,655 @@
+// Importer for USDA FoodData Central downloads (SR Legacy and Foundation Foods)
+//
+// Accepts either download format:
+//   JSON  one file with a top-level `SRLegacyFoods` or `FoundationFoods` array
+//   CSV   the unzipped directory: food.csv, nutrient.csv, food_nutrient.csv and, when present,
+//         food_category.csv, food_portion.csv and measure_unit.csv
+// Foods that are already in the database are replaced, so a newer release can be imported
+// over an older one.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::glycemic;
+
+/// Foods written per transaction.
+const BATCH_SIZE: usize = 500;
+
+/// FDC nutrient numbers of the basic nutrition values, in order of preference.
+/// Foundation Foods report energy as Atwater factors (958/957) rather than 208.
+const ENERGY: &[&str] = &["208", "958", "957"];
+const PROTEIN: &[&str] = &["203"];
+const CARBOHYDRATES: &[&str] = &["205", "205.2"];
+const FAT: &[&str] = &["204", "298"];
+const SATURATED_FAT: &[&str] = &["606"];
+const FIBER: &[&str] = &["291"];
+const SUGAR: &[&str] = &["269", "269.3"];
+const SODIUM: &[&str] = &["307"];
+
+/// FDC nutrient numbers of the micronutrients that have a dietary reference intake,
+/// mapped to the names used in `data/dri.json`.
+const MICRONUTRIENTS: &[(&str, &str)] = &[
+    ("320", "Vitamin A"),
+    ("401", "Vitamin C"),
+    ("328", "Vitamin D"),
+    ("323", "Vitamin E"),
+    ("430", "Vitamin K"),
+    ("404", "Thiamin"),
+    ("405", "Riboflavin"),
+    ("406", "Niacin"),
+    ("415", "Vitamin B6"),
+    ("435", "Folate"),
+    ("418", "Vitamin B12"),
+    ("410", "Pantothenic Acid"),
+    ("416", "Biotin"),
+    ("421", "Choline"),
+    ("301", "Calcium"),
+    ("312", "Copper"),
+    ("313", "Fluoride"),
+    ("303", "Iron"),
+    ("304", "Magnesium"),
+    ("315", "Manganese"),
+    ("305", "Phosphorus"),
+    ("317", "Selenium"),
+    ("309", "Zinc"),
+    ("306", "Potassium"),
+];
+
+/// A food as read from either download format.
+#[derive(Debug)]
+struct FdcFood {
+    fdc_id: i32,
+    data_type: String,
+    description: String,
+    category: Option<String>,
+    /// Nutrient number -> (unit, amount per 100 g)
+    nutrients: HashMap<String, (String, f32)>,
+    portions: Vec<FdcPortion>,
+}
+
+#[derive(Debug)]
+struct FdcPortion {
+    amount: Option<f32>,
+    measure_unit: Option<String>,
+    modifier: Option<String>,
+    gram_weight: f32,
+}
+
+impl FdcFood {
+    fn amount(&self, numbers: &[&str]) -> Option<f32> {
+        numbers.iter().find_map(|number| self.nutrients.get(*number).map(|(_, amount)| *amount))
+    }
+
+    /// The food's micronutrients with a DRI, as (name, unit, amount per 100 g).
+    fn micronutrients(&self) -> Vec<(&'static str, &str, f32)> {
+        MICRONUTRIENTS
+            .iter()
+            .filter_map(|(number, name)| self.nutrients.get(*number).map(|(unit, amount)| (*name, unit.as_str(), *amount)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub foods: usize,
+    pub nutrients: usize,
+    pub portions: usize,
+    /// Foods with a glycemic index after the reference values were applied
+    pub glycemic_indexes: i64,
+}
+
+/// Imports a JSON file or an unzipped CSV directory.
+pub async fn import_path(pool: &PgPool, path: &Path) -> Result<ImportSummary> {
+    let foods = if path.is_dir() { read_csv_dir(path)? } else { read_json(path)? };
+    if foods.is_empty() {
+        bail!("no SR Legacy or Foundation foods found in {}", path.display());
+    }
+
+    let mut summary = ImportSummary::default();
+    for batch in foods.chunks(BATCH_SIZE) {
+        let mut tx = pool.begin().await?;
+        for food in batch {
+            store_food(&mut tx, food, &mut summary).await?;
+        }
+        tx.commit().await?;
+        tracing::info!("Imported {} of {} foods", summary.foods, foods.len());
+    }
+    summary.glycemic_indexes = glycemic::assign_reference_values(pool).await?;
+    Ok(summary)
+}
+
+async fn store_food(tx: &mut Transaction<'_, Postgres>, food: &FdcFood, summary: &mut ImportSummary) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO foods (fdc_id, data_type, description, food_category, calories_kcal, protein_g, \
+                            carbohydrates_g, fat_g, saturated_fat_g, fiber_g, sugar_g, sodium_mg) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) \
+         ON CONFLICT (fdc_id) DO UPDATE SET data_type = EXCLUDED.data_type, description = EXCLUDED.description, \
+             food_category = EXCLUDED.food_category, calories_kcal = EXCLUDED.calories_kcal, \
+             protein_g = EXCLUDED.protein_g, carbohydrates_g = EXCLUDED.carbohydrates_g, fat_g = EXCLUDED.fat_g, \
+             saturated_fat_g = EXCLUDED.saturated_fat_g, \
+             fiber_g = EXCLUDED.fiber_g, sugar_g = EXCLUDED.sugar_g, sodium_mg = EXCLUDED.sodium_mg, \
+             imported_at = NOW()",
+    )
+    .bind(food.fdc_id)
+    .bind(&food.data_type)
+    .bind(&food.description)
+    .bind(&food.category)
+    .bind(food.amount(ENERGY))
+    .bind(food.amount(PROTEIN))
+    .bind(food.amount(CARBOHYDRATES))
+    .bind(food.amount(FAT))
+    .bind(food.amount(SATURATED_FAT))
+    .bind(food.amount(FIBER))
+    .bind(food.amount(SUGAR))
+    .bind(food.amount(SODIUM))
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query("DELETE FROM food_nutrients WHERE fdc_id = $1")
+        .bind(food.fdc_id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("DELETE FROM food_portions WHERE fdc_id = $1")
+        .bind(food.fdc_id)
+        .execute(&mut **tx)
+        .await?;
+
+    let (mut names, mut units, mut amounts) = (Vec::new(), Vec::new(), Vec::new());
+    for (name, unit, amount) in food.micronutrients() {
+        names.push(name.to_string());
+        units.push(unit.to_string());
+        amounts.push(amount);
+    }
+    sqlx::query(
+        "INSERT INTO food_nutrients (fdc_id, name, unit, amount_per_100g) \
+         SELECT $1, * FROM UNNEST($2::text[], $3::text[], $4::real[])",
+    )
+    .bind(food.fdc_id)
+    .bind(&names)
+    .bind(&units)
+    .bind(&amounts)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO food_portions (fdc_id, amount, measure_unit, modifier, gram_weight) \
+         SELECT $1, * FROM UNNEST($2::real[], $3::text[], $4::text[], $5::real[])",
+    )
+    .bind(food.fdc_id)
+    .bind(food.portions.iter().map(|p| p.amount).collect::<Vec<_>>())
+    .bind(food.portions.iter().map(|p| p.measure_unit.clone()).collect::<Vec<_>>())
+    .bind(food.portions.iter().map(|p| p.modifier.clone()).collect::<Vec<_>>())
+    .bind(food.portions.iter().map(|p| p.gram_weight).collect::<Vec<_>>())
+    .execute(&mut **tx)
+    .await?;
+
+    summary.foods += 1;
+    summary.nutrients += names.len();
+    summary.portions += food.portions.len();
+    Ok(())
+}
+
+/// FDC writes units as `G`, `MG`, `UG`/`µg`, `KCAL`, ...; the DRI table uses `mcg`.
+fn normalize_unit(unit: &str) -> String {
+    match unit.trim().to_lowercase().as_str() {
+        "ug" | "µg" => "mcg".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Some releases write nutrient numbers as decimals (`208.0`).
+fn normalize_number(number: &str) -> String {
+    let number = number.trim();
+    number.strip_suffix(".0").unwrap_or(number).to_string()
+}
+
+/// SR Legacy portions have an `undetermined` unit and describe the measure in the modifier.
+fn measure_unit(name: Option<String>) -> Option<String> {
+    name.filter(|n| !n.is_empty() && n != "undetermined")
+}
+
+// JSON download
+
+#[derive(Deserialize)]
+struct JsonDownload {
+    #[serde(rename = "SRLegacyFoods", default)]
+    sr_legacy_foods: Vec<JsonFood>,
+    #[serde(rename = "FoundationFoods", default)]
+    foundation_foods: Vec<JsonFood>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonFood {
+    fdc_id: i32,
+    description: String,
+    food_category: Option<JsonDescription>,
+    #[serde(default)]
+    food_nutrients: Vec<JsonFoodNutrient>,
+    #[serde(default)]
+    food_portions: Vec<JsonPortion>,
+}
+
+#[derive(Deserialize)]
+struct JsonDescription {
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct JsonFoodNutrient {
+    nutrient: JsonNutrient,
+    amount: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonNutrient {
+    number: Option<String>,
+    unit_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonPortion {
+    amount: Option<f32>,
+    measure_unit: Option<JsonMeasureUnit>,
+    modifier: Option<String>,
+    portion_description: Option<String>,
+    gram_weight: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct JsonMeasureUnit {
+    name: String,
+}
+
+fn read_json(path: &Path) -> Result<Vec<FdcFood>> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let download: JsonDownload =
+        serde_json::from_reader(BufReader::new(file)).with_context(|| format!("parsing {}", path.display()))?;
+
+    let sr_legacy = download.sr_legacy_foods.into_iter().map(|f| (f, "sr_legacy_food"));
+    let foundation = download.foundation_foods.into_iter().map(|f| (f, "foundation_food"));
+
+    Ok(sr_legacy
+        .chain(foundation)
+        .map(|(food, data_type)| FdcFood {
+            fdc_id: food.fdc_id,
+            data_type: data_type.to_string(),
+            description: food.description,
+            category: food.food_category.map(|c| c.description),
+            nutrients: food
+                .food_nutrients
+                .into_iter()
+                .filter_map(|n| {
+                    let number = normalize_number(n.nutrient.number.as_deref()?);
+                    Some((number, (normalize_unit(&n.nutrient.unit_name), n.amount?)))
+                })
+                .collect(),
+            portions: food
+                .food_portions
+                .into_iter()
+                .filter_map(|p| {
+                    Some(FdcPortion {
+                        amount: p.amount,
+                        measure_unit: measure_unit(p.measure_unit.map(|u| u.name)),
+                        modifier: p.modifier.filter(|m| !m.is_empty()).or(p.portion_description),
+                        gram_weight: p.gram_weight?,
+                    })
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+// CSV download
+
+#[derive(Deserialize)]
+struct CsvFood {
+    fdc_id: i32,
+    data_type: String,
+    description: String,
+    food_category_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CsvCategory {
+    id: String,
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct CsvNutrient {
+    id: i32,
+    unit_name: String,
+    nutrient_nbr: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CsvFoodNutrient {
+    fdc_id: i32,
+    nutrient_id: i32,
+    amount: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct CsvPortion {
+    fdc_id: i32,
+    amount: Option<f32>,
+    measure_unit_id: Option<String>,
+    portion_description: Option<String>,
+    modifier: Option<String>,
+    gram_weight: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct CsvMeasureUnit {
+    id: String,
+    name: String,
+}
+
+/// Data types kept from the CSV download, which may also contain branded and survey foods.
+const CSV_DATA_TYPES: &[&str] = &["sr_legacy_food", "foundation_food"];
+
+fn read_csv<T: for<'de> Deserialize<'de>>(dir: &Path, file: &str) -> Result<Vec<T>> {
+    let path = dir.join(file);
+    let mut reader = csv::Reader::from_path(&path).with_context(|| format!("opening {}", path.display()))?;
+    reader
+        .deserialize()
+        .collect::<Result<Vec<T>, _>>()
+        .with_context(|| format!("parsing {}", path.display()))
+}
+
+fn read_optional_csv<T: for<'de> Deserialize<'de>>(dir: &Path, file: &str) -> Result<Vec<T>> {
+    if dir.join(file).exists() {
+        read_csv(dir, file)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+fn read_csv_dir(dir: &Path) -> Result<Vec<FdcFood>> {
+    let categories: HashMap<String, String> = read_optional_csv::<CsvCategory>(dir, "food_category.csv")?
+        .into_iter()
+        .map(|c| (c.id, c.description))
+        .collect();
+    let measure_units: HashMap<String, String> = read_optional_csv::<CsvMeasureUnit>(dir, "measure_unit.csv")?
+        .into_iter()
+        .map(|u| (u.id, u.name))
+        .collect();
+    let nutrients: HashMap<i32, (String, String)> = read_csv::<CsvNutrient>(dir, "nutrient.csv")?
+        .into_iter()
+        .filter_map(|n| Some((n.id, (normalize_number(n.nutrient_nbr.as_deref()?), normalize_unit(&n.unit_name)))))
+        .collect();
+
+    let mut foods: HashMap<i32, FdcFood> = read_csv::<CsvFood>(dir, "food.csv")?
+        .into_iter()
+        .filter(|f| CSV_DATA_TYPES.contains(&f.data_type.as_str()))
+        .map(|f| {
+            let food = FdcFood {
+                fdc_id: f.fdc_id,
+                data_type: f.data_type,
+                description: f.description,
+                category: f.food_category_id.and_then(|id| categories.get(&id).cloned()),
+                nutrients: HashMap::new(),
+                portions: Vec::new(),
+            };
+            (f.fdc_id, food)
+        })
+        .collect();
+
+    // food_nutrient.csv is the largest file, so it is streamed rather than collected
+    let path = dir.join("food_nutrient.csv");
+    let mut reader = csv::Reader::from_path(&path).with_context(|| format!("opening {}", path.display()))?;
+    for row in reader.deserialize::<CsvFoodNutrient>() {
+        let row = row.with_context(|| format!("parsing {}", path.display()))?;
+        let (Some(food), Some((number, unit)), Some(amount)) =
+            (foods.get_mut(&row.fdc_id), nutrients.get(&row.nutrient_id), row.amount)
+        else {
+            continue;
+        };
+        food.nutrients.insert(number.clone(), (unit.clone(), amount));
+    }
+
+    for portion in read_optional_csv::<CsvPortion>(dir, "food_portion.csv")? {
+        let (Some(food), Some(gram_weight)) = (foods.get_mut(&portion.fdc_id), portion.gram_weight) else {
+            continue;
+        };
+        food.portions.push(FdcPortion {
+            amount: portion.amount,
+            measure_unit: measure_unit(portion.measure_unit_id.and_then(|id| measure_units.get(&id).cloned())),
+            modifier: portion.modifier.filter(|m| !m.is_empty()).or(portion.portion_description),
+            gram_weight,
+        });
+    }
+
+    let mut foods: Vec<FdcFood> = foods.into_values().collect();
+    foods.sort_by_key(|f| f.fdc_id);
+    Ok(foods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A fresh directory under the system temp dir, removed when dropped.
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!("fdc-import-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            for (file, contents) in files {
+                std::fs::write(dir.join(file), contents).unwrap();
+            }
+            Self(dir)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const JSON_DOWNLOAD: &str = r#"{
+        "SRLegacyFoods": [{
+            "fdcId": 170567,
+            "description": "Nuts, almonds",
+            "foodCategory": {"description": "Nut and Seed Products"},
+            "foodNutrients": [
+                {"nutrient": {"number": "208", "unitName": "kcal"}, "amount": 579},
+                {"nutrient": {"number": "203", "unitName": "g"}, "amount": 21.2},
+                {"nutrient": {"number": "307", "unitName": "mg"}, "amount": 1},
+                {"nutrient": {"number": "435", "unitName": "µg"}, "amount": 44},
+                {"nutrient": {"number": "323", "unitName": "mg"}, "amount": 25.6},
+                {"nutrient": {"unitName": "g"}, "amount": 3},
+                {"nutrient": {"number": "291", "unitName": "g"}}
+            ],
+            "foodPortions": [
+                {"amount": 1, "measureUnit": {"name": "undetermined"}, "modifier": "cup, whole", "gramWeight": 143},
+                {"amount": 1, "measureUnit": {"name": "oz"}, "modifier": "", "portionDescription": "1 oz (23 whole kernels)", "gramWeight": 28.35}
+            ]
+        }],
+        "FoundationFoods": [{
+            "fdcId": 1999633,
+            "description": "Spinach, mature",
+            "foodCategory": {"description": "Vegetables and Vegetable Products"},
+            "foodNutrients": [
+                {"nutrient": {"number": "957", "unitName": "kcal"}, "amount": 28},
+                {"nutrient": {"number": "203.0", "unitName": "g"}, "amount": 2.91},
+                {"nutrient": {"number": "401", "unitName": "mg"}, "amount": 26.5}
+            ],
+            "foodPortions": [
+                {"amount": 1, "measureUnit": {"name": "cup"}, "gramWeight": 30},
+                {"amount": 1, "measureUnit": {"name": "leaf"}}
+            ]
+        }]
+    }"#;
+
+    #[test]
+    fn reads_the_json_download() {
+        let fixture = Fixture::new("json", &[("foods.json", JSON_DOWNLOAD)]);
+        let foods = read_json(&fixture.0.join("foods.json")).unwrap();
+        assert_eq!(foods.len(), 2);
+
+        let almonds = &foods[0];
+        assert_eq!((almonds.fdc_id, almonds.data_type.as_str()), (170567, "sr_legacy_food"));
+        assert_eq!(almonds.category.as_deref(), Some("Nut and Seed Products"));
+        assert_eq!(almonds.amount(ENERGY), Some(579.0));
+        assert_eq!(almonds.amount(SODIUM), Some(1.0));
+        // Nutrients without a number or an amount are skipped
+        assert_eq!(almonds.nutrients.len(), 5);
+        assert_eq!(almonds.amount(FIBER), None);
+        assert_eq!(almonds.micronutrients(), [("Vitamin E", "mg", 25.6), ("Folate", "mcg", 44.0)]);
+
+        let portions: Vec<_> = almonds
+            .portions
+            .iter()
+            .map(|p| (p.amount, p.measure_unit.as_deref(), p.modifier.as_deref(), p.gram_weight))
+            .collect();
+        assert_eq!(
+            portions,
+            [
+                (Some(1.0), None, Some("cup, whole"), 143.0),
+                (Some(1.0), Some("oz"), Some("1 oz (23 whole kernels)"), 28.35),
+            ]
+        );
+
+        let spinach = &foods[1];
+        assert_eq!(spinach.data_type, "foundation_food");
+        // Foundation Foods give energy as Atwater factors and may write numbers as decimals
+        assert_eq!(spinach.amount(ENERGY), Some(28.0));
+        assert_eq!(spinach.amount(PROTEIN), Some(2.91));
+        assert_eq!(spinach.micronutrients(), [("Vitamin C", "mg", 26.5)]);
+        // Portions without a gram weight cannot be used
+        assert_eq!(spinach.portions.len(), 1);
+        assert_eq!(spinach.portions[0].measure_unit.as_deref(), Some("cup"));
+    }
+
+    const FOOD_CSV: &str = "\
+fdc_id,data_type,description,food_category_id,publication_date
+170567,sr_legacy_food,\"Nuts, almonds\",12,2019-04-01
+1999633,foundation_food,\"Spinach, mature\",11,2021-10-28
+2000001,branded_food,Almond Butter Cups,,2021-10-28
+";
+    const NUTRIENT_CSV: &str = "\
+id,name,unit_name,nutrient_nbr,rank
+1008,Energy,KCAL,208.0,300
+2047,Energy (Atwater General Factors),KCAL,957.0,280
+1003,Protein,G,203.0,600
+1093,\"Sodium, Na\",MG,307.0,5800
+1162,\"Vitamin C, total ascorbic acid\",MG,401.0,6300
+1177,\"Folate, total\",UG,435.0,6800
+2000,Unnumbered,G,,0
+";
+    const FOOD_NUTRIENT_CSV: &str = "\
+id,fdc_id,nutrient_id,amount
+1,170567,1008,579
+2,170567,1003,21.2
+3,170567,1093,1
+4,170567,1177,44
+5,170567,2000,3
+6,1999633,2047,28
+7,1999633,1162,26.5
+8,1999633,1003,
+9,2000001,1008,520
+";
+    const FOOD_CATEGORY_CSV: &str = "\
+id,code,description
+11,1100,Vegetables and Vegetable Products
+12,1200,Nut and Seed Products
+";
+    const FOOD_PORTION_CSV: &str = "\
+id,fdc_id,seq_num,amount,measure_unit_id,portion_description,modifier,gram_weight
+1,170567,1,1,9999,,\"cup, whole\",143
+2,170567,2,1,1038,1 oz (23 whole kernels),,28.35
+3,1999633,1,1,1000,,,30
+4,1999633,2,1,1000,,,
+5,2000001,1,1,1000,,,32
+";
+    const MEASURE_UNIT_CSV: &str = "\
+id,name
+1000,cup
+1038,oz
+9999,undetermined
+";
+
+    #[test]
+    fn reads_the_csv_download() {
+        let fixture = Fixture::new(
+            "csv",
+            &[
+                ("food.csv", FOOD_CSV),
+                ("nutrient.csv", NUTRIENT_CSV),
+                ("food_nutrient.csv", FOOD_NUTRIENT_CSV),
+                ("food_category.csv", FOOD_CATEGORY_CSV),
+                ("food_portion.csv", FOOD_PORTION_CSV),
+                ("measure_unit.csv", MEASURE_UNIT_CSV),
+            ],
+        );
+        let foods = read_csv_dir(&fixture.0).unwrap();
+        // Branded foods are left out
+        let ids: Vec<i32> = foods.iter().map(|f| f.fdc_id).collect();
+        assert_eq!(ids, [170567, 1999633]);
+
+        let almonds = &foods[0];
+        assert_eq!(almonds.data_type, "sr_legacy_food");
+        assert_eq!(almonds.category.as_deref(), Some("Nut and Seed Products"));
+        assert_eq!(almonds.amount(ENERGY), Some(579.0));
+        assert_eq!(almonds.amount(PROTEIN), Some(21.2));
+        assert_eq!(almonds.amount(SODIUM), Some(1.0));
+        assert_eq!(almonds.nutrients.len(), 4);
+        assert_eq!(almonds.micronutrients(), [("Folate", "mcg", 44.0)]);
+        let portions: Vec<_> = almonds
+            .portions
+            .iter()
+            .map(|p| (p.measure_unit.as_deref(), p.modifier.as_deref(), p.gram_weight))
+            .collect();
+        assert_eq!(
+            portions,
+            [(None, Some("cup, whole"), 143.0), (Some("oz"), Some("1 oz (23 whole kernels)"), 28.35)]
+        );
+
+        let spinach = &foods[1];
+        assert_eq!(spinach.data_type, "foundation_food");
+        assert_eq!(spinach.category.as_deref(), Some("Vegetables and Vegetable Products"));
+        assert_eq!(spinach.amount(ENERGY), Some(28.0));
+        assert_eq!(spinach.amount(PROTEIN), None);
+        assert_eq!(spinach.micronutrients(), [("Vitamin C", "mg", 26.5)]);
+        assert_eq!(spinach.portions.len(), 1);
+    }
+
+    #[test]
+    fn optional_csv_files_may_be_missing() {
+        let fixture = Fixture::new(
+            "csv-minimal",
+            &[("food.csv", FOOD_CSV), ("nutrient.csv", NUTRIENT_CSV), ("food_nutrient.csv", FOOD_NUTRIENT_CSV)],
+        );
+        let foods = read_csv_dir(&fixture.0).unwrap();
+        assert_eq!(foods.len(), 2);
+        assert!(foods.iter().all(|f| f.category.is_none() && f.portions.is_empty()));
+        assert_eq!(foods[0].amount(ENERGY), Some(579.0));
+
+        let incomplete = Fixture::new("csv-incomplete", &[("food.csv", FOOD_CSV)]);
+        assert!(read_csv_dir(&incomplete.0).is_err());
+    }
+
+    #[test]
+    fn normalizes_units_and_numbers() {
+        assert_eq!(normalize_unit("UG"), "mcg");
+        assert_eq!(normalize_unit("µg"), "mcg");
+        assert_eq!(normalize_unit("KCAL"), "kcal");
+        assert_eq!(normalize_number(" 208.0 "), "208");
+        assert_eq!(normalize_number("205.2"), "205.2");
+        assert_eq!(measure_unit(Some("undetermined".to_string())), None);
+        assert_eq!(measure_unit(Some(String::new())), None);
+    }
+}
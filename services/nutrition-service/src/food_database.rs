@@ -0,0 +1,168 @@
+// Ingredient lookups against the imported FoodData Central tables, cached in memory
+use anyhow::Result;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::ingredient_matcher;
+
+/// Lookups (including misses) are reused for this long, so a new import is picked up
+/// without restarting the service.
+const CACHE_TTL: Duration = Duration::from_secs(3600);
+/// The cache is cleared when it reaches this many names.
+const CACHE_CAPACITY: usize = 5_000;
+/// Candidate descriptions fetched from Postgres and ranked by `ingredient_matcher::score`.
+const CANDIDATE_LIMIT: i64 = 50;
+
+const FOOD_COLUMNS: &str = "fdc_id, description, food_category, calories_kcal, protein_g, carbohydrates_g, \
+                            fat_g, saturated_fat_g, fiber_g, sugar_g, sodium_mg, glycemic_index";
+
+/// A food with its nutrition per 100 g.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FoodRecord {
+    pub fdc_id: i32,
+    pub description: String,
+    pub food_category: Option<String>,
+    pub calories_kcal: Option<f32>,
+    pub protein_g: Option<f32>,
+    pub carbohydrates_g: Option<f32>,
+    pub fat_g: Option<f32>,
+    pub saturated_fat_g: Option<f32>,
+    pub fiber_g: Option<f32>,
+    pub sugar_g: Option<f32>,
+    pub sodium_mg: Option<f32>,
+    pub glycemic_index: Option<f32>,
+    #[sqlx(skip)]
+    pub micronutrients: Vec<FoodNutrient>,
+    #[sqlx(skip)]
+    pub portions: Vec<FoodPortion>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FoodNutrient {
+    pub name: String,
+    pub unit: String,
+    pub amount_per_100g: f32,
+}
+
+/// The food an ingredient name resolved to.
+#[derive(Debug, Clone)]
+pub struct FoodMatch {
+    pub food: Arc<FoodRecord>,
+    /// 0-1; 1 for an exact description or a synonym pinned to a food
+    pub confidence: f32,
+}
+
+/// A household measure: `amount` `measure_unit` (`modifier`) weighs `gram_weight`.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FoodPortion {
+    pub amount: Option<f32>,
+    pub measure_unit: Option<String>,
+    pub modifier: Option<String>,
+    pub gram_weight: f32,
+}
+
+struct CacheEntry {
+    loaded_at: Instant,
+    found: Option<FoodMatch>,
+}
+
+pub struct FoodDatabase {
+    pool: PgPool,
+    cache: RwLock<HashMap<String, CacheEntry>>,
+}
+
+impl FoodDatabase {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Finds the food for an ingredient name, or `None` if nothing matches with at least
+    /// `MIN_CONFIDENCE`.
+    pub async fn find(&self, ingredient_name: &str) -> Result<Option<FoodMatch>> {
+        let key = ingredient_matcher::normalize(ingredient_name);
+        if key.is_empty() {
+            return Ok(None);
+        }
+        if let Some(entry) = self.cache.read().unwrap().get(&key) {
+            if entry.loaded_at.elapsed() < CACHE_TTL {
+                return Ok(entry.found.clone());
+            }
+        }
+
+        let found = self.resolve(&key).await?;
+
+        let mut cache = self.cache.write().unwrap();
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(key, CacheEntry {
+            loaded_at: Instant::now(),
+            found: found.clone(),
+        });
+        Ok(found)
+    }
+
+    /// Applies `ingredient_synonyms`, then ranks the descriptions sharing words with the name.
+    async fn resolve(&self, normalized: &str) -> Result<Option<FoodMatch>> {
+        let synonym: Option<(String, Option<i32>)> =
+            sqlx::query_as("SELECT canonical_name, fdc_id FROM ingredient_synonyms WHERE synonym = $1")
+                .bind(normalized)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        let name = match synonym {
+            Some((_, Some(fdc_id))) => {
+                let food: Option<FoodRecord> = sqlx::query_as(&format!("SELECT {} FROM foods WHERE fdc_id = $1", FOOD_COLUMNS))
+                    .bind(fdc_id)
+                    .fetch_optional(&self.pool)
+                    .await?;
+                return match food {
+                    Some(food) => Ok(Some(self.with_details(food, 1.0).await?)),
+                    None => Ok(None),
+                };
+            }
+            Some((canonical_name, None)) => ingredient_matcher::normalize(&canonical_name),
+            None => normalized.to_string(),
+        };
+
+        let candidates: Vec<FoodRecord> = sqlx::query_as(&format!(
+            "SELECT {} FROM foods \
+             WHERE lower(description) LIKE ANY($2) OR $1 <% lower(description) \
+             ORDER BY word_similarity($1, lower(description)) DESC, length(description) \
+             LIMIT $3",
+            FOOD_COLUMNS
+        ))
+        .bind(&name)
+        .bind(ingredient_matcher::search_patterns(&name))
+        .bind(CANDIDATE_LIMIT)
+        .fetch_all(&self.pool)
+        .await?;
+
+        match ingredient_matcher::best_match(&name, candidates, |food| &food.description) {
+            Some((confidence, food)) => Ok(Some(self.with_details(food, confidence).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn with_details(&self, mut food: FoodRecord, confidence: f32) -> Result<FoodMatch> {
+        food.micronutrients = sqlx::query_as("SELECT name, unit, amount_per_100g FROM food_nutrients WHERE fdc_id = $1")
+            .bind(food.fdc_id)
+            .fetch_all(&self.pool)
+            .await?;
+        food.portions = sqlx::query_as(
+            "SELECT amount, measure_unit, modifier, gram_weight FROM food_portions WHERE fdc_id = $1 ORDER BY id",
+        )
+        .bind(food.fdc_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(FoodMatch {
+            food: Arc::new(food),
+            confidence,
+        })
+    }
+}
@@ -0,0 +1,249 @@
+// Glycemic index reference values and glycemic load of meals and days
+//
+// Glycemic load = GI x available carbohydrate (carbohydrate minus fiber) / 100. GI values live
+// in `foods.glycemic_index`; the importer fills them from `data/glycemic_index.json`, matched
+// on FDC descriptions, without touching values entered by hand.
+
+use anyhow::Result;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+use crate::models::{GlycemicLoad, GlycemicRating};
+
+const BUNDLED_VALUES: &str = include_str!("../data/glycemic_index.json");
+
+/// Ingredients with less available carbohydrate than this per 100 g have no meaningful load
+/// and are not reported when their GI is unknown.
+const MIN_AVAILABLE_CARBS_PER_100G: f32 = 2.0;
+
+/// Loads at or below the first value rate low and at or above the second high; per meal (or
+/// serving) and per day.
+const MEAL_THRESHOLDS: (f32, f32) = (10.0, 20.0);
+const DAILY_THRESHOLDS: (f32, f32) = (80.0, 120.0);
+
+#[derive(Debug, Deserialize)]
+struct ReferenceValues {
+    /// FDC food category -> GI used when no food pattern matches
+    category_defaults: HashMap<String, f32>,
+    /// Later entries override earlier ones, so specific patterns follow general ones.
+    foods: Vec<ReferenceFood>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReferenceFood {
+    /// SQL LIKE pattern on the lowercased FDC description
+    pattern: String,
+    glycemic_index: f32,
+}
+
+/// Sets `foods.glycemic_index` from the bundled reference values, leaving rows whose source is
+/// neither 'reference' nor 'category' alone. Returns how many foods have a GI afterwards.
+pub async fn assign_reference_values(pool: &PgPool) -> Result<i64> {
+    let values: ReferenceValues = serde_json::from_str(BUNDLED_VALUES)?;
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "UPDATE foods SET glycemic_index = NULL, glycemic_index_source = NULL \
+         WHERE glycemic_index_source IN ('reference', 'category')",
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    for (category, glycemic_index) in &values.category_defaults {
+        sqlx::query(
+            "UPDATE foods SET glycemic_index = $2, glycemic_index_source = 'category' \
+             WHERE food_category = $1 AND glycemic_index_source IS NULL",
+        )
+        .bind(category)
+        .bind(glycemic_index)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for food in &values.foods {
+        sqlx::query(
+            "UPDATE foods SET glycemic_index = $2, glycemic_index_source = 'reference' \
+             WHERE lower(description) LIKE $1 \
+               AND (glycemic_index_source IS NULL OR glycemic_index_source IN ('reference', 'category'))",
+        )
+        .bind(&food.pattern)
+        .bind(food.glycemic_index)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let (assigned,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM foods WHERE glycemic_index IS NOT NULL")
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(assigned)
+}
+
+/// Carbohydrate minus fiber, never negative: FDC foods can list more fiber than carbohydrate
+/// when the two were analyzed separately.
+pub fn available_carbohydrates(carbohydrates: f32, fiber: f32) -> f32 {
+    (carbohydrates - fiber).max(0.0)
+}
+
+/// Running totals for the glycemic load of one meal.
+#[derive(Debug, Default)]
+pub struct LoadTotals {
+    load: f32,
+    /// Available carbohydrate of the ingredients with a known GI
+    carbs_with_gi: f32,
+    available_carbs: f32,
+    without_gi: Vec<String>,
+}
+
+impl LoadTotals {
+    /// Adds `available_carbs` g of an ingredient whose food has `available_per_100g` g per 100 g.
+    pub fn add(&mut self, ingredient: &str, glycemic_index: Option<f32>, available_carbs: f32, available_per_100g: f32) {
+        self.available_carbs += available_carbs;
+        match glycemic_index {
+            Some(gi) => {
+                self.load += gi * available_carbs / 100.0;
+                self.carbs_with_gi += available_carbs;
+            }
+            None if available_per_100g >= MIN_AVAILABLE_CARBS_PER_100G => self.without_gi.push(ingredient.to_string()),
+            None => {}
+        }
+    }
+
+    /// The meal's load, scaled to the portion eaten.
+    pub fn meal(self, portion_size: f32) -> GlycemicLoad {
+        let glycemic_load = self.load * portion_size;
+        GlycemicLoad {
+            glycemic_load,
+            glycemic_index: (self.carbs_with_gi > 0.0).then(|| self.load / self.carbs_with_gi * 100.0),
+            available_carbohydrates: self.available_carbs * portion_size,
+            rating: rate(glycemic_load, MEAL_THRESHOLDS),
+            ingredients_without_gi: self.without_gi,
+        }
+    }
+}
+
+/// The load of a day's meals, rated against daily thresholds.
+pub fn daily<'a>(meals: impl IntoIterator<Item = &'a GlycemicLoad>) -> GlycemicLoad {
+    let mut load = 0.0;
+    let mut carbs_with_gi = 0.0;
+    let mut available_carbs = 0.0;
+    let mut without_gi = Vec::new();
+    for meal in meals {
+        load += meal.glycemic_load;
+        if let Some(gi) = meal.glycemic_index.filter(|gi| *gi > 0.0) {
+            carbs_with_gi += meal.glycemic_load / gi * 100.0;
+        }
+        available_carbs += meal.available_carbohydrates;
+        for ingredient in &meal.ingredients_without_gi {
+            if !without_gi.contains(ingredient) {
+                without_gi.push(ingredient.clone());
+            }
+        }
+    }
+
+    GlycemicLoad {
+        glycemic_load: load,
+        glycemic_index: (carbs_with_gi > 0.0).then(|| load / carbs_with_gi * 100.0),
+        available_carbohydrates: available_carbs,
+        rating: rate(load, DAILY_THRESHOLDS),
+        ingredients_without_gi: without_gi,
+    }
+}
+
+fn rate(load: f32, (low, high): (f32, f32)) -> GlycemicRating {
+    if load <= low {
+        GlycemicRating::Low
+    } else if load >= high {
+        GlycemicRating::High
+    } else {
+        GlycemicRating::Medium
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn available_carbohydrate_is_never_negative() {
+        assert_eq!(available_carbohydrates(30.0, 4.0), 26.0);
+        assert_eq!(available_carbohydrates(3.0, 3.0), 0.0);
+        assert_eq!(available_carbohydrates(2.0, 9.0), 0.0);
+    }
+
+    #[test]
+    fn meal_load_is_gi_times_available_carbohydrate() {
+        let mut totals = LoadTotals::default();
+        // 150 g white rice (GI 73, 28 g available per 100 g) and 40 g lentils (GI 32)
+        totals.add("white rice", Some(73.0), 42.0, 28.0);
+        totals.add("lentils", Some(32.0), 8.0, 20.0);
+        totals.add("olive oil", None, 0.0, 0.0);
+        totals.add("mystery flour", None, 10.0, 70.0);
+
+        let meal = totals.meal(0.5);
+        assert!((meal.glycemic_load - (73.0 * 42.0 + 32.0 * 8.0) / 100.0 * 0.5).abs() < 0.001);
+        // GI weighted by the carbohydrate of ingredients with a known GI
+        assert!((meal.glycemic_index.unwrap() - (73.0 * 42.0 + 32.0 * 8.0) / 50.0).abs() < 0.001);
+        assert_eq!(meal.available_carbohydrates, 30.0);
+        assert_eq!(meal.ingredients_without_gi, ["mystery flour"]);
+    }
+
+    #[test]
+    fn meals_without_gi_have_no_index() {
+        let mut totals = LoadTotals::default();
+        totals.add("cucumber", None, 1.5, 1.5);
+        let meal = totals.meal(1.0);
+        assert_eq!(meal.glycemic_load, 0.0);
+        assert_eq!(meal.glycemic_index, None);
+        assert!(meal.ingredients_without_gi.is_empty());
+        assert!(matches!(meal.rating, GlycemicRating::Low));
+    }
+
+    #[test]
+    fn meal_ratings_change_at_10_and_20() {
+        let rating = |load: f32| {
+            let mut totals = LoadTotals::default();
+            totals.add("sugar", Some(100.0), load, 100.0);
+            totals.meal(1.0).rating
+        };
+        assert!(matches!(rating(10.0), GlycemicRating::Low));
+        assert!(matches!(rating(10.5), GlycemicRating::Medium));
+        assert!(matches!(rating(19.5), GlycemicRating::Medium));
+        assert!(matches!(rating(20.0), GlycemicRating::High));
+    }
+
+    #[test]
+    fn daily_load_sums_the_meals() {
+        let meal = |gi: f32, carbs: f32, without_gi: &[&str]| {
+            let mut totals = LoadTotals::default();
+            totals.add("food", Some(gi), carbs, 50.0);
+            for ingredient in without_gi {
+                totals.add(ingredient, None, 5.0, 50.0);
+            }
+            totals.meal(1.0)
+        };
+        let meals = [meal(50.0, 60.0, &["bread"]), meal(70.0, 40.0, &["bread", "dates"]), meal(40.0, 50.0, &[])];
+        let day = daily(&meals);
+
+        assert!((day.glycemic_load - 78.0).abs() < 0.001);
+        assert!((day.glycemic_index.unwrap() - 78.0 / 150.0 * 100.0).abs() < 0.001);
+        assert_eq!(day.available_carbohydrates, 165.0);
+        assert_eq!(day.ingredients_without_gi, ["bread", "dates"]);
+        assert!(matches!(day.rating, GlycemicRating::Low));
+        assert!(daily(&[]).glycemic_index.is_none());
+    }
+
+    #[test]
+    fn daily_ratings_change_at_80_and_120() {
+        let rating = |load: f32| {
+            let mut totals = LoadTotals::default();
+            totals.add("sugar", Some(100.0), load, 100.0);
+            daily(&[totals.meal(1.0)]).rating
+        };
+        assert!(matches!(rating(80.0), GlycemicRating::Low));
+        assert!(matches!(rating(81.0), GlycemicRating::Medium));
+        assert!(matches!(rating(119.0), GlycemicRating::Medium));
+        assert!(matches!(rating(120.0), GlycemicRating::High));
+    }
+}
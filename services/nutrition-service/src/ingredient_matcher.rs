@@ -0,0 +1,185 @@
+// Ingredient name normalisation and ranking of FoodData Central candidates
+use strsim::normalized_levenshtein;
+
+/// Matches scoring below this are reported as unmatched instead of guessed.
+pub const MIN_CONFIDENCE: f32 = 0.6;
+
+/// Two words closer than this (normalised edit distance) count as the same word,
+/// which absorbs typos and spelling variants ("yoghurt"/"yogurt").
+const WORD_SIMILARITY: f64 = 0.75;
+
+/// Preparation, size and quality words that do not change which food an ingredient is.
+const DESCRIPTORS: &[&str] = &[
+    "fresh", "freshly", "chopped", "diced", "minced", "sliced", "grated", "shredded", "crushed", "peeled",
+    "trimmed", "halved", "quartered", "cubed", "julienned", "finely", "roughly", "coarsely", "thinly",
+    "boneless", "skinless", "large", "small", "medium", "organic", "baby", "ripe", "packed", "heaping",
+    "extra", "virgin", "lean", "optional", "divided", "softened", "melted", "room", "temperature", "to",
+    "taste",
+];
+
+const STOPWORDS: &[&str] = &["a", "an", "and", "of", "or", "the", "with", "in", "for"];
+
+/// Words that make up a food's identity, without stopwords and in singular form.
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !(c.is_alphanumeric() || c == '-'))
+        .filter(|w| !w.is_empty() && !STOPWORDS.contains(w))
+        .map(singularize)
+        .collect()
+}
+
+fn singularize(word: &str) -> String {
+    if word.len() <= 3 || word.ends_with("ss") || word.ends_with("us") {
+        return word.to_string();
+    }
+    if let Some(stem) = word.strip_suffix("ies") {
+        return format!("{}y", stem);
+    }
+    for suffix in ["oes", "ches", "shes", "sses", "xes"] {
+        if word.ends_with(suffix) {
+            return word[..word.len() - 2].to_string();
+        }
+    }
+    word.strip_suffix('s').unwrap_or(word).to_string()
+}
+
+/// Canonical form of an ingredient name: lowercase, singular, without parenthesised notes,
+/// preparation words or adjectives. "2 Chicken Breasts (boneless, skinless)" -> "chicken breast".
+pub fn normalize(name: &str) -> String {
+    let mut without_notes = String::with_capacity(name.len());
+    let mut depth = 0;
+    for c in name.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = (depth - 1).max(0),
+            _ if depth == 0 => without_notes.push(c),
+            _ => {}
+        }
+    }
+
+    words(&without_notes)
+        .into_iter()
+        .filter(|w| !DESCRIPTORS.contains(&w.as_str()) && !w.chars().all(|c| c.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// LIKE patterns that find candidate descriptions for a normalised name (spelling variants
+/// are left to the trigram search). The stems drop a trailing "y"/"e" so "berry" also finds
+/// "strawberries".
+pub fn search_patterns(normalized: &str) -> Vec<String> {
+    normalized
+        .split_whitespace()
+        .map(|w| {
+            let stem = if w.len() > 3 { w.trim_end_matches(['y', 'e']) } else { w };
+            format!("%{}%", stem.replace(['%', '_'], ""))
+        })
+        .collect()
+}
+
+fn same_word(a: &str, b: &str) -> bool {
+    a == b || normalized_levenshtein(a, b) >= WORD_SIMILARITY
+}
+
+/// Confidence (0-1) that an FDC `description` is the food called `normalized`.
+///
+/// Mostly the share of the name's words found in the description, plus credit when the main
+/// food (the part before the first comma) is one of them and when the description adds few
+/// other words. Raw foods win ties, since amounts are usually given raw.
+pub fn score(normalized: &str, description: &str) -> f32 {
+    let query: Vec<&str> = normalized.split_whitespace().collect();
+    if query.is_empty() {
+        return 0.0;
+    }
+    let described = words(description);
+    let head = words(description.split(',').next().unwrap_or_default());
+
+    let found = query.iter().filter(|q| described.iter().any(|d| same_word(q, d))).count();
+    let coverage = found as f32 / query.len() as f32;
+    let head_matches = head.iter().any(|h| query.iter().any(|q| same_word(q, h)));
+    let extra = described.iter().filter(|d| !query.iter().any(|q| same_word(q, d))).count();
+    let precision = 1.0 - extra as f32 / described.len().max(1) as f32;
+    let raw = described.iter().any(|d| d == "raw");
+
+    let score = 0.75 * coverage
+        + if head_matches { 0.15 } else { 0.0 }
+        + 0.1 * precision
+        + if raw { 0.02 } else { 0.0 };
+    score.min(1.0)
+}
+
+/// The candidate whose description best matches `normalized`, with its confidence, or `None`
+/// when none reaches `MIN_CONFIDENCE`. Of equally good matches the shortest description wins.
+pub fn best_match<T>(normalized: &str, candidates: Vec<T>, description: impl Fn(&T) -> &str) -> Option<(f32, T)> {
+    candidates
+        .into_iter()
+        .map(|candidate| (score(normalized, description(&candidate)), candidate))
+        .filter(|(confidence, _)| *confidence >= MIN_CONFIDENCE)
+        .max_by(|(a, ca), (b, cb)| a.total_cmp(b).then(description(cb).len().cmp(&description(ca).len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHICKEN: &[&str] = &[
+        "Soup, chicken noodle, canned",
+        "Chicken, broilers or fryers, thigh, meat only, raw",
+        "Chicken, broilers or fryers, breast, meat and skin, raw",
+        "Chicken, broilers or fryers, breast, meat only, cooked, roasted",
+        "Chicken, broilers or fryers, breast, meat only, raw",
+        "Turkey, whole, breast, meat only, raw",
+    ];
+
+    #[test]
+    fn normalizes_ingredient_names() {
+        assert_eq!(normalize("Chicken Breasts"), "chicken breast");
+        assert_eq!(normalize("boneless skinless chicken breast"), "chicken breast");
+        assert_eq!(normalize("2 Chicken Breasts (boneless, skinless)"), "chicken breast");
+        assert_eq!(normalize("baby spinach"), "spinach");
+        assert_eq!(normalize("Tomatoes"), "tomato");
+        assert_eq!(normalize("fresh, chopped"), "");
+    }
+
+    #[test]
+    fn search_patterns_use_word_stems() {
+        assert_eq!(search_patterns("chicken breast"), ["%chicken%", "%breast%"]);
+        assert_eq!(search_patterns("strawberry"), ["%strawberr%"]);
+        assert_eq!(search_patterns("rye"), ["%rye%"]);
+        assert_eq!(search_patterns("100%_bran"), ["%100bran%"]);
+    }
+
+    #[test]
+    fn ranks_the_named_food_first() {
+        let name = normalize("boneless skinless chicken breast");
+        let scores: Vec<f32> = CHICKEN.iter().map(|d| score(&name, d)).collect();
+        // raw breast > roasted breast > other chicken parts > other birds > dishes with chicken
+        assert!(scores[4] > scores[3]);
+        assert!(scores[3] > scores[1]);
+        assert!(scores[1] > scores[5]);
+        assert!(scores[5] >= scores[0]);
+
+        let spinach = normalize("baby spinach");
+        assert!(score(&spinach, "Spinach, raw") > score(&spinach, "Spinach, frozen, chopped or leaf, unprepared"));
+        assert!(score(&spinach, "Spinach, raw") > score(&spinach, "Pasta, spinach, cooked"));
+
+        // Spelling variants count as the same word
+        assert_eq!(score("yoghurt", "Yogurt, plain, whole milk"), score("yogurt", "Yogurt, plain, whole milk"));
+    }
+
+    #[test]
+    fn picks_the_best_candidate() {
+        let (confidence, description) = best_match("chicken breast", CHICKEN.to_vec(), |d| d).unwrap();
+        // Ties with "meat and skin" and wins on length
+        assert_eq!(description, "Chicken, broilers or fryers, breast, meat only, raw");
+        assert!((MIN_CONFIDENCE..=1.0).contains(&confidence));
+    }
+
+    #[test]
+    fn leaves_poor_matches_unmatched() {
+        assert!(score("spinach", "Beef, ground, raw") < MIN_CONFIDENCE);
+        assert!(best_match("spinach", vec!["Beef, ground, raw", "Soup, chicken noodle, canned"], |d| d).is_none());
+        assert!(best_match("spinach", Vec::<&str>::new(), |d| d).is_none());
+        assert_eq!(score("", "Spinach, raw"), 0.0);
+    }
+}
@@ -13,9 +13,21 @@ use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
 mod ai_engine;
+mod allergens;
+mod cooking;
+mod diets;
+mod dri;
+mod energy_calculator;
+mod fdc_import;
+mod food_database;
+mod glycemic;
+mod ingredient_matcher;
+mod meal_parser;
 mod nutrition_analyzer;
 mod recommendation_engine;
+mod scoring;
 mod models;
+mod units;
 mod database;
 
 use ai_engine::NutritionAI;
@@ -39,6 +51,9 @@ pub struct MealIngredient {
     pub amount: f32,
     pub unit: String,
     pub preparation: Option<String>,
+    /// `amount` is the weight after cooking rather than the raw weight.
+    #[serde(default)]
+    pub cooked_weight: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,20 +72,38 @@ pub struct Micronutrient {
     pub name: String,
     pub amount: f32,
     pub unit: String,
+    /// Share of the consumer's RDA/AI; 0 when there is no reference value.
     pub daily_value_percentage: f32,
+    pub reference_intake: Option<f32>,
+    /// "RDA" or "AI"
+    pub reference_basis: Option<String>,
+    pub upper_limit: Option<f32>,
+    pub exceeds_upper_limit: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DietaryCompliance {
-    pub keto_friendly: bool,
-    pub vegan: bool,
-    pub vegetarian: bool,
-    pub gluten_free: bool,
-    pub dairy_free: bool,
-    pub low_sodium: bool,
+    /// One entry per configured diet (`data/diets/`)
+    pub diets: Vec<DietCompliance>,
     pub anti_inflammatory_score: f32,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DietCompliance {
+    pub diet: String,
+    pub compliant: bool,
+    pub violations: Vec<DietViolation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DietViolation {
+    pub reason: String,
+    /// Ingredients that break the rule, or that contribute most to a nutrient over its limit
+    pub ingredients: Vec<String>,
+    /// Measured value and limit for nutrient rules
+    pub detail: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnvironmentalImpact {
     pub carbon_footprint: f32,
@@ -97,6 +130,9 @@ pub struct MealNutritionBreakdown {
     pub meal_type: String,
     pub nutrition: BasicNutrition,
     pub percentage_of_daily_goals: f32,
+    pub glycemic_load: f32,
+    /// 0-100, see `MealQuality`
+    pub nutrition_score: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -127,6 +163,8 @@ pub struct MealAnalysisRequest {
     pub ingredients: Vec<MealIngredient>,
     pub portion_size: f32,
     pub cooking_method: Option<String>,
+    #[serde(default)]
+    pub consumer: Option<models::ConsumerProfile>,
 }
 
 #[derive(Debug, Serialize)]
@@ -140,6 +178,8 @@ pub struct DailyNutritionRequest {
     pub user_id: Uuid,
     pub date: DateTime<Utc>,
     pub meals: Vec<LoggedMeal>,
+    #[serde(default)]
+    pub consumer: Option<models::ConsumerProfile>,
 }
 
 #[derive(Debug, Serialize)]
@@ -150,7 +190,9 @@ pub struct DailyNutritionResponse {
 
 #[derive(Debug, Serialize)]
 pub struct HealthInsights {
-    pub overall_score: f32,
+    /// Serving-weighted `nutrition_score` of the recipes logged in the period; `null` when
+    /// none of them has been scored.
+    pub overall_score: Option<f32>,
     pub insights: Vec<String>,
     pub recommendations: Vec<String>,
 }
@@ -171,14 +213,32 @@ async fn main() -> anyhow::Result<()> {
     info!("Starting Nutrition service...");
 
     let database_url = std::env::var("DATABASE_URL")?;
-    let redis_url = std::env::var("REDIS_URL")?;
-
     let db = sqlx::PgPool::connect(&database_url).await?;
+
+    // `nutrition-service import-fdc <path>` loads a FoodData Central download and exits
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("import-fdc") {
+        let path = args
+            .get(2)
+            .ok_or_else(|| anyhow::anyhow!("usage: nutrition-service import-fdc <json file | csv directory>"))?;
+        let summary = fdc_import::import_path(&db, std::path::Path::new(path)).await?;
+        info!(
+            "Imported {} foods ({} nutrient values, {} portions, {} with a glycemic index) from {}",
+            summary.foods, summary.nutrients, summary.portions, summary.glycemic_indexes, path
+        );
+        return Ok(());
+    }
+
+    let redis_url = std::env::var("REDIS_URL")?;
     let redis = redis::Client::open(redis_url)?;
 
     // Initialize AI components
     let ai_engine = Arc::new(NutritionAI::new().await?);
-    let analyzer = Arc::new(NutritionalAnalyzer::new());
+    let mut diets = diets::DietRules::bundled()?;
+    if let Ok(dir) = std::env::var("DIET_RULES_DIR") {
+        diets.load_dir(std::path::Path::new(&dir))?;
+    }
+    let analyzer = Arc::new(NutritionalAnalyzer::new(db.clone(), diets));
     let recommendation_engine = Arc::new(RecommendationEngine::new(ai_engine.clone()));
 
     let app_state = AppState {
@@ -194,10 +254,15 @@ async fn main() -> anyhow::Result<()> {
         .route("/health", get(health_check))
         .route("/analyze/meal", post(analyze_meal))
         .route("/analyze/daily", post(analyze_daily_nutrition))
+        .route("/analyze/recipe/:id", post(analyze_recipe))
         .route("/analyze/trends", get(analyze_nutrition_trends))
+        .route("/parse/meal", post(parse_meal))
+        .route("/diets", get(list_diets))
         .route("/recommendations/meals", post(recommend_meals))
         .route("/recommendations/supplements", post(recommend_supplements))
         .route("/insights/health", get(generate_health_insights))
+        .route("/goals/calculate", post(calculate_nutrition_goals))
+        .route("/goals/seed", post(seed_nutrition_goals))
         .layer(CorsLayer::permissive())
         .with_state(app_state);
 
@@ -216,10 +281,11 @@ async fn analyze_meal(
     axum::extract::State(state): axum::extract::State<AppState>,
     axum::Json(request): axum::Json<MealAnalysisRequest>,
 ) -> Result<axum::Json<MealAnalysisResponse>, axum::http::StatusCode> {
+    let consumer = with_profile_consumer(&state.db, request.user_id, request.consumer).await?;
     let analysis = state.analyzer
-        .analyze_meal(&request.ingredients, request.portion_size, request.cooking_method.as_deref())
+        .analyze_meal(&request.ingredients, request.portion_size, request.cooking_method.as_deref(), Some(&consumer))
         .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(analysis_error)?;
 
     let insights = state.ai_engine
         .generate_health_insights(&analysis, request.user_id)
@@ -248,12 +314,14 @@ async fn analyze_daily_nutrition(
     };
 
     let mut meal_breakdown = Vec::new();
+    let mut meal_loads = Vec::new();
+    let consumer = with_profile_consumer(&state.db, request.user_id, request.consumer).await?;
 
     for meal in &request.meals {
         let meal_analysis = state.analyzer
-            .analyze_meal(&meal.ingredients, 1.0, None)
+            .analyze_meal(&meal.ingredients, 1.0, None, Some(&consumer))
             .await
-            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+            .map_err(analysis_error)?;
 
         total_nutrition.calories += meal_analysis.basic_nutrition.calories;
         total_nutrition.protein += meal_analysis.basic_nutrition.protein;
@@ -267,7 +335,10 @@ async fn analyze_daily_nutrition(
             meal_type: "main".to_string(),
             nutrition: meal_analysis.basic_nutrition.clone(),
             percentage_of_daily_goals: 25.0, // Placeholder
+            glycemic_load: meal_analysis.glycemic_load.glycemic_load,
+            nutrition_score: meal_analysis.quality.nutrition_score,
         });
+        meal_loads.push(meal_analysis.glycemic_load);
     }
 
     // Generate mock user goals for demonstration
@@ -281,15 +352,20 @@ async fn analyze_daily_nutrition(
         sodium: 2300.0,
     };
 
+    let protein_adherence = total_nutrition.protein / user_goals.protein * 100.0;
+    let carb_adherence = total_nutrition.carbohydrates / user_goals.carbohydrates * 100.0;
+    let fat_adherence = total_nutrition.fat / user_goals.fat * 100.0;
+
     let analysis = models::DailyNutritionAnalysisInternal {
         total_nutrition: total_nutrition.clone(),
         meal_breakdown,
         goal_adherence: GoalAdherence {
-            overall_score: 85.0,
-            protein_adherence: total_nutrition.protein / user_goals.protein * 100.0,
-            carb_adherence: total_nutrition.carbohydrates / user_goals.carbohydrates * 100.0,
-            fat_adherence: total_nutrition.fat / user_goals.fat * 100.0,
+            overall_score: overall_adherence(&[protein_adherence, carb_adherence, fat_adherence]),
+            protein_adherence,
+            carb_adherence,
+            fat_adherence,
         },
+        glycemic_load: glycemic::daily(&meal_loads),
         next_meal_suggestions: generate_meal_suggestions(&total_nutrition, &user_goals),
         hydration_reminder: total_nutrition.sodium > 1500.0,
         sleep_nutrition_impact: SleepNutritionImpact {
@@ -309,6 +385,37 @@ async fn analyze_daily_nutrition(
     }))
 }
 
+/// Analyzes a stored recipe per serving and saves the result, `nutrition_score` included, to
+/// `recipe_nutrition`. The gateway calls this whenever it stores a recipe.
+async fn analyze_recipe(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::extract::Path(recipe_id): axum::extract::Path<Uuid>,
+) -> Result<axum::Json<models::RecipeNutrition>, axum::http::StatusCode> {
+    let (servings, ingredients) = database::load_recipe_ingredients(&state.db, recipe_id)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+    if ingredients.is_empty() {
+        return Err(axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let analysis = state.analyzer
+        .analyze_meal(&ingredients, 1.0 / servings as f32, None, None)
+        .await
+        .map_err(analysis_error)?;
+
+    database::save_recipe_nutrition(&state.db, recipe_id, &analysis.basic_nutrition, analysis.quality.nutrition_score)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(axum::Json(models::RecipeNutrition {
+        recipe_id,
+        servings,
+        per_serving: analysis.basic_nutrition,
+        nutrition_score: analysis.quality.nutrition_score,
+    }))
+}
+
 async fn analyze_nutrition_trends(
     axum::extract::State(_state): axum::extract::State<AppState>,
     axum::extract::Query(_params): axum::extract::Query<std::collections::HashMap<String, String>>,
@@ -319,11 +426,53 @@ async fn analyze_nutrition_trends(
     })))
 }
 
+/// Reads a free-text meal description into ingredients for `/analyze/meal`.
+async fn parse_meal(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::Json(request): axum::Json<models::MealParseRequest>,
+) -> Result<axum::Json<models::ParsedMeal>, axum::http::StatusCode> {
+    if request.text.trim().is_empty() {
+        return Err(axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let parsed = meal_parser::parse_meal(state.analyzer.foods(), &request.text)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(axum::Json(parsed))
+}
+
+async fn list_diets(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> axum::Json<Vec<models::DietSummary>> {
+    let diets = state.analyzer
+        .diets()
+        .diets()
+        .iter()
+        .map(|diet| models::DietSummary {
+            name: diet.name.clone(),
+            description: diet.description.clone(),
+        })
+        .collect();
+    axum::Json(diets)
+}
+
 async fn recommend_meals(
-    axum::extract::State(_state): axum::extract::State<AppState>,
-    axum::Json(_request): axum::Json<serde_json::Value>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::Json(mut request): axum::Json<MealRecommendationRequest>,
 ) -> Result<axum::Json<Vec<MealRecommendation>>, axum::http::StatusCode> {
-    Ok(axum::Json(vec![]))
+    if request.allergies.is_none() {
+        let allergies = database::load_user_allergies(&state.db, request.user_id)
+            .await
+            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+        request.allergies = Some(allergies);
+    }
+
+    let recommendations = state.recommendation_engine
+        .recommend_meals(&request)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(axum::Json(recommendations))
 }
 
 async fn recommend_supplements(
@@ -333,18 +482,121 @@ async fn recommend_supplements(
     Ok(axum::Json(vec![]))
 }
 
+/// Summarizes the quality of the recipes the user logged recently, from their stored scores.
 async fn generate_health_insights(
-    axum::extract::State(_state): axum::extract::State<AppState>,
-    axum::extract::Query(_params): axum::extract::Query<std::collections::HashMap<String, String>>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<models::HealthInsightsQuery>,
 ) -> Result<axum::Json<HealthInsights>, axum::http::StatusCode> {
+    let days = query.days.unwrap_or(7).clamp(1, 365);
+    let (overall_score, meals) = database::recent_meal_quality(&state.db, query.user_id, days)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut insights = Vec::new();
+    let mut recommendations = Vec::new();
+    match overall_score {
+        Some(score) => {
+            insights.push(format!(
+                "{} logged meals in the last {} days scored {:.0} of 100 on average",
+                meals, days, score
+            ));
+            if score < 50.0 {
+                recommendations.push("Swap some meals for recipes with a higher nutrition score".to_string());
+            }
+        }
+        None => recommendations.push(format!("Log meals from your recipes to get a score for the last {} days", days)),
+    }
+
     Ok(axum::Json(HealthInsights {
-        overall_score: 85.0,
-        insights: vec!["Good protein intake".to_string()],
-        recommendations: vec!["Add more vegetables".to_string()],
+        overall_score,
+        insights,
+        recommendations,
     }))
 }
 
+async fn calculate_nutrition_goals(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::Json(request): axum::Json<models::NutritionGoalsRequest>,
+) -> Result<axum::Json<models::NutritionGoalCalculation>, axum::http::StatusCode> {
+    let calculation = state.recommendation_engine
+        .calculate_nutrition_goals(&request)
+        .await
+        .map_err(goals_error)?;
+
+    Ok(axum::Json(calculation))
+}
+
+/// Calculates goals and stores them as the user's active, AI-created `nutrition_goals` row.
+async fn seed_nutrition_goals(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::Json(request): axum::Json<models::NutritionGoalsRequest>,
+) -> Result<axum::Json<models::SeededNutritionGoal>, axum::http::StatusCode> {
+    let calculation = state.recommendation_engine
+        .calculate_nutrition_goals(&request)
+        .await
+        .map_err(goals_error)?;
+
+    let goal_id = database::insert_ai_nutrition_goal(&state.db, request.user_id, &calculation)
+        .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(axum::Json(models::SeededNutritionGoal { goal_id, calculation }))
+}
+
 // Helper functions
+
+/// The request's consumer profile, with the allergies and age from the user's profile
+/// filling in whatever it does not state itself.
+async fn with_profile_consumer(
+    db: &sqlx::PgPool,
+    user_id: Uuid,
+    consumer: Option<models::ConsumerProfile>,
+) -> Result<models::ConsumerProfile, axum::http::StatusCode> {
+    let mut consumer = consumer.unwrap_or_default();
+    if consumer.allergies.is_none() || consumer.age_years.is_none() {
+        let profile = database::load_profile_consumer(db, user_id)
+            .await
+            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+        consumer.allergies = consumer.allergies.or(profile.allergies);
+        consumer.age_years = consumer.age_years.or(profile.age_years);
+    }
+    Ok(consumer)
+}
+
+/// Unconvertible amounts and unknown or missing cooking methods are the caller's mistake (422);
+/// anything else is ours.
+fn analysis_error(error: anyhow::Error) -> axum::http::StatusCode {
+    if error.is::<units::UnitConversionError>()
+        || error.is::<cooking::UnknownCookingMethod>()
+        || error.is::<cooking::CookedWeightWithoutMethod>()
+    {
+        tracing::warn!("Rejected meal analysis: {}", error);
+        axum::http::StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// Out-of-range body metrics are rejected (422) instead of producing nonsense targets.
+fn goals_error(error: anyhow::Error) -> axum::http::StatusCode {
+    if error.is::<energy_calculator::InvalidBodyMetrics>() {
+        tracing::warn!("Rejected goal calculation: {}", error);
+        axum::http::StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// 100 when every macro meets its goal exactly, falling by one point per percent of
+/// shortfall or excess, averaged over the macros.
+fn overall_adherence(adherences: &[f32]) -> f32 {
+    if adherences.is_empty() {
+        return 0.0;
+    }
+    let total: f32 = adherences.iter().map(|a| (100.0 - (a - 100.0).abs()).max(0.0)).sum();
+    total / adherences.len() as f32
+}
+
 fn generate_meal_suggestions(current_nutrition: &BasicNutrition, goals: &BasicNutrition) -> Vec<MealSuggestion> {
     let mut suggestions = Vec::new();
     
@@ -387,6 +639,10 @@ pub struct MealRecommendationRequest {
     pub dietary_restrictions: Vec<String>,
     pub preferences: Vec<String>,
     pub target_nutrition: Option<BasicNutrition>,
+    /// Meals conflicting with any of these are never recommended; read from the user's
+    /// profile when not given.
+    #[serde(default)]
+    pub allergies: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
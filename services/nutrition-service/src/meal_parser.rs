@@ -0,0 +1,451 @@
+// Free-text meals ("2 scrambled eggs, a slice of toast with butter") read into ingredients
+//
+// The text is split into items at commas, semicolons, "and", "with" and "plus", keeping dishes
+// such as "mac and cheese" whole. Each item is read as a quantity ("2", "1 1/2", "½", "2-3",
+// "a", "half a"), a unit (mass and volume units, household measures, "large", "slice"; a glass,
+// mug, bowl or can is a typical volume), preparation words and the food. Items without a
+// quantity are one whole item or serving; those after "with" are a spoonful. Foods are resolved
+// against the ingredient database, and an amount the food's portions cannot weigh falls back to
+// a typical weight, so the ingredients can be analysed as they are.
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::cooking::CookingMethod;
+use crate::food_database::FoodDatabase;
+use crate::models::{ParsedMeal, ParsedMealItem};
+use crate::units::{self, Unit};
+use crate::MealIngredient;
+
+/// Dishes whose name contains a separator word.
+const WHOLE_DISHES: &[&str] = &[
+    "mac and cheese",
+    "macaroni and cheese",
+    "fish and chips",
+    "salt and pepper",
+    "sweet and sour",
+    "bread and butter",
+    "half and half",
+    "peanut butter and jelly",
+    "franks and beans",
+    "rice and beans",
+    "pork and beans",
+    "surf and turf",
+];
+
+const SEPARATORS: &[&str] = &[" and ", " plus ", " & ", " + "];
+
+const NUMBER_WORDS: &[(&str, f32)] = &[
+    ("a", 1.0),
+    ("an", 1.0),
+    ("one", 1.0),
+    ("two", 2.0),
+    ("three", 3.0),
+    ("four", 4.0),
+    ("five", 5.0),
+    ("six", 6.0),
+    ("seven", 7.0),
+    ("eight", 8.0),
+    ("nine", 9.0),
+    ("ten", 10.0),
+    ("eleven", 11.0),
+    ("twelve", 12.0),
+    ("half", 0.5),
+    ("quarter", 0.25),
+    ("couple", 2.0),
+    ("few", 3.0),
+    ("several", 3.0),
+];
+
+const UNICODE_FRACTIONS: &[(char, &str)] = &[
+    ('½', "1/2"),
+    ('⅓', "1/3"),
+    ('⅔', "2/3"),
+    ('¼', "1/4"),
+    ('¾', "3/4"),
+    ('⅕', "1/5"),
+    ('⅛', "1/8"),
+    ('⅜', "3/8"),
+    ('⅝', "5/8"),
+    ('⅞', "7/8"),
+];
+
+/// Household measures and sizes the food's FDC portions are expected to weigh.
+const COUNT_UNITS: &[&str] = &[
+    "slice", "piece", "clove", "stalk", "sprig", "head", "bunch", "fillet", "breast", "thigh", "drumstick", "wing",
+    "strip", "rasher", "wedge", "scoop", "stick", "pat", "patty", "link", "square", "bar", "large", "medium",
+    "small", "serving", "portion",
+];
+
+/// Containers, as millilitres.
+const CONTAINERS: &[(&str, f32)] = &[
+    ("glass", 240.0),
+    ("mug", 300.0),
+    ("bowl", 300.0),
+    ("can", 355.0),
+    ("bottle", 500.0),
+    ("handful", 60.0),
+    ("splash", 15.0),
+    ("drizzle", 5.0),
+];
+
+/// Words describing how the food was prepared; a cooking method among them sets `preparation`.
+const PREPARATION_WORDS: &[&str] = &[
+    "scrambled", "fried", "deep-fried", "stir-fried", "pan-fried", "boiled", "hard-boiled", "soft-boiled", "poached",
+    "grilled", "broiled", "baked", "roasted", "steamed", "toasted", "sauteed", "sautéed", "microwaved", "barbecued",
+    "raw", "chopped", "sliced", "diced", "mashed", "grated", "shredded", "minced", "whipped", "melted", "buttered",
+    "fresh", "plain",
+];
+
+/// Weight assumed for one item the food's portions cannot weigh, g.
+const DEFAULT_ITEM_GRAMS: f32 = 100.0;
+/// Weight assumed for one serving of an accompaniment ("with butter"), g.
+const DEFAULT_ACCOMPANIMENT_GRAMS: f32 = 15.0;
+/// Confidence is scaled by this when the amount was assumed rather than read.
+const ASSUMED_AMOUNT_CONFIDENCE: f32 = 0.8;
+
+/// One item of the text, before its food is resolved.
+#[derive(Debug)]
+struct MealItem {
+    text: String,
+    amount: f32,
+    unit: String,
+    /// The food, without preparation words
+    name: String,
+    /// The food as written, with them
+    described_name: String,
+    preparation: Vec<String>,
+    quantity_given: bool,
+    /// Came after "with", so defaults to a spoonful
+    accompaniment: bool,
+}
+
+pub async fn parse_meal(foods: &FoodDatabase, text: &str) -> Result<ParsedMeal> {
+    let mut ingredients = Vec::new();
+    let mut items = Vec::new();
+
+    for item in split_items(text).into_iter().filter_map(|(text, accompaniment)| parse_item(&text, accompaniment)) {
+        // Preparation words can name a different food ("scrambled eggs"), so both are tried
+        let mut best = foods.find(&item.name).await?.map(|found| (item.name.clone(), found));
+        if item.described_name != item.name {
+            if let Some(found) = foods.find(&item.described_name).await? {
+                if best.as_ref().is_none_or(|(_, b)| found.confidence >= b.confidence) {
+                    best = Some((item.described_name.clone(), found));
+                }
+            }
+        }
+
+        let mut amount = item.amount;
+        let mut unit = item.unit.clone();
+        let mut amount_assumed = !item.quantity_given;
+        let name = best.as_ref().map_or(item.name.clone(), |(name, _)| name.clone());
+        if let Some((_, found)) = &best {
+            if units::to_grams(amount, &unit, &found.food).is_err() {
+                let grams = if item.accompaniment { DEFAULT_ACCOMPANIMENT_GRAMS } else { DEFAULT_ITEM_GRAMS };
+                amount *= grams;
+                unit = "g".to_string();
+                amount_assumed = true;
+            }
+        }
+
+        let ingredient = MealIngredient {
+            ingredient_id: Uuid::new_v4(),
+            name,
+            amount,
+            unit,
+            preparation: preparation(&item.preparation),
+            cooked_weight: false,
+        };
+        items.push(ParsedMealItem {
+            text: item.text,
+            ingredient: ingredient.clone(),
+            fdc_id: best.as_ref().map(|(_, found)| found.food.fdc_id),
+            food_description: best.as_ref().map(|(_, found)| found.food.description.clone()),
+            confidence: best.as_ref().map_or(0.0, |(_, found)| {
+                found.confidence * if amount_assumed { ASSUMED_AMOUNT_CONFIDENCE } else { 1.0 }
+            }),
+            amount_assumed,
+        });
+        ingredients.push(ingredient);
+    }
+
+    Ok(ParsedMeal { ingredients, items })
+}
+
+/// A cooking method the analysis understands, else the preparation words as written.
+fn preparation(words: &[String]) -> Option<String> {
+    if words.is_empty() {
+        return None;
+    }
+    words
+        .iter()
+        .find(|w| CookingMethod::parse(w).is_some())
+        .cloned()
+        .or_else(|| Some(words.join(" ")))
+}
+
+/// The items of a meal, each flagged when it is an accompaniment: it follows "with", and neither
+/// it nor an item before it in the same list has a quantity ("with milk and sugar" vs. "with
+/// butter and a coffee").
+fn split_items(text: &str) -> Vec<(String, bool)> {
+    let mut text = format!(" {} ", text.to_lowercase().replace(['\n', ';'], ","));
+    for dish in WHOLE_DISHES {
+        text = text.replace(dish, &dish.replace(' ', "_"));
+    }
+    for separator in SEPARATORS {
+        text = text.replace(separator, " | ");
+    }
+
+    let mut items = Vec::new();
+    for part in text.split(',') {
+        for (index, chunk) in part.split(" with ").enumerate() {
+            let mut accompaniment = index > 0;
+            for piece in chunk.split('|') {
+                let piece = piece.replace('_', " ").trim().to_string();
+                if piece.is_empty() {
+                    continue;
+                }
+                accompaniment &= quantity(&tokens(&piece)).is_none();
+                items.push((piece, accompaniment));
+            }
+        }
+    }
+    items
+}
+
+/// "1½" -> "1 1/2", "200g" -> "200 g"
+fn tokens(item: &str) -> Vec<String> {
+    let mut spaced = String::with_capacity(item.len());
+    for c in item.chars() {
+        match UNICODE_FRACTIONS.iter().find(|(f, _)| *f == c) {
+            Some((_, ascii)) => {
+                spaced.push(' ');
+                spaced.push_str(ascii);
+                spaced.push(' ');
+            }
+            None => spaced.push(c),
+        }
+    }
+
+    let mut tokens = Vec::new();
+    for token in spaced.split_whitespace() {
+        let token = token.trim_matches(|c: char| !(c.is_alphanumeric() || c == '/' || c == '.' || c == '-'));
+        let digits = token.find(|c: char| c.is_alphabetic()).unwrap_or(token.len());
+        if digits > 0 && digits < token.len() && number(&token[..digits]).is_some() {
+            tokens.push(token[..digits].to_string());
+            tokens.push(token[digits..].to_string());
+        } else if !token.is_empty() {
+            tokens.push(token.to_string());
+        }
+    }
+    tokens
+}
+
+/// "2", "1.5", "1/2" or a range "2-3" (its middle).
+fn number(token: &str) -> Option<f32> {
+    if let Ok(value) = token.parse::<f32>() {
+        return (value.is_finite() && value >= 0.0).then_some(value);
+    }
+    if let Some((numerator, denominator)) = token.split_once('/') {
+        let denominator: f32 = denominator.parse().ok()?;
+        return (denominator != 0.0).then_some(numerator.parse::<f32>().ok()? / denominator);
+    }
+    let (low, high) = token.split_once('-')?;
+    Some((number(low)? + number(high)?) / 2.0)
+}
+
+fn number_word(token: &str) -> Option<f32> {
+    NUMBER_WORDS.iter().find(|(word, _)| *word == token).map(|(_, value)| *value)
+}
+
+fn singular(word: &str) -> &str {
+    for suffix in ["sses", "shes", "ches", "xes"] {
+        if word.ends_with(suffix) {
+            return &word[..word.len() - 2];
+        }
+    }
+    match word.strip_suffix('s') {
+        Some(stem) if !word.ends_with("ss") && stem.len() > 1 => stem,
+        _ => word,
+    }
+}
+
+/// Leading quantity and how many tokens it took.
+fn quantity(tokens: &[String]) -> Option<(f32, usize)> {
+    let first = tokens.first()?;
+    let mut used = 1;
+    let mut amount = match number(first) {
+        Some(value) => {
+            // A mixed number: "1 1/2"
+            if let Some(fraction) = tokens.get(1).filter(|t| t.contains('/')).and_then(|t| number(t)) {
+                used += 1;
+                value + fraction
+            } else {
+                value
+            }
+        }
+        None => number_word(first)?,
+    };
+
+    // "a half", "a couple of", "half a", "a dozen", "2 dozen"
+    while let Some(next) = tokens.get(used) {
+        match next.as_str() {
+            "half" | "quarter" | "couple" | "few" if first == "a" || first == "an" => amount = number_word(next)?,
+            "a" | "an" if first == "half" || first == "quarter" => {}
+            "dozen" => amount *= 12.0,
+            _ => break,
+        }
+        used += 1;
+    }
+    Some((amount, used))
+}
+
+/// A unit at the start of `tokens`, as the unit the analysis takes, its multiplier and how
+/// many tokens it took.
+fn unit(tokens: &[String]) -> Option<(String, f32, usize)> {
+    if let [first, second, ..] = tokens {
+        let two_words = format!("{} {}", first, second);
+        if !matches!(units::parse_unit(&two_words), Unit::Count) {
+            return Some((two_words, 1.0, 2));
+        }
+    }
+    let first = tokens.first()?;
+    if !matches!(units::parse_unit(first), Unit::Count) {
+        return Some((first.clone(), 1.0, 1));
+    }
+    let word = singular(first);
+    if let Some((_, ml)) = CONTAINERS.iter().find(|(container, _)| *container == word) {
+        return Some(("ml".to_string(), *ml, 1));
+    }
+    COUNT_UNITS.contains(&word).then(|| (word.to_string(), 1.0, 1))
+}
+
+fn parse_item(text: &str, accompaniment: bool) -> Option<MealItem> {
+    let tokens = tokens(text);
+    let (amount, mut position) = quantity(&tokens).unwrap_or((1.0, 0));
+    let quantity_given = position > 0;
+
+    let (unit, multiplier) = match unit(&tokens[position..]) {
+        Some((unit, multiplier, used)) => {
+            position += used;
+            (unit, multiplier)
+        }
+        // A bare accompaniment is a spoonful, not a whole stick of butter
+        None if accompaniment && !quantity_given => ("g".to_string(), DEFAULT_ACCOMPANIMENT_GRAMS),
+        None => (String::new(), 1.0),
+    };
+    let rest: Vec<&String> = tokens[position..]
+        .iter()
+        .skip_while(|t| matches!(t.as_str(), "of" | "a" | "an" | "the" | "some"))
+        .collect();
+
+    let preparation: Vec<String> = rest
+        .iter()
+        .filter(|t| PREPARATION_WORDS.contains(&t.as_str()))
+        .map(|t| t.to_string())
+        .collect();
+    let name: Vec<&str> = rest
+        .iter()
+        .filter(|t| !PREPARATION_WORDS.contains(&t.as_str()))
+        .map(|t| t.as_str())
+        .collect();
+    if name.is_empty() {
+        return None;
+    }
+
+    Some(MealItem {
+        text: text.to_string(),
+        amount: amount * multiplier,
+        unit,
+        name: name.join(" "),
+        described_name: rest.iter().map(|t| t.as_str()).collect::<Vec<_>>().join(" "),
+        preparation,
+        quantity_given,
+        accompaniment,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_items(text: &str, expected: &[(&str, bool)]) {
+        let items = split_items(text);
+        let items: Vec<(&str, bool)> = items.iter().map(|(item, accompaniment)| (item.as_str(), *accompaniment)).collect();
+        assert_eq!(items, expected);
+    }
+
+    #[test]
+    fn splits_items_and_flags_accompaniments() {
+        assert_items(
+            "2 scrambled eggs, a slice of whole wheat toast with butter",
+            &[("2 scrambled eggs", false), ("a slice of whole wheat toast", false), ("butter", true)],
+        );
+        assert_items(
+            "Oatmeal with milk and sugar",
+            &[("oatmeal", false), ("milk", true), ("sugar", true)],
+        );
+        assert_items(
+            "toast with butter and a coffee",
+            &[("toast", false), ("butter", true), ("a coffee", false)],
+        );
+    }
+
+    #[test]
+    fn keeps_dishes_with_separator_words_whole() {
+        assert_items(
+            "mac and cheese; a salad",
+            &[("mac and cheese", false), ("a salad", false)],
+        );
+    }
+
+    #[test]
+    fn reads_quantity_unit_preparation_and_food() {
+        let eggs = parse_item("2 scrambled eggs", false).unwrap();
+        assert_eq!(eggs.amount, 2.0);
+        assert_eq!(eggs.unit, "");
+        assert_eq!(eggs.name, "eggs");
+        assert_eq!(eggs.described_name, "scrambled eggs");
+        assert_eq!(eggs.preparation, vec!["scrambled"]);
+        assert_eq!(preparation(&eggs.preparation).as_deref(), Some("scrambled"));
+        assert!(eggs.quantity_given);
+
+        let toast = parse_item("a slice of whole wheat toast", false).unwrap();
+        assert_eq!(toast.amount, 1.0);
+        assert_eq!(toast.unit, "slice");
+        assert_eq!(toast.name, "whole wheat toast");
+        assert!(toast.preparation.is_empty());
+    }
+
+    #[test]
+    fn bare_accompaniment_is_a_spoonful() {
+        let butter = parse_item("butter", true).unwrap();
+        assert_eq!((butter.amount, butter.unit.as_str()), (DEFAULT_ACCOMPANIMENT_GRAMS, "g"));
+        assert!(!butter.quantity_given);
+        assert!(butter.accompaniment);
+
+        let toast = parse_item("toast", false).unwrap();
+        assert_eq!((toast.amount, toast.unit.as_str()), (1.0, ""));
+    }
+
+    #[test]
+    fn reads_fractions_ranges_words_and_containers() {
+        let milk = parse_item("1½ cups milk", false).unwrap();
+        assert_eq!((milk.amount, milk.unit.as_str(), milk.name.as_str()), (1.5, "cups", "milk"));
+
+        let chicken = parse_item("200g grilled chicken", false).unwrap();
+        assert_eq!((chicken.amount, chicken.unit.as_str()), (200.0, "g"));
+        assert_eq!(chicken.name, "chicken");
+
+        assert_eq!(parse_item("2-3 apples", false).unwrap().amount, 2.5);
+        assert_eq!(parse_item("half a dozen eggs", false).unwrap().amount, 6.0);
+
+        let juice = parse_item("a glass of orange juice", false).unwrap();
+        assert_eq!((juice.amount, juice.unit.as_str(), juice.name.as_str()), (240.0, "ml", "orange juice"));
+    }
+
+    #[test]
+    fn an_item_without_a_food_is_dropped() {
+        assert!(parse_item("2 cups", false).is_none());
+    }
+}
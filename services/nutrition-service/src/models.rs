@@ -1,6 +1,22 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Who a meal is analysed for; selects the dietary reference intakes.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ConsumerProfile {
+    pub age_years: Option<f32>,
+    /// `male` / `female`
+    pub sex: Option<String>,
+    #[serde(default)]
+    pub pregnant: bool,
+    #[serde(default)]
+    pub lactating: bool,
+    /// Allergies and intolerances to check the meal against ("peanuts", "dairy", "shellfish").
+    /// Read from the user's profile when not given; an empty list skips the check.
+    #[serde(default)]
+    pub allergies: Option<Vec<String>>,
+}
+
 // Internal types for analysis
 #[derive(Debug, Clone, Serialize)]
 pub struct NutritionAnalysisInternal {
@@ -8,6 +24,154 @@ pub struct NutritionAnalysisInternal {
     pub micronutrients: Vec<crate::Micronutrient>,
     pub dietary_compliance: crate::DietaryCompliance,
     pub environmental_impact: crate::EnvironmentalImpact,
+    pub ingredient_matches: Vec<IngredientMatch>,
+    /// Ingredients with no confident food match; they are left out of every total.
+    pub unmatched_ingredients: Vec<String>,
+    pub allergens: Vec<DetectedAllergen>,
+    /// Verdict against the consumer's allergies; `None` when none are known
+    pub allergy_safety: Option<AllergenSafety>,
+    pub glycemic_load: GlycemicLoad,
+    pub quality: MealQuality,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GlycemicLoad {
+    /// Sum of GI x available carbohydrate / 100 over the ingredients with a known GI
+    pub glycemic_load: f32,
+    /// Carbohydrate-weighted GI of those ingredients
+    pub glycemic_index: Option<f32>,
+    /// Carbohydrate minus fiber, g
+    pub available_carbohydrates: f32,
+    pub rating: GlycemicRating,
+    /// Ingredients with available carbohydrate but no known GI, left out of the load
+    pub ingredients_without_gi: Vec<String>,
+}
+
+/// Meals: low <= 10 < medium < 20 <= high. Days: low <= 80 < medium < 120 <= high.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GlycemicRating {
+    Low,
+    Medium,
+    High,
+}
+
+/// How healthy a meal is, with the parts of each score so a low score can be explained.
+#[derive(Debug, Clone, Serialize)]
+pub struct MealQuality {
+    /// 0-100, higher is better: the mean of the Nutri-Score and NRF9.3 on a common scale
+    pub nutrition_score: f32,
+    pub nutri_score: NutriScore,
+    /// `None` for a meal without energy
+    pub nutrient_rich_foods: Option<NutrientRichFoods>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NutriScore {
+    /// A (best) to E
+    pub grade: String,
+    /// Negative points minus positive points, -15 to 40
+    pub score: i32,
+    /// Energy, sugars, saturated fat and sodium
+    pub negative: Vec<ScoreComponent>,
+    /// Fiber, protein and fruit/vegetables/legumes/nuts
+    pub positive: Vec<ScoreComponent>,
+    /// False when protein was left out because the negative points reached 11
+    pub protein_counted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreComponent {
+    pub name: String,
+    pub value_per_100g: f32,
+    pub unit: String,
+    pub points: i32,
+    pub max_points: i32,
+}
+
+/// NRF9.3: percent Daily Values of nine nutrients to encourage minus three to limit, per 100 kcal.
+#[derive(Debug, Clone, Serialize)]
+pub struct NutrientRichFoods {
+    pub score: f32,
+    pub encouraged: Vec<NutrientScore>,
+    pub limited: Vec<NutrientScore>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NutrientScore {
+    pub nutrient: String,
+    pub amount_per_100kcal: f32,
+    pub unit: String,
+    /// Share of the Daily Value (capped at 100 for encouraged nutrients) or Maximum Recommended Value
+    pub percent_of_reference: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MealParseRequest {
+    /// "2 scrambled eggs, a slice of whole wheat toast with butter and a coffee with milk"
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParsedMeal {
+    /// Ready for `/analyze/meal`
+    pub ingredients: Vec<crate::MealIngredient>,
+    /// How each ingredient was read, in the same order
+    pub items: Vec<ParsedMealItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParsedMealItem {
+    /// The part of the text the ingredient was read from
+    pub text: String,
+    pub ingredient: crate::MealIngredient,
+    /// The food it resolved to; `None` when nothing matched, and the analysis will skip it
+    pub fdc_id: Option<i32>,
+    pub food_description: Option<String>,
+    /// 0-1: the food match confidence, lowered when the amount was assumed; 0 when unmatched
+    pub confidence: f32,
+    /// No quantity was given, or the food's portions could not weigh it, so a typical amount was used
+    pub amount_assumed: bool,
+}
+
+/// A diet the analysis checks meals against.
+#[derive(Debug, Serialize)]
+pub struct DietSummary {
+    pub name: String,
+    pub description: String,
+}
+
+/// A major allergen category and the ingredients it was found in.
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectedAllergen {
+    pub allergen: String,
+    pub ingredients: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AllergenSafety {
+    /// False when any ingredient conflicts with an allergy
+    pub safe: bool,
+    pub checked_allergies: Vec<String>,
+    pub conflicts: Vec<AllergyConflict>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AllergyConflict {
+    pub allergy: String,
+    pub ingredient: String,
+    /// The allergen category that matched, or `None` when the allergy named the ingredient itself
+    pub allergen: Option<String>,
+}
+
+/// The FoodData Central food an ingredient was resolved to.
+#[derive(Debug, Clone, Serialize)]
+pub struct IngredientMatch {
+    pub ingredient: String,
+    pub fdc_id: i32,
+    pub food_description: String,
+    /// 0-1; 1 for an exact description or a synonym pinned to a food
+    pub confidence: f32,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -15,6 +179,7 @@ pub struct DailyNutritionAnalysisInternal {
     pub total_nutrition: crate::BasicNutrition,
     pub meal_breakdown: Vec<crate::MealNutritionBreakdown>,
     pub goal_adherence: crate::GoalAdherence,
+    pub glycemic_load: GlycemicLoad,
     pub next_meal_suggestions: Vec<crate::MealSuggestion>,
     pub hydration_reminder: bool,
     pub sleep_nutrition_impact: crate::SleepNutritionImpact,
@@ -23,6 +188,7 @@ pub struct DailyNutritionAnalysisInternal {
 // AI insights types
 #[derive(Debug, Clone, Serialize)]
 pub struct HealthInsights {
+    /// The meal's `nutrition_score`, 0-100
     pub overall_score: f32,
     pub insights: Vec<String>,
 }
@@ -61,6 +227,47 @@ pub struct NutritionGoalsRequest {
     pub height_cm: f32,
     pub activity_level: String,
     pub goals: Vec<String>,
+    /// `male` / `female`; Mifflin-St Jeor uses the midpoint when it is not given.
+    #[serde(default)]
+    pub sex: Option<String>,
+    /// Switches BMR to Katch-McArdle when known.
+    #[serde(default)]
+    pub body_fat_percentage: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NutritionGoalCalculation {
+    pub bmr: f32,
+    pub bmr_formula: String,
+    pub activity_multiplier: f32,
+    pub tdee: f32,
+    /// Fraction of TDEE added or removed for the goal, e.g. -0.2 for weight loss.
+    pub calorie_adjustment: f32,
+    pub goal_type: String,
+    pub goals: NutritionGoals,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeededNutritionGoal {
+    pub goal_id: Uuid,
+    pub calculation: NutritionGoalCalculation,
+}
+
+/// Per-serving nutrition of a stored recipe, as saved to `recipe_nutrition`.
+#[derive(Debug, Serialize)]
+pub struct RecipeNutrition {
+    pub recipe_id: Uuid,
+    pub servings: i32,
+    pub per_serving: crate::BasicNutrition,
+    /// 0-100, see `MealQuality`
+    pub nutrition_score: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HealthInsightsQuery {
+    pub user_id: Uuid,
+    /// How far back to look at logged meals; 7 days by default.
+    pub days: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1,10 +1,22 @@
 use anyhow::Result;
+use sqlx::PgPool;
 use std::collections::HashMap;
 use crate::{MealIngredient, BasicNutrition, Micronutrient, DietaryCompliance, EnvironmentalImpact};
-use crate::models::NutritionAnalysisInternal;
+use crate::allergens::{self, IngredientAllergens};
+use crate::cooking::{self, CookedWeightWithoutMethod, CookingMethod, CookingTable, MethodFactors, UnknownCookingMethod};
+use crate::diets::{DietRules, IngredientFacts};
+use crate::dri::{self, DriTable};
+use crate::glycemic::{self, LoadTotals};
+use crate::scoring::{self, MealComposition};
+use crate::food_database::{FoodDatabase, FoodRecord};
+use crate::units;
+use crate::models::{ConsumerProfile, DetectedAllergen, IngredientMatch, NutritionAnalysisInternal};
 
 pub struct NutritionalAnalyzer {
-    nutrition_database: HashMap<String, IngredientNutrition>,
+    foods: FoodDatabase,
+    dri: DriTable,
+    cooking: CookingTable,
+    diets: DietRules,
 }
 
 #[derive(Debug, Clone)]
@@ -13,12 +25,14 @@ struct IngredientNutrition {
     protein_per_100g: f32,
     carbs_per_100g: f32,
     fat_per_100g: f32,
+    saturated_fat_per_100g: f32,
     fiber_per_100g: f32,
     sugar_per_100g: f32,
     sodium_per_100g: f32,
     micronutrients: Vec<MicronutrientData>,
-    allergens: Vec<String>,
-    dietary_flags: DietaryFlags,
+    anti_inflammatory_score: f32,
+    /// Counts towards the Nutri-Score fruit, vegetable, legume and nut share
+    fruit_vegetable: bool,
     carbon_footprint_per_100g: f32,
     water_usage_per_100g: f32,
 }
@@ -28,89 +42,33 @@ struct MicronutrientData {
     name: String,
     amount_per_100g: f32,
     unit: String,
-    daily_value: f32,
     bioavailability_factor: f32,
 }
 
-#[derive(Debug, Clone)]
-struct DietaryFlags {
-    vegan: bool,
-    vegetarian: bool,
-    gluten_free: bool,
-    dairy_free: bool,
-    low_sodium: bool,
+/// Traits FoodData Central does not record, estimated from the food category.
+struct CategoryProfile {
     anti_inflammatory_score: f32,
+    /// kg CO2e and litres of water per kg, like the rest of the environmental figures
+    carbon_footprint: f32,
+    water_usage: f32,
 }
 
 impl NutritionalAnalyzer {
-    pub fn new() -> Self {
-        let mut nutrition_database = HashMap::new();
-        
-        // Initialize comprehensive nutrition database
-        nutrition_database.insert("chicken breast".to_string(), IngredientNutrition {
-            calories_per_100g: 165.0,
-            protein_per_100g: 31.0,
-            carbs_per_100g: 0.0,
-            fat_per_100g: 3.6,
-            fiber_per_100g: 0.0,
-            sugar_per_100g: 0.0,
-            sodium_per_100g: 74.0,
-            micronutrients: vec![
-                MicronutrientData {
-                    name: "Vitamin B6".to_string(),
-                    amount_per_100g: 0.89,
-                    unit: "mg".to_string(),
-                    daily_value: 1.3,
-                    bioavailability_factor: 0.9,
-                },
-            ],
-            allergens: vec![],
-            dietary_flags: DietaryFlags {
-                vegan: false,
-                vegetarian: false,
-                gluten_free: true,
-                dairy_free: true,
-                low_sodium: true,
-                anti_inflammatory_score: 7.5,
-            },
-            carbon_footprint_per_100g: 6.1,
-            water_usage_per_100g: 4325.0,
-        });
-
-        nutrition_database.insert("brown rice".to_string(), IngredientNutrition {
-            calories_per_100g: 112.0,
-            protein_per_100g: 2.6,
-            carbs_per_100g: 23.0,
-            fat_per_100g: 0.9,
-            fiber_per_100g: 1.8,
-            sugar_per_100g: 0.4,
-            sodium_per_100g: 5.0,
-            micronutrients: vec![
-                MicronutrientData {
-                    name: "Magnesium".to_string(),
-                    amount_per_100g: 43.0,
-                    unit: "mg".to_string(),
-                    daily_value: 400.0,
-                    bioavailability_factor: 0.8,
-                },
-            ],
-            allergens: vec![],
-            dietary_flags: DietaryFlags {
-                vegan: true,
-                vegetarian: true,
-                gluten_free: true,
-                dairy_free: true,
-                low_sodium: true,
-                anti_inflammatory_score: 8.2,
-            },
-            carbon_footprint_per_100g: 2.3,
-            water_usage_per_100g: 2497.0,
-        });
+    pub fn new(db: PgPool, diets: DietRules) -> Self {
+        Self {
+            foods: FoodDatabase::new(db),
+            dri: DriTable::bundled().expect("bundled data/dri.json is valid"),
+            cooking: CookingTable::bundled().expect("bundled data/cooking_factors.json is valid"),
+            diets,
+        }
+    }
 
-        // Add more ingredients...
-        Self::add_common_ingredients(&mut nutrition_database);
+    pub fn diets(&self) -> &DietRules {
+        &self.diets
+    }
 
-        Self { nutrition_database }
+    pub fn foods(&self) -> &FoodDatabase {
+        &self.foods
     }
 
     pub async fn analyze_meal(
@@ -118,6 +76,7 @@ impl NutritionalAnalyzer {
         ingredients: &[MealIngredient],
         portion_size: f32,
         cooking_method: Option<&str>,
+        consumer: Option<&ConsumerProfile>,
     ) -> Result<NutritionAnalysisInternal> {
         let mut total_nutrition = BasicNutrition {
             calories: 0.0,
@@ -129,62 +88,156 @@ impl NutritionalAnalyzer {
             sodium: 0.0,
         };
 
-        let mut micronutrients: HashMap<String, f32> = HashMap::new();
-        let mut allergens: Vec<String> = Vec::new();
-        let mut dietary_compliance = DietaryCompliance {
-            keto_friendly: true,
-            vegan: true,
-            vegetarian: true,
-            gluten_free: true,
-            dairy_free: true,
-            low_sodium: true,
-            anti_inflammatory_score: 0.0,
-        };
+        let mut micronutrients: HashMap<String, (String, f32)> = HashMap::new();
+        let mut ingredient_allergens = Vec::new();
+        let mut ingredient_facts = Vec::new();
+        let mut glycemic_load = LoadTotals::default();
+        let mut saturated_fat = 0.0;
+        let mut eaten_weight = 0.0;
+        let mut fruit_vegetable_weight = 0.0;
 
         let mut total_carbon_footprint = 0.0;
         let mut total_water_usage = 0.0;
         let mut anti_inflammatory_scores = Vec::new();
+        let mut ingredient_matches = Vec::new();
+        let mut unmatched_ingredients = Vec::new();
+
+        let meal_method = match cooking_method {
+            Some(method) => Some(CookingMethod::parse(method).ok_or_else(|| UnknownCookingMethod(method.to_string()))?),
+            None => None,
+        };
 
         for ingredient in ingredients {
-            let ingredient_nutrition = self.get_ingredient_nutrition(&ingredient.name);
-            let amount_factor = self.calculate_amount_factor(&ingredient.amount, &ingredient.unit);
-            let cooking_factor = self.get_cooking_factor(cooking_method, &ingredient.name);
+            let Some(food_match) = self.foods.find(&ingredient.name).await? else {
+                // Still checked for allergens by name
+                let allergens = IngredientAllergens::new(&ingredient.name, None);
+                ingredient_facts.push(IngredientFacts {
+                    ingredient: ingredient.name.clone(),
+                    description: None,
+                    category: None,
+                    allergens: allergens.allergens.clone(),
+                    nutrients: HashMap::new(),
+                });
+                ingredient_allergens.push(allergens);
+                unmatched_ingredients.push(ingredient.name.clone());
+                continue;
+            };
+            let allergens = IngredientAllergens::new(&ingredient.name, Some(&food_match.food.description));
+            ingredient_matches.push(IngredientMatch {
+                ingredient: ingredient.name.clone(),
+                fdc_id: food_match.food.fdc_id,
+                food_description: food_match.food.description.clone(),
+                confidence: food_match.confidence,
+            });
+            let ingredient_nutrition = Self::from_food_record(&food_match.food);
+            let amount_factor = self.calculate_amount_factor(ingredient.amount, &ingredient.unit, &food_match.food)?;
+
+            // A preparation that names a method ("steamed") overrides the meal's method
+            let method = ingredient
+                .preparation
+                .as_deref()
+                .and_then(CookingMethod::parse)
+                .or(meal_method)
+                .unwrap_or(CookingMethod::Raw);
+            // Treating a cooked weight as raw would overstate e.g. cooked rice about 2.5x
+            if ingredient.cooked_weight
+                && method == CookingMethod::Raw
+                && !cooking::is_cooked_description(&food_match.food.description)
+            {
+                return Err(CookedWeightWithoutMethod(ingredient.name.clone()).into());
+            }
+            let cooking_factor = self.get_cooking_factor(method, &food_match.food);
+            let retention = |nutrient: &str| cooking_factor.map_or(1.0, |factors| self.cooking.retention(factors, nutrient));
+
+            // FDC values are per 100 g raw, so a cooked weight is first converted back to raw
+            let raw_factor = match cooking_factor {
+                Some(factors) if ingredient.cooked_weight => factors.raw_weight(amount_factor),
+                _ => amount_factor,
+            };
+
+            // Energy follows the macronutrients it comes from
+            let protein_energy = ingredient_nutrition.protein_per_100g * 4.0;
+            let carb_energy = ingredient_nutrition.carbs_per_100g * 4.0;
+            let fat_energy = ingredient_nutrition.fat_per_100g * 9.0;
+            let macro_energy = protein_energy + carb_energy + fat_energy;
+            let energy_retention = if macro_energy > 0.0 {
+                (protein_energy * retention("protein") + carb_energy * retention("carbohydrates") + fat_energy * retention("fat"))
+                    / macro_energy
+            } else {
+                1.0
+            };
 
             // Calculate nutrition with cooking adjustments
-            total_nutrition.calories += ingredient_nutrition.calories_per_100g * amount_factor * cooking_factor.calories;
-            total_nutrition.protein += ingredient_nutrition.protein_per_100g * amount_factor * cooking_factor.protein;
-            total_nutrition.carbohydrates += ingredient_nutrition.carbs_per_100g * amount_factor;
-            total_nutrition.fat += ingredient_nutrition.fat_per_100g * amount_factor * cooking_factor.fat;
-            total_nutrition.fiber += ingredient_nutrition.fiber_per_100g * amount_factor * cooking_factor.fiber;
-            total_nutrition.sugar += ingredient_nutrition.sugar_per_100g * amount_factor;
-            total_nutrition.sodium += ingredient_nutrition.sodium_per_100g * amount_factor;
-
-            // Process micronutrients
-            for micro in &ingredient_nutrition.micronutrients {
-                let amount = micro.amount_per_100g * amount_factor * micro.bioavailability_factor * cooking_factor.micronutrients;
-                *micronutrients.entry(micro.name.clone()).or_insert(0.0) += amount;
+            let contribution = BasicNutrition {
+                calories: ingredient_nutrition.calories_per_100g * raw_factor * energy_retention,
+                protein: ingredient_nutrition.protein_per_100g * raw_factor * retention("protein"),
+                carbohydrates: ingredient_nutrition.carbs_per_100g * raw_factor * retention("carbohydrates"),
+                fat: ingredient_nutrition.fat_per_100g * raw_factor * retention("fat"),
+                fiber: ingredient_nutrition.fiber_per_100g * raw_factor * retention("fiber"),
+                sugar: ingredient_nutrition.sugar_per_100g * raw_factor * retention("sugar"),
+                sodium: ingredient_nutrition.sodium_per_100g * raw_factor * retention("Sodium"),
+            };
+            total_nutrition.calories += contribution.calories;
+            total_nutrition.protein += contribution.protein;
+            total_nutrition.carbohydrates += contribution.carbohydrates;
+            total_nutrition.fat += contribution.fat;
+            total_nutrition.fiber += contribution.fiber;
+            total_nutrition.sugar += contribution.sugar;
+            total_nutrition.sodium += contribution.sodium;
+            glycemic_load.add(
+                &ingredient.name,
+                food_match.food.glycemic_index,
+                glycemic::available_carbohydrates(contribution.carbohydrates, contribution.fiber),
+                glycemic::available_carbohydrates(ingredient_nutrition.carbs_per_100g, ingredient_nutrition.fiber_per_100g),
+            );
+            let ingredient_saturated_fat = ingredient_nutrition.saturated_fat_per_100g * raw_factor * retention("fat");
+            saturated_fat += ingredient_saturated_fat;
+
+            // Weight as eaten, for the per-100 g scores
+            let weight = raw_factor * 100.0 * cooking_factor.map_or(1.0, |factors| factors.weight_yield);
+            eaten_weight += weight;
+            if ingredient_nutrition.fruit_vegetable {
+                fruit_vegetable_weight += weight;
             }
 
-            // Aggregate allergens
-            for allergen in &ingredient_nutrition.allergens {
-                if !allergens.contains(allergen) {
-                    allergens.push(allergen.clone());
-                }
+            let mut nutrients: HashMap<String, f32> = diet_nutrients(&contribution)
+                .into_iter()
+                .map(|(name, _, amount)| (name.to_string(), amount))
+                .collect();
+            nutrients.insert("saturated_fat".to_string(), ingredient_saturated_fat);
+
+            // Process micronutrients, summed in the unit of their reference intake
+            for micro in &ingredient_nutrition.micronutrients {
+                let amount = micro.amount_per_100g * raw_factor * micro.bioavailability_factor * retention(&micro.name);
+                let (unit, amount) = match self.dri.lookup(&micro.name, consumer) {
+                    Some(reference) => match dri::convert_mass(amount, &micro.unit, &reference.unit) {
+                        Some(converted) => (reference.unit, converted),
+                        None => (micro.unit.clone(), amount),
+                    },
+                    None => (micro.unit.clone(), amount),
+                };
+                *nutrients.entry(micro.name.clone()).or_insert(0.0) += amount;
+                let entry = micronutrients.entry(micro.name.clone()).or_insert((unit, 0.0));
+                entry.1 += amount;
             }
 
-            // Update dietary compliance
-            dietary_compliance.vegan &= ingredient_nutrition.dietary_flags.vegan;
-            dietary_compliance.vegetarian &= ingredient_nutrition.dietary_flags.vegetarian;
-            dietary_compliance.gluten_free &= ingredient_nutrition.dietary_flags.gluten_free;
-            dietary_compliance.dairy_free &= ingredient_nutrition.dietary_flags.dairy_free;
+            ingredient_facts.push(IngredientFacts {
+                ingredient: ingredient.name.clone(),
+                description: Some(food_match.food.description.clone()),
+                category: food_match.food.food_category.clone(),
+                allergens: allergens.allergens.clone(),
+                nutrients,
+            });
+            ingredient_allergens.push(allergens);
 
             // Environmental impact
-            total_carbon_footprint += ingredient_nutrition.carbon_footprint_per_100g * amount_factor;
-            total_water_usage += ingredient_nutrition.water_usage_per_100g * amount_factor;
-            anti_inflammatory_scores.push(ingredient_nutrition.dietary_flags.anti_inflammatory_score);
+            total_carbon_footprint += ingredient_nutrition.carbon_footprint_per_100g * raw_factor;
+            total_water_usage += ingredient_nutrition.water_usage_per_100g * raw_factor;
+            anti_inflammatory_scores.push(ingredient_nutrition.anti_inflammatory_score);
         }
 
         // Apply portion size
+        saturated_fat *= portion_size;
         total_nutrition.calories *= portion_size;
         total_nutrition.protein *= portion_size;
         total_nutrition.carbohydrates *= portion_size;
@@ -193,22 +246,53 @@ impl NutritionalAnalyzer {
         total_nutrition.sugar *= portion_size;
         total_nutrition.sodium *= portion_size;
 
-        // Determine keto-friendliness
-        dietary_compliance.keto_friendly = total_nutrition.carbohydrates < 20.0;
-        dietary_compliance.low_sodium = total_nutrition.sodium < 600.0;
-        dietary_compliance.anti_inflammatory_score = anti_inflammatory_scores.iter().sum::<f32>() / anti_inflammatory_scores.len() as f32;
-
         // Convert micronutrients to final format
         let final_micronutrients: Vec<Micronutrient> = micronutrients
             .into_iter()
-            .map(|(name, amount)| Micronutrient {
-                name: name.clone(),
-                amount: amount * portion_size,
-                unit: self.get_nutrient_unit(&name),
-                daily_value_percentage: (amount * portion_size / self.get_daily_value(&name)) * 100.0,
+            .map(|(name, (unit, amount))| {
+                let amount = amount * portion_size;
+                let reference = self.dri.lookup(&name, consumer);
+                let reference_intake = reference.as_ref().map(|r| r.target);
+                let reference_basis = reference.as_ref().map(|r| r.basis.clone());
+                let upper_limit = reference.as_ref().and_then(|r| r.upper_limit);
+                Micronutrient {
+                    name,
+                    amount,
+                    unit,
+                    daily_value_percentage: reference_intake.map_or(0.0, |target| amount / target * 100.0),
+                    reference_intake,
+                    reference_basis,
+                    upper_limit,
+                    exceeds_upper_limit: upper_limit.is_some_and(|ul| amount > ul),
+                }
             })
             .collect();
 
+        // Diet rules see the whole meal: macronutrients and every micronutrient, portion applied
+        let mut totals: HashMap<String, (String, f32)> = diet_nutrients(&total_nutrition)
+            .into_iter()
+            .map(|(name, unit, amount)| (name.to_string(), (unit.to_string(), amount)))
+            .collect();
+        totals.insert("saturated_fat".to_string(), ("g".to_string(), saturated_fat));
+        totals.extend(final_micronutrients.iter().map(|m| (m.name.clone(), (m.unit.clone(), m.amount))));
+        let dietary_compliance = DietaryCompliance {
+            diets: self.diets.evaluate(&ingredient_facts, &totals),
+            anti_inflammatory_score: if anti_inflammatory_scores.is_empty() {
+                0.0
+            } else {
+                anti_inflammatory_scores.iter().sum::<f32>() / anti_inflammatory_scores.len() as f32
+            },
+        };
+
+        // Both scores are relative to weight or energy, so the portion size cancels out
+        let quality = scoring::score_meal(&MealComposition {
+            nutrition: &total_nutrition,
+            saturated_fat,
+            micronutrients: &final_micronutrients,
+            weight: eaten_weight * portion_size,
+            fruit_vegetable_weight: fruit_vegetable_weight * portion_size,
+        });
+
         Ok(NutritionAnalysisInternal {
             basic_nutrition: total_nutrition,
             micronutrients: final_micronutrients,
@@ -218,108 +302,60 @@ impl NutritionalAnalyzer {
                 water_usage: total_water_usage * portion_size,
                 sustainability_score: self.calculate_sustainability_score(total_carbon_footprint, total_water_usage),
             },
+            ingredient_matches,
+            unmatched_ingredients,
+            allergens: group_by_allergen(&ingredient_allergens),
+            allergy_safety: consumer
+                .and_then(|c| c.allergies.as_deref())
+                .filter(|allergies| !allergies.is_empty())
+                .map(|allergies| allergens::check(allergies, &ingredient_allergens)),
+            glycemic_load: glycemic_load.meal(portion_size),
+            quality,
         })
     }
 
-    fn get_ingredient_nutrition(&self, ingredient_name: &str) -> IngredientNutrition {
-        // Normalize ingredient name for lookup
-        let normalized = ingredient_name.to_lowercase().trim().to_string();
-        
-        self.nutrition_database
-            .get(&normalized)
-            .cloned()
-            .unwrap_or_else(|| self.get_default_nutrition())
-    }
+    fn from_food_record(food: &FoodRecord) -> IngredientNutrition {
+        let profile = category_profile(food.food_category.as_deref(), &food.description);
 
-    fn get_default_nutrition(&self) -> IngredientNutrition {
         IngredientNutrition {
-            calories_per_100g: 50.0,
-            protein_per_100g: 2.0,
-            carbs_per_100g: 10.0,
-            fat_per_100g: 1.0,
-            fiber_per_100g: 2.0,
-            sugar_per_100g: 2.0,
-            sodium_per_100g: 10.0,
-            micronutrients: vec![],
-            allergens: vec![],
-            dietary_flags: DietaryFlags {
-                vegan: true,
-                vegetarian: true,
-                gluten_free: true,
-                dairy_free: true,
-                low_sodium: true,
-                anti_inflammatory_score: 5.0,
-            },
-            carbon_footprint_per_100g: 1.0,
-            water_usage_per_100g: 500.0,
+            calories_per_100g: food.calories_kcal.unwrap_or(0.0),
+            protein_per_100g: food.protein_g.unwrap_or(0.0),
+            carbs_per_100g: food.carbohydrates_g.unwrap_or(0.0),
+            fat_per_100g: food.fat_g.unwrap_or(0.0),
+            saturated_fat_per_100g: food.saturated_fat_g.unwrap_or(0.0),
+            fiber_per_100g: food.fiber_g.unwrap_or(0.0),
+            sugar_per_100g: food.sugar_g.unwrap_or(0.0),
+            sodium_per_100g: food.sodium_mg.unwrap_or(0.0),
+            // FDC amounts are the content of the food; the DRIs already allow for typical absorption
+            micronutrients: food
+                .micronutrients
+                .iter()
+                .map(|n| MicronutrientData {
+                    name: n.name.clone(),
+                    amount_per_100g: n.amount_per_100g,
+                    unit: n.unit.clone(),
+                    bioavailability_factor: 1.0,
+                })
+                .collect(),
+            anti_inflammatory_score: profile.anti_inflammatory_score,
+            fruit_vegetable: is_fruit_vegetable(food.food_category.as_deref(), &food.description),
+            carbon_footprint_per_100g: profile.carbon_footprint,
+            water_usage_per_100g: profile.water_usage,
         }
     }
 
-    fn calculate_amount_factor(&self, amount: &f32, unit: &str) -> f32 {
-        match unit.to_lowercase().as_str() {
-            "g" | "grams" => amount / 100.0,
-            "kg" | "kilograms" => amount * 10.0,
-            "oz" | "ounces" => amount * 0.2835,
-            "lb" | "pounds" => amount * 4.536,
-            "cup" | "cups" => amount * 2.4, // Approximate
-            "tbsp" | "tablespoon" => amount * 0.15,
-            "tsp" | "teaspoon" => amount * 0.05,
-            _ => amount / 100.0, // Default to grams
-        }
-    }
-
-    fn get_cooking_factor(&self, cooking_method: Option<&str>, _ingredient: &str) -> CookingFactor {
-        match cooking_method {
-            Some("grilled") => CookingFactor {
-                calories: 1.0,
-                protein: 0.95,
-                fat: 0.9,
-                fiber: 1.0,
-                micronutrients: 0.85,
-            },
-            Some("boiled") => CookingFactor {
-                calories: 1.0,
-                protein: 0.9,
-                fat: 1.0,
-                fiber: 0.9,
-                micronutrients: 0.7,
-            },
-            Some("steamed") => CookingFactor {
-                calories: 1.0,
-                protein: 0.98,
-                fat: 1.0,
-                fiber: 0.95,
-                micronutrients: 0.9,
-            },
-            _ => CookingFactor {
-                calories: 1.0,
-                protein: 1.0,
-                fat: 1.0,
-                fiber: 1.0,
-                micronutrients: 1.0,
-            },
-        }
-    }
-
-    fn get_daily_value(&self, nutrient_name: &str) -> f32 {
-        match nutrient_name.to_lowercase().as_str() {
-            "vitamin c" => 90.0,
-            "vitamin d" => 20.0,
-            "vitamin b6" => 1.3,
-            "calcium" => 1000.0,
-            "iron" => 18.0,
-            "magnesium" => 400.0,
-            "zinc" => 11.0,
-            _ => 100.0, // Default
-        }
+    /// Multiplier for per-100 g values.
+    fn calculate_amount_factor(&self, amount: f32, unit: &str, food: &FoodRecord) -> Result<f32> {
+        Ok(units::to_grams(amount, unit, food)? / 100.0)
     }
 
-    fn get_nutrient_unit(&self, nutrient_name: &str) -> String {
-        match nutrient_name.to_lowercase().as_str() {
-            "vitamin c" | "vitamin b6" | "calcium" | "iron" | "magnesium" | "zinc" => "mg".to_string(),
-            "vitamin d" => "mcg".to_string(),
-            _ => "mg".to_string(),
+    /// Retention and yield for `method`, or `None` when nothing changes: raw food, or an FDC
+    /// entry that already describes the cooked food.
+    fn get_cooking_factor(&self, method: CookingMethod, food: &FoodRecord) -> Option<&MethodFactors> {
+        if cooking::is_cooked_description(&food.description) {
+            return None;
         }
+        self.cooking.factors(food.food_category.as_deref(), method)
     }
 
     fn calculate_sustainability_score(&self, carbon_footprint: f32, water_usage: f32) -> f32 {
@@ -328,40 +364,81 @@ impl NutritionalAnalyzer {
         let water_score = (10000.0 - water_usage.min(10000.0)) / 1000.0;
         ((carbon_score + water_score) / 2.0).max(0.0).min(10.0)
     }
+}
 
-    fn add_common_ingredients(database: &mut HashMap<String, IngredientNutrition>) {
-        // Add more common ingredients to the database
-        database.insert("spinach".to_string(), IngredientNutrition {
-            calories_per_100g: 23.0,
-            protein_per_100g: 2.9,
-            carbs_per_100g: 3.6,
-            fat_per_100g: 0.4,
-            fiber_per_100g: 2.2,
-            sugar_per_100g: 0.4,
-            sodium_per_100g: 79.0,
-            micronutrients: vec![],
-            allergens: vec![],
-            dietary_flags: DietaryFlags {
-                vegan: true,
-                vegetarian: true,
-                gluten_free: true,
-                dairy_free: true,
-                low_sodium: true,
-                anti_inflammatory_score: 9.0,
-            },
-            carbon_footprint_per_100g: 0.7,
-            water_usage_per_100g: 322.0,
-        });
+/// Macronutrients under the names diet rules use, with their units.
+fn diet_nutrients(nutrition: &BasicNutrition) -> [(&'static str, &'static str, f32); 8] {
+    [
+        ("calories", "kcal", nutrition.calories),
+        ("protein", "g", nutrition.protein),
+        ("carbohydrates", "g", nutrition.carbohydrates),
+        ("net_carbohydrates", "g", glycemic::available_carbohydrates(nutrition.carbohydrates, nutrition.fiber)),
+        ("fat", "g", nutrition.fat),
+        ("fiber", "g", nutrition.fiber),
+        ("sugar", "g", nutrition.sugar),
+        ("sodium", "mg", nutrition.sodium),
+    ]
+}
 
-        // Add more ingredients as needed...
+/// Detected allergens with the ingredients each was found in, in order of first appearance.
+fn group_by_allergen(ingredients: &[IngredientAllergens]) -> Vec<DetectedAllergen> {
+    let mut detected: Vec<DetectedAllergen> = Vec::new();
+    for ingredient in ingredients {
+        for allergen in &ingredient.allergens {
+            match detected.iter_mut().find(|d| d.allergen == *allergen) {
+                Some(entry) => entry.ingredients.push(ingredient.ingredient.clone()),
+                None => detected.push(DetectedAllergen {
+                    allergen: allergen.to_string(),
+                    ingredients: vec![ingredient.ingredient.clone()],
+                }),
+            }
+        }
     }
+    detected
 }
 
-#[derive(Debug, Clone)]
-struct CookingFactor {
-    calories: f32,
-    protein: f32,
-    fat: f32,
-    fiber: f32,
-    micronutrients: f32,
+/// Fruit, vegetables (not starchy roots), legumes, nuts and the oils the Nutri-Score counts with them.
+fn is_fruit_vegetable(category: Option<&str>, description: &str) -> bool {
+    let description = description.to_lowercase();
+    match category.unwrap_or_default() {
+        "Fruits and Fruit Juices" | "Legumes and Legume Products" | "Nut and Seed Products" => true,
+        "Vegetables and Vegetable Products" => {
+            !["potato", "yam", "cassava", "taro"].iter().any(|root| description.contains(root))
+        }
+        "Fats and Oils" => ["olive", "walnut", "rapeseed", "canola"].iter().any(|oil| description.contains(oil)),
+        _ => false,
+    }
+}
+
+/// Mixed dishes and unknown categories get middling estimates.
+fn category_profile(category: Option<&str>, description: &str) -> CategoryProfile {
+    let profile = |anti_inflammatory_score, carbon_footprint, water_usage| CategoryProfile {
+        anti_inflammatory_score,
+        carbon_footprint,
+        water_usage,
+    };
+
+    match category.unwrap_or_default() {
+        "Vegetables and Vegetable Products" => profile(9.0, 0.5, 322.0),
+        "Fruits and Fruit Juices" => profile(8.5, 0.7, 962.0),
+        "Legumes and Legume Products" => profile(8.0, 0.9, 4055.0),
+        "Nut and Seed Products" => profile(8.0, 0.4, 9063.0),
+        "Spices and Herbs" => profile(8.5, 1.0, 1000.0),
+        "Cereal Grains and Pasta" | "Breakfast Cereals" => profile(7.0, 1.6, 1644.0),
+        "Fats and Oils" => profile(5.0, 3.5, 4000.0),
+        "Baked Products" => profile(4.0, 1.6, 1608.0),
+        "Sweets" => profile(3.0, 2.5, 1782.0),
+        "Beverages" => profile(5.0, 0.5, 300.0),
+        "Dairy and Egg Products" => {
+            let egg = description.to_lowercase().starts_with("egg");
+            profile(6.0, if egg { 4.7 } else { 3.2 }, if egg { 3265.0 } else { 1020.0 })
+        }
+        "Finfish and Shellfish Products" => profile(8.0, 5.1, 1000.0),
+        "Poultry Products" => profile(7.0, 6.1, 4325.0),
+        "Pork Products" => profile(5.0, 7.2, 5988.0),
+        "Sausages and Luncheon Meats" => profile(3.0, 7.2, 5988.0),
+        "Beef Products" => profile(5.0, 60.0, 15415.0),
+        "Lamb, Veal, and Game Products" => profile(5.0, 24.0, 10412.0),
+        _ => profile(5.0, 1.0, 500.0),
+    }
 }
@@ -2,6 +2,8 @@ use anyhow::Result;
 use std::sync::Arc;
 use uuid::Uuid;
 use crate::ai_engine::NutritionAI;
+use crate::allergens;
+use crate::energy_calculator;
 use crate::{MealIngredient, MealRecommendationRequest, MealRecommendation};
 use crate::models::*;
 
@@ -26,10 +28,10 @@ impl RecommendationEngine {
 
     pub async fn recommend_meals(
         &self,
-        _request: &MealRecommendationRequest,
+        request: &MealRecommendationRequest,
     ) -> Result<Vec<MealRecommendation>> {
         // Mock implementation - return sample meal recommendations
-        Ok(vec![
+        let mut recommendations = vec![
             MealRecommendation {
                 meal_id: Uuid::new_v4(),
                 name: "Grilled Chicken Salad".to_string(),
@@ -40,6 +42,7 @@ impl RecommendationEngine {
                         amount: 150.0,
                         unit: "g".to_string(),
                         preparation: Some("grilled".to_string()),
+                        cooked_weight: false,
                     },
                     MealIngredient {
                         ingredient_id: Uuid::new_v4(),
@@ -47,6 +50,7 @@ impl RecommendationEngine {
                         amount: 100.0,
                         unit: "g".to_string(),
                         preparation: None,
+                        cooked_weight: false,
                     },
                 ],
                 nutrition: crate::BasicNutrition {
@@ -72,7 +76,13 @@ impl RecommendationEngine {
                 difficulty: "Easy".to_string(),
                 cuisine_type: "Mediterranean".to_string(),
             }
-        ])
+        ];
+
+        // Allergies are a hard filter, not a preference
+        if let Some(allergies) = &request.allergies {
+            recommendations.retain(|meal| allergens::check_ingredients(allergies, &meal.ingredients).safe);
+        }
+        Ok(recommendations)
     }
 
     pub async fn recommend_supplements(
@@ -84,15 +94,9 @@ impl RecommendationEngine {
 
     pub async fn calculate_nutrition_goals(
         &self,
-        _request: &NutritionGoalsRequest,
-    ) -> Result<NutritionGoals> {
-        Ok(NutritionGoals {
-            daily_calories: 2000.0,
-            daily_protein: 150.0,
-            daily_carbs: 250.0,
-            daily_fat: 65.0,
-            daily_fiber: 35.0,
-        })
+        request: &NutritionGoalsRequest,
+    ) -> Result<NutritionGoalCalculation> {
+        Ok(energy_calculator::calculate(request)?)
     }
 
     pub async fn track_goal_progress(
@@ -0,0 +1,361 @@
+// Meal quality scores: Nutri-Score and the Nutrient Rich Foods index (NRF9.3)
+//
+// Nutri-Score follows the 2017 algorithm for general foods, applied to 100 g of the meal as
+// eaten: 0-10 points each for energy, sugars, saturated fat and sodium, minus 0-5 each for
+// fiber, protein and the share of fruit, vegetables, legumes and nuts. Protein is not
+// subtracted when the negative points reach 11 and fruit/vegetables score under 5. Grades are
+// A (<= -1), B (0-2), C (3-10), D (11-18) and E (>= 19).
+//
+// NRF9.3 (Drewnowski 2009) is computed per 100 kcal: the sum of the percent Daily Values of nine
+// nutrients to encourage, each capped at 100, minus the percent Maximum Recommended Values of
+// three to limit. Total sugars stand in for added sugars, which FoodData Central does not report.
+//
+// `nutrition_score` (0-100) is the mean of the Nutri-Score mapped linearly from its range
+// (-15 = 100, 40 = 0) and the NRF9.3 clamped to 0-100.
+
+use crate::dri;
+use crate::models::{MealQuality, NutrientRichFoods, NutriScore, NutrientScore, ScoreComponent};
+use crate::{BasicNutrition, Micronutrient};
+
+/// Nutri-Score thresholds: a point for each one the value per 100 g is above.
+const ENERGY_KJ: &[f32] = &[335.0, 670.0, 1005.0, 1340.0, 1675.0, 2010.0, 2345.0, 2680.0, 3015.0, 3350.0];
+const SUGARS_G: &[f32] = &[4.5, 9.0, 13.5, 18.0, 22.5, 27.0, 31.0, 36.0, 40.0, 45.0];
+const SATURATED_FAT_G: &[f32] = &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
+const SODIUM_MG: &[f32] = &[90.0, 180.0, 270.0, 360.0, 450.0, 540.0, 630.0, 720.0, 810.0, 900.0];
+const FIBER_G: &[f32] = &[0.9, 1.9, 2.8, 3.7, 4.7];
+const PROTEIN_G: &[f32] = &[1.6, 3.2, 4.8, 6.4, 8.0];
+/// Fruit, vegetable, legume and nut share (%) for 1, 2 and 5 points.
+const FRUIT_VEGETABLE_PERCENT: &[(f32, i32)] = &[(40.0, 1), (60.0, 2), (80.0, 5)];
+
+const NUTRI_SCORE_RANGE: (f32, f32) = (-15.0, 40.0);
+
+/// NRF9.3 nutrients to encourage and their Daily Values (FDA 2016).
+const NRF_ENCOURAGED: &[(&str, f32, &str)] = &[
+    ("protein", 50.0, "g"),
+    ("fiber", 28.0, "g"),
+    ("Vitamin A", 900.0, "mcg"),
+    ("Vitamin C", 90.0, "mg"),
+    ("Vitamin E", 15.0, "mg"),
+    ("Calcium", 1300.0, "mg"),
+    ("Iron", 18.0, "mg"),
+    ("Potassium", 4700.0, "mg"),
+    ("Magnesium", 420.0, "mg"),
+];
+
+/// NRF9.3 nutrients to limit and their Maximum Recommended Values.
+const NRF_LIMITED: &[(&str, f32, &str)] = &[("saturated_fat", 20.0, "g"), ("sugar", 50.0, "g"), ("sodium", 2300.0, "mg")];
+
+const KJ_PER_KCAL: f32 = 4.184;
+
+/// What the scores are computed from, for the whole meal as eaten.
+pub struct MealComposition<'a> {
+    pub nutrition: &'a BasicNutrition,
+    pub saturated_fat: f32,
+    pub micronutrients: &'a [Micronutrient],
+    /// Weight as eaten, g
+    pub weight: f32,
+    /// Weight of fruit, vegetables, legumes, nuts and olive, walnut and rapeseed oil, g
+    pub fruit_vegetable_weight: f32,
+}
+
+impl MealComposition<'_> {
+    fn amount(&self, nutrient: &str, unit: &str) -> f32 {
+        match nutrient {
+            "protein" => self.nutrition.protein,
+            "fiber" => self.nutrition.fiber,
+            "sugar" => self.nutrition.sugar,
+            "sodium" => self.nutrition.sodium,
+            "saturated_fat" => self.saturated_fat,
+            _ => self
+                .micronutrients
+                .iter()
+                .find(|m| m.name == nutrient)
+                .and_then(|m| dri::convert_mass(m.amount, &m.unit, unit))
+                .unwrap_or(0.0),
+        }
+    }
+}
+
+pub fn score_meal(meal: &MealComposition) -> MealQuality {
+    let nutri_score = nutri_score(meal);
+    let nutrient_rich_foods = nutrient_rich_foods(meal);
+
+    let (best, worst) = NUTRI_SCORE_RANGE;
+    let nutri_component = ((worst - nutri_score.score as f32) / (worst - best) * 100.0).clamp(0.0, 100.0);
+    let nutrition_score = match &nutrient_rich_foods {
+        Some(nrf) => (nutri_component + nrf.score.clamp(0.0, 100.0)) / 2.0,
+        None => nutri_component,
+    };
+
+    MealQuality {
+        nutrition_score,
+        nutri_score,
+        nutrient_rich_foods,
+    }
+}
+
+fn points(value: f32, thresholds: &[f32]) -> i32 {
+    thresholds.iter().filter(|t| value > **t).count() as i32
+}
+
+fn component(name: &str, value: f32, unit: &str, thresholds: &[f32]) -> ScoreComponent {
+    ScoreComponent {
+        name: name.to_string(),
+        value_per_100g: value,
+        unit: unit.to_string(),
+        points: points(value, thresholds),
+        max_points: thresholds.len() as i32,
+    }
+}
+
+fn nutri_score(meal: &MealComposition) -> NutriScore {
+    let per_100g = |amount: f32| if meal.weight > 0.0 { amount * 100.0 / meal.weight } else { 0.0 };
+    let fruit_vegetable_percent = per_100g(meal.fruit_vegetable_weight);
+
+    let negative = vec![
+        component("energy", per_100g(meal.nutrition.calories * KJ_PER_KCAL), "kJ", ENERGY_KJ),
+        component("sugars", per_100g(meal.nutrition.sugar), "g", SUGARS_G),
+        component("saturated_fat", per_100g(meal.saturated_fat), "g", SATURATED_FAT_G),
+        component("sodium", per_100g(meal.nutrition.sodium), "mg", SODIUM_MG),
+    ];
+    let fruit_vegetable = ScoreComponent {
+        name: "fruit_vegetables_legumes_nuts".to_string(),
+        value_per_100g: fruit_vegetable_percent,
+        unit: "%".to_string(),
+        points: FRUIT_VEGETABLE_PERCENT
+            .iter()
+            .filter(|(percent, _)| fruit_vegetable_percent > *percent)
+            .map(|(_, points)| *points)
+            .max()
+            .unwrap_or(0),
+        max_points: 5,
+    };
+    let fiber = component("fiber", per_100g(meal.nutrition.fiber), "g", FIBER_G);
+    let protein = component("protein", per_100g(meal.nutrition.protein), "g", PROTEIN_G);
+
+    let negative_points: i32 = negative.iter().map(|c| c.points).sum();
+    let protein_counted = negative_points < 11 || fruit_vegetable.points >= 5;
+    let score = negative_points - fiber.points - fruit_vegetable.points - if protein_counted { protein.points } else { 0 };
+
+    let grade = match score {
+        i32::MIN..=-1 => "A",
+        0..=2 => "B",
+        3..=10 => "C",
+        11..=18 => "D",
+        _ => "E",
+    };
+
+    NutriScore {
+        grade: grade.to_string(),
+        score,
+        negative,
+        positive: vec![fiber, protein, fruit_vegetable],
+        protein_counted,
+    }
+}
+
+fn nutrient_rich_foods(meal: &MealComposition) -> Option<NutrientRichFoods> {
+    let calories = meal.nutrition.calories;
+    if calories <= 0.0 {
+        return None;
+    }
+    let per_100_kcal = |nutrient: &str, reference: f32, unit: &str, cap: Option<f32>| {
+        let amount = meal.amount(nutrient, unit) / calories * 100.0;
+        let percent = amount / reference * 100.0;
+        NutrientScore {
+            nutrient: nutrient.to_string(),
+            amount_per_100kcal: amount,
+            unit: unit.to_string(),
+            percent_of_reference: cap.map_or(percent, |cap| percent.min(cap)),
+        }
+    };
+
+    let encouraged: Vec<NutrientScore> = NRF_ENCOURAGED
+        .iter()
+        .map(|(nutrient, daily_value, unit)| per_100_kcal(nutrient, *daily_value, unit, Some(100.0)))
+        .collect();
+    let limited: Vec<NutrientScore> = NRF_LIMITED
+        .iter()
+        .map(|(nutrient, maximum, unit)| per_100_kcal(nutrient, *maximum, unit, None))
+        .collect();
+
+    let score = encouraged.iter().map(|n| n.percent_of_reference).sum::<f32>()
+        - limited.iter().map(|n| n.percent_of_reference).sum::<f32>();
+    Some(NutrientRichFoods {
+        score,
+        encouraged,
+        limited,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nutrition(calories: f32, protein: f32, fiber: f32, sugar: f32, sodium: f32) -> BasicNutrition {
+        BasicNutrition {
+            calories,
+            protein,
+            carbohydrates: 0.0,
+            fat: 0.0,
+            fiber,
+            sugar,
+            sodium,
+        }
+    }
+
+    /// Nutri-Score of 100 g with the given nutrition.
+    fn nutri(nutrition: &BasicNutrition, saturated_fat: f32, fruit_vegetable_weight: f32) -> NutriScore {
+        nutri_score(&MealComposition {
+            nutrition,
+            saturated_fat,
+            micronutrients: &[],
+            weight: 100.0,
+            fruit_vegetable_weight,
+        })
+    }
+
+    fn micronutrient(name: &str, amount: f32, unit: &str) -> Micronutrient {
+        Micronutrient {
+            name: name.to_string(),
+            amount,
+            unit: unit.to_string(),
+            daily_value_percentage: 0.0,
+            reference_intake: None,
+            reference_basis: None,
+            upper_limit: None,
+            exceeds_upper_limit: false,
+        }
+    }
+
+    #[test]
+    fn points_start_above_each_threshold() {
+        assert_eq!(points(335.0, ENERGY_KJ), 0);
+        assert_eq!(points(335.1, ENERGY_KJ), 1);
+        assert_eq!(points(3350.0, ENERGY_KJ), 9);
+        assert_eq!(points(3350.1, ENERGY_KJ), 10);
+        assert_eq!(points(4.5, SUGARS_G), 0);
+        assert_eq!(points(45.1, SUGARS_G), 10);
+        assert_eq!(points(900.0, SODIUM_MG), 9);
+        assert_eq!(points(901.0, SODIUM_MG), 10);
+        assert_eq!(points(0.9, FIBER_G), 0);
+        assert_eq!(points(4.8, FIBER_G), 5);
+        assert_eq!(points(8.0, PROTEIN_G), 4);
+        assert_eq!(points(8.1, PROTEIN_G), 5);
+    }
+
+    #[test]
+    fn fruit_and_vegetables_score_one_two_or_five() {
+        let plain = nutrition(0.0, 0.0, 0.0, 0.0, 0.0);
+        let fruit_points = |grams: f32| nutri(&plain, 0.0, grams).positive[2].points;
+        assert_eq!(fruit_points(40.0), 0);
+        assert_eq!(fruit_points(40.1), 1);
+        assert_eq!(fruit_points(60.0), 1);
+        assert_eq!(fruit_points(60.1), 2);
+        assert_eq!(fruit_points(80.0), 2);
+        assert_eq!(fruit_points(80.1), 5);
+    }
+
+    #[test]
+    fn grades_change_at_the_score_boundaries() {
+        let grade = |fiber: f32, sodium: f32, saturated_fat: f32| {
+            let score = nutri(&nutrition(0.0, 0.0, fiber, 0.0, sodium), saturated_fat, 0.0);
+            (score.score, score.grade)
+        };
+        assert_eq!(grade(1.0, 0.0, 0.0), (-1, "A".to_string()));
+        assert_eq!(grade(0.0, 0.0, 0.0), (0, "B".to_string()));
+        assert_eq!(grade(0.0, 200.0, 0.0), (2, "B".to_string()));
+        assert_eq!(grade(0.0, 300.0, 0.0), (3, "C".to_string()));
+        assert_eq!(grade(0.0, 901.0, 0.0), (10, "C".to_string()));
+        assert_eq!(grade(0.0, 901.0, 1.5), (11, "D".to_string()));
+        assert_eq!(grade(0.0, 901.0, 8.5), (18, "D".to_string()));
+        assert_eq!(grade(0.0, 901.0, 9.5), (19, "E".to_string()));
+    }
+
+    #[test]
+    fn protein_is_not_subtracted_from_eleven_negative_points() {
+        // 820 kcal/100 g is 3431 kJ, 10 points; 10 g protein is 5 points
+        let rich = nutrition(820.0, 10.0, 0.0, 0.0, 0.0);
+
+        let ten = nutri(&rich, 0.9, 0.0);
+        assert!(ten.protein_counted);
+        assert_eq!(ten.score, 10 - 5);
+
+        let eleven = nutri(&rich, 1.5, 0.0);
+        assert!(!eleven.protein_counted);
+        assert_eq!(eleven.score, 11);
+
+        // Unless fruit and vegetables earn all 5 points
+        let with_vegetables = nutri(&rich, 1.5, 85.0);
+        assert!(with_vegetables.protein_counted);
+        assert_eq!(with_vegetables.score, 11 - 5 - 5);
+    }
+
+    #[test]
+    fn nutri_score_is_per_100_grams() {
+        let salty = nutrition(0.0, 0.0, 0.0, 0.0, 400.0);
+        let meal = |weight: f32| {
+            nutri_score(&MealComposition {
+                nutrition: &salty,
+                saturated_fat: 0.0,
+                micronutrients: &[],
+                weight,
+                fruit_vegetable_weight: 0.0,
+            })
+            .score
+        };
+        assert_eq!(meal(100.0), 4);
+        assert_eq!(meal(200.0), 2);
+        assert_eq!(meal(0.0), 0);
+    }
+
+    #[test]
+    fn scores_a_worked_nrf_example() {
+        // 200 kcal; per 100 kcal every nutrient is 5% of its reference except protein and
+        // fiber (10%) and vitamin C (167%, capped at 100): 150 - 15 = 135
+        let nutrition = nutrition(200.0, 10.0, 5.6, 5.0, 230.0);
+        let micronutrients = [
+            micronutrient("Vitamin A", 90.0, "mcg"),
+            micronutrient("Vitamin C", 300.0, "mg"),
+            micronutrient("Vitamin E", 1500.0, "mcg"),
+            micronutrient("Calcium", 130.0, "mg"),
+            micronutrient("Iron", 1.8, "mg"),
+            micronutrient("Potassium", 470.0, "mg"),
+            micronutrient("Magnesium", 42.0, "mg"),
+        ];
+        let meal = MealComposition {
+            nutrition: &nutrition,
+            saturated_fat: 2.0,
+            micronutrients: &micronutrients,
+            weight: 250.0,
+            fruit_vegetable_weight: 0.0,
+        };
+
+        let nrf = nutrient_rich_foods(&meal).unwrap();
+        let vitamin_c = nrf.encouraged.iter().find(|n| n.nutrient == "Vitamin C").unwrap();
+        assert_eq!(vitamin_c.percent_of_reference, 100.0);
+        let limited: f32 = nrf.limited.iter().map(|n| n.percent_of_reference).sum();
+        assert!((limited - 15.0).abs() < 0.01, "{}", limited);
+        assert!((nrf.score - 135.0).abs() < 0.01, "{}", nrf.score);
+
+        // Per 100 g: 335 kJ, 92 mg sodium (1), 2.24 g fiber (2), 4 g protein (2): -3.
+        // Mapped (-3 of -15..40) that is 78.2, averaged with the NRF capped at 100.
+        let quality = score_meal(&meal);
+        assert_eq!(quality.nutri_score.score, -3);
+        assert!((quality.nutrition_score - (43.0 / 55.0 * 100.0 + 100.0) / 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn meals_without_energy_have_no_nrf() {
+        let water = nutrition(0.0, 0.0, 0.0, 0.0, 10.0);
+        let quality = score_meal(&MealComposition {
+            nutrition: &water,
+            saturated_fat: 0.0,
+            micronutrients: &[],
+            weight: 250.0,
+            fruit_vegetable_weight: 0.0,
+        });
+        assert!(quality.nutrient_rich_foods.is_none());
+        assert!((quality.nutrition_score - 40.0 / 55.0 * 100.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,298 @@
+// Ingredient amounts: mass, volume and count units, converted to grams
+//
+// Mass converts directly. Volume needs the food's density, taken from its FoodData Central
+// household measures ("1 cup, chopped = 30 g"). Counts ("2 cloves", "1 large", "3 slices")
+// use the weight of the matching FDC portion. Anything else is an error rather than a guess.
+
+use std::fmt;
+
+use crate::food_database::{FoodPortion, FoodRecord};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    /// Grams per unit
+    Mass(f32),
+    /// Millilitres per unit
+    Volume(f32),
+    /// A household measure or a whole item, resolved through the food's portions
+    Count,
+}
+
+const MASS_UNITS: &[(&str, f32)] = &[
+    ("g", 1.0),
+    ("gram", 1.0),
+    ("gramme", 1.0),
+    ("kg", 1000.0),
+    ("kilogram", 1000.0),
+    ("mg", 0.001),
+    ("milligram", 0.001),
+    ("oz", 28.3495),
+    ("ounce", 28.3495),
+    ("lb", 453.592),
+    ("pound", 453.592),
+];
+
+const VOLUME_UNITS: &[(&str, f32)] = &[
+    ("ml", 1.0),
+    ("milliliter", 1.0),
+    ("millilitre", 1.0),
+    ("cl", 10.0),
+    ("dl", 100.0),
+    ("l", 1000.0),
+    ("liter", 1000.0),
+    ("litre", 1000.0),
+    ("tsp", 4.92892),
+    ("teaspoon", 4.92892),
+    ("tbsp", 14.7868),
+    ("tablespoon", 14.7868),
+    ("fl oz", 29.5735),
+    ("fluid ounce", 29.5735),
+    ("cup", 236.588),
+    ("pint", 473.176),
+    ("pt", 473.176),
+    ("quart", 946.353),
+    ("qt", 946.353),
+    ("gallon", 3785.41),
+    ("gal", 3785.41),
+    ("pinch", 0.31),
+    ("dash", 0.62),
+];
+
+/// Units meaning "one of the thing itself"; they use the food's medium-sized portion.
+const WHOLE_ITEM_UNITS: &[&str] = &["", "piece", "pc", "each", "ea", "whole", "item", "unit", "serving"];
+
+/// Foods that are close enough to water for a volume to be weighed at 1 g/ml.
+const WATER_LIKE_WORDS: &[&str] = &["water", "juice", "broth", "stock", "tea", "coffee", "vinegar"];
+
+/// Why an ingredient amount could not be converted to grams.
+#[derive(Debug)]
+pub struct UnitConversionError {
+    pub ingredient: String,
+    pub unit: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for UnitConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot convert {:?} of {} to grams: {}", self.unit, self.ingredient, self.reason)
+    }
+}
+
+impl std::error::Error for UnitConversionError {}
+
+/// Lowercase, without full stops and with a plural "s" removed ("Tbsps." -> "tbsp").
+fn normalize_unit(unit: &str) -> String {
+    let unit = unit.trim().to_lowercase().replace('.', "");
+    let unit = unit.split_whitespace().collect::<Vec<_>>().join(" ");
+    match unit.strip_suffix("es") {
+        Some(stem) if stem.ends_with("inch") || stem.ends_with("ash") => stem.to_string(),
+        _ => match unit.strip_suffix('s') {
+            Some(stem) if !stem.is_empty() && !unit.ends_with("ss") => stem.to_string(),
+            _ => unit,
+        },
+    }
+}
+
+pub fn parse_unit(unit: &str) -> Unit {
+    let unit = normalize_unit(unit);
+    if let Some((_, grams)) = MASS_UNITS.iter().find(|(name, _)| *name == unit) {
+        return Unit::Mass(*grams);
+    }
+    if let Some((_, ml)) = VOLUME_UNITS.iter().find(|(name, _)| *name == unit) {
+        return Unit::Volume(*ml);
+    }
+    Unit::Count
+}
+
+impl FoodPortion {
+    /// The measure this portion is given in: the FDC measure unit, or for SR Legacy (where it
+    /// is "undetermined") the first word of the modifier, e.g. "cup" in "cup, chopped".
+    fn measure(&self) -> String {
+        let measure = match &self.measure_unit {
+            Some(unit) => unit.clone(),
+            None => self
+                .modifier
+                .as_deref()
+                .unwrap_or_default()
+                .split([',', '('])
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        };
+        normalize_unit(&measure)
+    }
+
+    fn grams_per_unit(&self) -> f32 {
+        self.gram_weight / self.amount.filter(|a| *a > 0.0).unwrap_or(1.0)
+    }
+
+    fn mentions(&self, word: &str) -> bool {
+        let modifier = self.modifier.as_deref().unwrap_or_default().to_lowercase();
+        self.measure() == word
+            || modifier
+                .split(|c: char| !c.is_alphanumeric())
+                .any(|w| !w.is_empty() && normalize_unit(w) == word)
+    }
+}
+
+/// Density in g/ml from the food's volume portions, preferring one in the same unit as the
+/// amount (a packed cup and a level tablespoon of the same food can differ).
+fn density(food: &FoodRecord, unit: &str) -> Option<f32> {
+    let volume_portions: Vec<(&FoodPortion, f32)> = food
+        .portions
+        .iter()
+        .filter_map(|p| match parse_unit(&p.measure()) {
+            Unit::Volume(ml) => Some((p, ml)),
+            _ => None,
+        })
+        .collect();
+
+    let same_unit = volume_portions.iter().find(|(p, _)| p.measure() == unit);
+    if let Some((portion, ml)) = same_unit.or(volume_portions.first()) {
+        return Some(portion.grams_per_unit() / ml);
+    }
+
+    let description = food.description.to_lowercase();
+    let water_like = food.food_category.as_deref() == Some("Beverages")
+        || WATER_LIKE_WORDS.iter().any(|w| description.split(|c: char| !c.is_alphanumeric()).any(|d| d == *w));
+    water_like.then_some(1.0)
+}
+
+/// Weight of one `unit` of a counted food: a portion naming the unit ("clove", "large",
+/// "slice"), or for whole items the medium portion, else the first one with a weight.
+fn count_weight(food: &FoodRecord, unit: &str) -> Option<f32> {
+    if WHOLE_ITEM_UNITS.contains(&unit) {
+        let whole_items: Vec<&FoodPortion> = food
+            .portions
+            .iter()
+            .filter(|p| matches!(parse_unit(&p.measure()), Unit::Count))
+            .collect();
+        return whole_items
+            .iter()
+            .find(|p| p.mentions("medium"))
+            .or(whole_items.first())
+            .map(|p| p.grams_per_unit());
+    }
+    food.portions.iter().find(|p| p.mentions(unit)).map(|p| p.grams_per_unit())
+}
+
+/// Converts `amount` `unit` of `food` to grams.
+pub fn to_grams(amount: f32, unit: &str, food: &FoodRecord) -> Result<f32, UnitConversionError> {
+    let error = |reason| UnitConversionError {
+        ingredient: food.description.clone(),
+        unit: unit.to_string(),
+        reason,
+    };
+    if !amount.is_finite() || amount < 0.0 {
+        return Err(error("the amount must not be negative"));
+    }
+
+    let normalized = normalize_unit(unit);
+    match parse_unit(unit) {
+        Unit::Mass(grams) => Ok(amount * grams),
+        Unit::Volume(ml) => density(food, &normalized)
+            .map(|g_per_ml| amount * ml * g_per_ml)
+            .ok_or_else(|| error("no density is known for this food; give the amount by weight")),
+        Unit::Count => count_weight(food, &normalized)
+            .map(|grams| amount * grams)
+            .ok_or_else(|| error("unknown unit, or no portion of this size is known for this food")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portion(amount: f32, measure_unit: Option<&str>, modifier: &str, gram_weight: f32) -> FoodPortion {
+        FoodPortion {
+            amount: Some(amount),
+            measure_unit: measure_unit.map(str::to_string),
+            modifier: Some(modifier.to_string()),
+            gram_weight,
+        }
+    }
+
+    fn food(description: &str, portions: Vec<FoodPortion>) -> FoodRecord {
+        FoodRecord {
+            fdc_id: 1,
+            description: description.to_string(),
+            food_category: None,
+            calories_kcal: None,
+            protein_g: None,
+            carbohydrates_g: None,
+            fat_g: None,
+            saturated_fat_g: None,
+            fiber_g: None,
+            sugar_g: None,
+            sodium_mg: None,
+            glycemic_index: None,
+            micronutrients: vec![],
+            portions,
+        }
+    }
+
+    #[test]
+    fn normalizes_abbreviations_and_plurals() {
+        assert_eq!(normalize_unit("Tbsps."), "tbsp");
+        assert_eq!(normalize_unit("cups"), "cup");
+        assert_eq!(normalize_unit("cloves"), "clove");
+        assert_eq!(normalize_unit("pinches"), "pinch");
+        assert_eq!(normalize_unit("dashes"), "dash");
+        assert_eq!(normalize_unit("fl.  oz"), "fl oz");
+        assert_eq!(normalize_unit("g"), "g");
+    }
+
+    #[test]
+    fn classifies_units() {
+        assert_eq!(parse_unit("kg"), Unit::Mass(1000.0));
+        assert_eq!(parse_unit("Ounces"), Unit::Mass(28.3495));
+        assert_eq!(parse_unit("cups"), Unit::Volume(236.588));
+        assert_eq!(parse_unit("ml"), Unit::Volume(1.0));
+        assert_eq!(parse_unit("cloves"), Unit::Count);
+        assert_eq!(parse_unit(""), Unit::Count);
+    }
+
+    #[test]
+    fn converts_volume_through_the_food_density() {
+        let flour = food("Wheat flour, white, all-purpose", vec![portion(1.0, None, "cup", 125.0)]);
+
+        let grams = to_grams(1.5, "cups", &flour).unwrap();
+        assert!((grams - 187.5).abs() < 0.01, "{}", grams);
+
+        // A tablespoon of the same food uses the cup's density
+        let grams = to_grams(1.0, "tbsp", &flour).unwrap();
+        assert!((grams - 125.0 / 236.588 * 14.7868).abs() < 0.01, "{}", grams);
+    }
+
+    #[test]
+    fn converts_counts_through_named_portions() {
+        let garlic = food("Garlic, raw", vec![portion(1.0, None, "clove", 3.0), portion(1.0, None, "cup", 136.0)]);
+        assert_eq!(to_grams(2.0, "cloves", &garlic).unwrap(), 6.0);
+
+        let egg = food(
+            "Egg, whole, raw, fresh",
+            vec![portion(1.0, None, "medium", 44.0), portion(1.0, None, "large", 50.0)],
+        );
+        assert_eq!(to_grams(1.0, "large", &egg).unwrap(), 50.0);
+        assert_eq!(to_grams(2.0, "", &egg).unwrap(), 88.0);
+    }
+
+    #[test]
+    fn mass_needs_no_portions() {
+        let rice = food("Rice, white, cooked", vec![]);
+        assert_eq!(to_grams(200.0, "g", &rice).unwrap(), 200.0);
+        assert_eq!(to_grams(0.5, "kg", &rice).unwrap(), 500.0);
+    }
+
+    #[test]
+    fn refuses_to_guess() {
+        let rice = food("Rice, white, cooked", vec![]);
+        assert!(to_grams(1.0, "cup", &rice).is_err());
+        assert!(to_grams(2.0, "handfuls", &rice).is_err());
+        assert!(to_grams(-1.0, "g", &rice).is_err());
+
+        // Water-like foods weigh 1 g/ml without a portion
+        let stock = food("Soup, stock, chicken", vec![]);
+        assert_eq!(to_grams(250.0, "ml", &stock).unwrap(), 250.0);
+    }
+}